@@ -1,22 +1,23 @@
 use battery::Battery;
 use chrono::Local;
 use device::CURRENT_DEVICE;
+use dictionary::Dictionary;
 use failure::{Error, ResultExt};
 use fnv::FnvHashMap;
 use font::Fonts;
-use framebuffer::{Framebuffer, UpdateMode};
+use framebuffer::{Framebuffer, UpdateMode, normalize_rotation};
 use frontlight::{FakeFrontlight, Frontlight, NaturalFrontlight, StandardFrontlight};
 use gesture::{GestureEvent, BUTTON_HOLD_DELAY};
 use helpers::{load_json, load_toml, save_json, save_toml};
 use input::{ButtonCode, ButtonStatus, DeviceEvent};
 use lightsensor::{KoboLightSensor, LightSensor};
-use metadata::{import, Metadata, METADATA_FILENAME};
-use settings::{Settings, SETTINGS_PATH};
+use metadata::{import, Metadata, TextIndex, METADATA_FILENAME};
+use settings::{GestureAction, Settings, SETTINGS_PATH};
 use std::collections::VecDeque;
 use std::fs::{self};
 use std::path::{Path, PathBuf};
 use std::process::Command;
-use std::sync::mpsc;
+use std::sync::{mpsc, Arc, Mutex};
 use std::thread;
 use std::time::{Duration, Instant};
 use view::common::{locate, locate_by_id, overlapping_rectangle};
@@ -35,6 +36,13 @@ pub const APP_NAME: &str = "Plato";
 const CLOCK_REFRESH_INTERVAL: Duration = Duration::from_secs(60);
 const BATTERY_REFRESH_INTERVAL: Duration = Duration::from_secs(299);
 
+// The state of the indexing thread: the metadata given while it runs is indexed next.
+#[derive(Default)]
+pub struct Indexing {
+    pub running: bool,
+    pub pending: Option<Metadata>,
+}
+
 pub struct Context {
     pub settings: Settings,
     pub metadata: Metadata,
@@ -43,6 +51,10 @@ pub struct Context {
     pub frontlight: Box<Frontlight>,
     pub battery: Box<Battery>,
     pub lightsensor: Box<LightSensor>,
+    pub dictionaries: Vec<Box<Dictionary>>,
+    // The full-text index, loaded on the first search, and updated by the indexing thread.
+    pub text_index: Arc<Mutex<Option<TextIndex>>>,
+    pub indexing: Arc<Mutex<Indexing>>,
     pub notification_index: u8,
     pub resumed_at: Instant,
     pub inverted: bool,
@@ -71,6 +83,9 @@ impl Context {
             battery,
             frontlight,
             lightsensor,
+            dictionaries: Vec::new(),
+            text_index: Arc::new(Mutex::new(None)),
+            indexing: Arc::new(Mutex::new(Indexing::default())),
             notification_index: 0,
             resumed_at: Instant::now(),
             inverted: false,
@@ -159,13 +174,66 @@ fn reload(context: &mut Context) {
     if metadata.is_ok() {
         context.metadata.append(&mut metadata.unwrap());
     }
+
+    if context.settings.import.text_index {
+        update_text_index(context);
+    }
+}
+
+// Opening every new book can take a while: only one thread updates the index at a time.
+fn update_text_index(context: &Context) {
+    {
+        let mut indexing = context.indexing.lock().unwrap();
+        if indexing.running {
+            indexing.pending = Some(context.metadata.clone());
+            return;
+        }
+        indexing.running = true;
+    }
+    let library_path = context.settings.library_path.clone();
+    let font_path = context.settings.reader.font_path.clone();
+    let mut metadata = context.metadata.clone();
+    let text_index = Arc::clone(&context.text_index);
+    let indexing = Arc::clone(&context.indexing);
+    thread::spawn(move || {
+        loop {
+            let mut index = TextIndex::load(&library_path);
+            let removed = index.retain(&metadata);
+            let added = index.add(&library_path, &metadata, &font_path);
+            if removed + added > 0 {
+                if let Err(e) = index.save(&library_path) {
+                    eprintln!("Can't save the text index: {}", e);
+                }
+            }
+            *text_index.lock().unwrap() = Some(index);
+            let mut indexing = indexing.lock().unwrap();
+            match indexing.pending.take() {
+                Some(pending) => metadata = pending,
+                None => {
+                    indexing.running = false;
+                    break;
+                },
+            }
+        }
+    });
 }
 
 
 pub fn run() -> Result<(), Error> {
     let mut context = build_context().context("Can't build context.")?;
+
+    if context.settings.import.text_index {
+        update_text_index(&context);
+    }
     let mut fb = CURRENT_DEVICE.create_framebuffer();
 
+    if context.settings.rotation != 0 {
+        match fb.set_rotation(context.settings.rotation) {
+            Ok(_) => CURRENT_DEVICE.set_rotation(context.settings.rotation),
+            Err(e) => eprintln!("Can't rotate the framebuffer: {}", e),
+        }
+    }
+
     let touch_screen = CURRENT_DEVICE.create_touchscreen();
     // let usb_port = usb_events();
 
@@ -209,7 +277,7 @@ pub fn run() -> Result<(), Error> {
         tx5.send(Event::BatteryTick).unwrap();
     });
 
-    let fb_rect = fb.rect();
+    let mut fb_rect = fb.rect();
 
     let mut history: Vec<Box<View>> = Vec::new();
     let mut view: Box<View> = Box::new(Home::new(fb_rect, &tx, &mut context)?);
@@ -388,6 +456,17 @@ pub fn run() -> Result<(), Error> {
                     fb.update(interm.rect(), UpdateMode::Full).ok();
                     break;
                 }
+                // Turning two fingers clockwise rotates the frame clockwise,
+                // unless the gesture is bound to an action of the reader.
+                GestureEvent::Rotate { quarter_turns, .. }
+                    if quarter_turns != 0
+                        && (view.downcast_ref::<Reader>().is_none()
+                            || context.settings.reader.gestures.rotate_action()
+                                == GestureAction::None) =>
+                {
+                    let n = CURRENT_DEVICE.rotation() - quarter_turns;
+                    tx.send(Event::Select(EntryId::Rotate(n))).unwrap();
+                }
                 _ => {
                     handle_event(view.as_mut(), &evt, &tx, &mut bus, &mut context);
                 }
@@ -448,6 +527,16 @@ pub fn run() -> Result<(), Error> {
                 history.push(view as Box<View>);
                 view = Box::new(r) as Box<View>;
             }
+            Event::OpenAnnotations(ref annotations, ref toc) => {
+                let r = Reader::from_annotations(fb_rect, annotations, toc, &tx, &mut context);
+                history.push(view as Box<View>);
+                view = Box::new(r) as Box<View>;
+            }
+            Event::OpenStatistics(ref report) => {
+                let r = Reader::from_statistics(fb_rect, report, &tx, &mut context);
+                history.push(view as Box<View>);
+                view = Box::new(r) as Box<View>;
+            }
             Event::Back => {
                 if let Some(v) = history.pop() {
                     view = v;
@@ -516,6 +605,25 @@ pub fn run() -> Result<(), Error> {
                     Command::new("scripts/wifi-disable.sh").spawn().ok();
                 }
             }
+            Event::Select(EntryId::Rotate(n)) => {
+                let n = normalize_rotation(n);
+                if n == CURRENT_DEVICE.rotation() {
+                    continue;
+                }
+                updating.retain(|tok, _| fb.wait(*tok).is_err());
+                if let Err(e) = fb.set_rotation(n) {
+                    eprintln!("Can't rotate the framebuffer: {}", e);
+                    continue;
+                }
+                CURRENT_DEVICE.set_rotation(n);
+                context.settings.rotation = n;
+                fb_rect = fb.rect();
+                for v in history.iter_mut() {
+                    v.resize(fb_rect, &tx, &mut context);
+                }
+                view.resize(fb_rect, &tx, &mut context);
+                tx.send(Event::Render(fb_rect, UpdateMode::Full)).unwrap();
+            }
             Event::Select(EntryId::TakeScreenshot) => {
                 let name = Local::now().format("screenshot-%Y%m%d_%H%M%S.png");
                 let msg = match fb.save(&name.to_string()) {
@@ -1,6 +1,7 @@
 extern crate libc;
 use std::fmt;
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use unit::scale_by_dpi;
 use input::{DeviceEvent, TouchProto, InputEvent, raw_events, device_events, remarkable_parse_device_events, kobo_parse_device_events};
 use gesture::gesture_events;
@@ -8,7 +9,7 @@ use view::Event;
 use std::sync::mpsc::{Sender, Receiver};
 use battery::{Battery, KoboBattery, RemarkableBattery};
 use failure::{ResultExt};
-use framebuffer::{Framebuffer, RemarkableFramebuffer, KoboFramebuffer};
+use framebuffer::{Framebuffer, RemarkableFramebuffer, KoboFramebuffer, normalize_rotation};
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
 pub enum Model {
@@ -52,12 +53,32 @@ pub struct Device {
     pub mirrored_x: bool,
     pub dims: (u32, u32),
     pub dpi: u16,
+    rotation: AtomicUsize,
 }
 
 
 
 impl Device {
 
+    // Number of clockwise quarter turns applied to the display.
+    pub fn rotation(&self) -> i8 {
+        self.rotation.load(Ordering::Relaxed) as i8
+    }
+
+    pub fn set_rotation(&self, n: i8) {
+        self.rotation.store(normalize_rotation(n) as usize, Ordering::Relaxed);
+    }
+
+    // The dimensions of the display, as seen by the views.
+    pub fn display_dims(&self) -> (u32, u32) {
+        let (width, height) = self.dims;
+        if self.rotation() % 2 == 1 {
+            (height, width)
+        } else {
+            (width, height)
+        }
+    }
+
     pub fn has_light(&self) -> bool {
         match self.model {
             Model::Remarkable => false,
@@ -143,6 +164,7 @@ lazy_static! {
                 mirrored_x: true,
                 dims: (1404, 1872),
                 dpi: 226,
+                rotation: AtomicUsize::new(0),
             }
     };
 
@@ -155,6 +177,7 @@ lazy_static! {
 pub static ref BAR_SIZES: HashMap<(u32, u16), (u32, u32)> =
     [
     ((1872, 226), (91, 123)),
+    ((1404, 226), (93, 125)),
     ].iter().cloned().collect();
 }
 
@@ -0,0 +1,104 @@
+use std::fs;
+use std::path::Path;
+use fnv::FnvHashMap;
+use failure::{Error, ResultExt};
+use document::escape_html;
+use super::Dictionary;
+use super::dictzip::DictFile;
+
+const SHORT_NAME_ENTRY: &str = "00-database-short";
+
+// The *.index* and *.dict(.dz)* files of a dictd dictionary.
+pub struct DictD {
+    name: String,
+    index: FnvHashMap<String, Vec<(u64, u32)>>,
+    data: DictFile,
+}
+
+impl DictD {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<DictD, Error> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path).context("Can't read index file.")?;
+        let mut index: FnvHashMap<String, Vec<(u64, u32)>> = FnvHashMap::default();
+
+        for line in text.lines() {
+            let fields: Vec<&str> = line.split('\t').collect();
+            if fields.len() < 3 {
+                continue;
+            }
+            if let (Some(offset), Some(size)) = (decode_base64(fields[1]), decode_base64(fields[2])) {
+                index.entry(fields[0].to_lowercase())
+                     .or_insert_with(Vec::new)
+                     .push((offset, size as u32));
+            }
+        }
+
+        let data_path = [path.with_extension("dict.dz"), path.with_extension("dict")]
+                            .iter().find(|p| p.exists()).cloned()
+                            .ok_or_else(|| format_err!("Missing data file."))?;
+        let data = DictFile::open(&data_path)?;
+
+        // The short name entry holds the headword on its first line.
+        let name = index.get(SHORT_NAME_ENTRY).and_then(|v| v.first()).cloned()
+                        .and_then(|(offset, size)| data.read(offset, size as usize).ok())
+                        .and_then(|bytes| {
+                            String::from_utf8_lossy(&bytes).lines()
+                                                           .map(str::trim)
+                                                           .find(|l| !l.is_empty() && *l != SHORT_NAME_ENTRY)
+                                                           .map(String::from)
+                        })
+                        .or_else(|| path.file_stem().map(|s| s.to_string_lossy().into_owned()))
+                        .ok_or_else(|| format_err!("Missing database name."))?;
+
+        Ok(DictD { name, index, data })
+    }
+}
+
+impl Dictionary for DictD {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn lookup(&self, word: &str) -> Result<Vec<String>, Error> {
+        let mut result = Vec::new();
+        if let Some(entries) = self.index.get(&word.to_lowercase()) {
+            for &(offset, size) in entries {
+                let entry = self.data.read(offset, size as usize)?;
+                let text = String::from_utf8_lossy(&entry);
+                result.push(format!("<div class=\"text\">{}</div>", escape_html(text.trim())));
+            }
+        }
+        Ok(result)
+    }
+}
+
+// Offsets and sizes are written in base 64, most significant digit first.
+fn decode_base64(text: &str) -> Option<u64> {
+    let mut value: u64 = 0;
+    for b in text.bytes() {
+        let digit = match b {
+            b'A'..=b'Z' => b - b'A',
+            b'a'..=b'z' => b - b'a' + 26,
+            b'0'..=b'9' => b - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            _ => return None,
+        };
+        value = value.checked_mul(64)? + u64::from(digit);
+    }
+    Some(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_base64() {
+        assert_eq!(decode_base64("A"), Some(0));
+        assert_eq!(decode_base64("/"), Some(63));
+        assert_eq!(decode_base64("BA"), Some(64));
+        assert_eq!(decode_base64("c2"), Some(28 * 64 + 54));
+        assert_eq!(decode_base64("a-b"), None);
+    }
+}
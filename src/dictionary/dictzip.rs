@@ -0,0 +1,171 @@
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::cell::RefCell;
+use std::path::Path;
+use flate2::{Decompress, FlushDecompress};
+use flate2::read::GzDecoder;
+use failure::{Error, ResultExt};
+
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+const FLAG_HCRC: u8 = 0x02;
+const FLAG_EXTRA: u8 = 0x04;
+const FLAG_NAME: u8 = 0x08;
+const FLAG_COMMENT: u8 = 0x10;
+
+// The data file of a dictionary: plain, gzipped or dictzipped.
+// Dictzip files are gzip files whose deflate stream is flushed at regular intervals,
+// the sizes of the resulting chunks are stored in the *RA* extra field of the header.
+pub enum DictFile {
+    Plain(RefCell<File>),
+    Memory(Vec<u8>),
+    Chunked(DictZip),
+}
+
+pub struct DictZip {
+    file: RefCell<File>,
+    chunk_length: usize,
+    // The offsets of the chunks within the file, followed by the end of the last chunk.
+    offsets: Vec<u64>,
+}
+
+impl DictFile {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<DictFile, Error> {
+        let mut file = File::open(path.as_ref()).context("Can't open dictionary data.")?;
+        let mut magic = [0u8; 2];
+
+        if file.read_exact(&mut magic).is_err() || magic != GZIP_MAGIC {
+            file.seek(SeekFrom::Start(0))?;
+            return Ok(DictFile::Plain(RefCell::new(file)));
+        }
+
+        file.seek(SeekFrom::Start(0))?;
+
+        if let Some((chunk_length, sizes)) = chunks_table(&mut file)? {
+            let start = file.seek(SeekFrom::Current(0))?;
+            let mut offsets = Vec::with_capacity(sizes.len() + 1);
+            offsets.push(start);
+            for size in sizes {
+                let last = *offsets.last().unwrap();
+                offsets.push(last + size as u64);
+            }
+            Ok(DictFile::Chunked(DictZip {
+                file: RefCell::new(file),
+                chunk_length,
+                offsets,
+            }))
+        } else {
+            file.seek(SeekFrom::Start(0))?;
+            let mut buf = Vec::new();
+            GzDecoder::new(file).read_to_end(&mut buf)
+                                .context("Can't decompress dictionary data.")?;
+            Ok(DictFile::Memory(buf))
+        }
+    }
+
+    pub fn read(&self, offset: u64, size: usize) -> Result<Vec<u8>, Error> {
+        match *self {
+            DictFile::Plain(ref file) => {
+                let mut file = file.borrow_mut();
+                let mut buf = vec![0; size];
+                file.seek(SeekFrom::Start(offset))?;
+                file.read_exact(&mut buf).context("Can't read dictionary data.")?;
+                Ok(buf)
+            },
+            DictFile::Memory(ref data) => {
+                let start = (offset as usize).min(data.len());
+                let end = (start + size).min(data.len());
+                Ok(data[start..end].to_vec())
+            },
+            DictFile::Chunked(ref dz) => dz.read(offset, size),
+        }
+    }
+}
+
+impl DictZip {
+    fn read(&self, offset: u64, size: usize) -> Result<Vec<u8>, Error> {
+        if size == 0 {
+            return Ok(Vec::new());
+        }
+
+        let chunks_count = self.offsets.len() - 1;
+        let first = offset as usize / self.chunk_length;
+        let last = ((offset as usize + size - 1) / self.chunk_length).min(chunks_count.saturating_sub(1));
+
+        if first >= chunks_count {
+            return Err(format_err!("Offset out of range: {}.", offset));
+        }
+
+        let mut buf = Vec::with_capacity((last - first + 1) * self.chunk_length);
+        let mut file = self.file.borrow_mut();
+
+        for index in first..=last {
+            let mut input = vec![0; (self.offsets[index+1] - self.offsets[index]) as usize];
+            file.seek(SeekFrom::Start(self.offsets[index]))?;
+            file.read_exact(&mut input).context("Can't read chunk.")?;
+            let mut output = Vec::with_capacity(self.chunk_length);
+            // Each chunk ends with a full flush: it can be inflated on its own.
+            Decompress::new(false).decompress_vec(&input, &mut output, FlushDecompress::Sync)
+                                  .context("Can't inflate chunk.")?;
+            buf.extend_from_slice(&output);
+        }
+
+        let start = (offset as usize - first * self.chunk_length).min(buf.len());
+        let end = (start + size).min(buf.len());
+        Ok(buf[start..end].to_vec())
+    }
+}
+
+// Parses the gzip header and returns the chunk length and the sizes of the chunks.
+// The file is left at the beginning of the compressed data.
+fn chunks_table(file: &mut File) -> Result<Option<(usize, Vec<u16>)>, Error> {
+    let mut header = [0u8; 10];
+    file.read_exact(&mut header).context("Can't read gzip header.")?;
+    let flags = header[3];
+    let mut table = None;
+
+    if flags & FLAG_EXTRA != 0 {
+        let mut len = [0u8; 2];
+        file.read_exact(&mut len)?;
+        let mut extra = vec![0; u16_le(&len) as usize];
+        file.read_exact(&mut extra).context("Can't read extra field.")?;
+        let mut i = 0;
+        while i + 4 <= extra.len() {
+            let sub_len = u16_le(&extra[i+2..i+4]) as usize;
+            let data = &extra[i+4..(i+4+sub_len).min(extra.len())];
+            if &extra[i..i+2] == b"RA" && data.len() >= 6 {
+                let chunk_length = u16_le(&data[2..4]) as usize;
+                let count = u16_le(&data[4..6]) as usize;
+                let sizes: Vec<u16> = data[6..].chunks(2).take(count)
+                                               .filter(|c| c.len() == 2)
+                                               .map(u16_le).collect();
+                if chunk_length > 0 && sizes.len() == count {
+                    table = Some((chunk_length, sizes));
+                }
+            }
+            i += 4 + sub_len;
+        }
+    }
+
+    for &flag in &[FLAG_NAME, FLAG_COMMENT] {
+        if flags & flag != 0 {
+            let mut byte = [0u8; 1];
+            loop {
+                file.read_exact(&mut byte)?;
+                if byte[0] == 0 {
+                    break;
+                }
+            }
+        }
+    }
+
+    if flags & FLAG_HCRC != 0 {
+        file.seek(SeekFrom::Current(2))?;
+    }
+
+    Ok(table)
+}
+
+#[inline]
+fn u16_le(bytes: &[u8]) -> u16 {
+    u16::from(bytes[0]) | u16::from(bytes[1]) << 8
+}
@@ -0,0 +1,131 @@
+mod dictzip;
+mod stardict;
+mod dictd;
+
+use std::path::Path;
+use glob::glob;
+use failure::Error;
+use self::stardict::StarDict;
+use self::dictd::DictD;
+
+pub const DICTIONARIES_DIRNAME: &str = "dictionaries";
+
+pub trait Dictionary {
+    fn name(&self) -> &str;
+    // Returns the HTML definitions of the given headword.
+    fn lookup(&self, word: &str) -> Result<Vec<String>, Error>;
+}
+
+pub struct Definition {
+    pub dictionary: String,
+    pub headword: String,
+    pub texts: Vec<String>,
+}
+
+// Loads the StarDict and dictd dictionaries found within *dir*.
+pub fn load_dictionaries<P: AsRef<Path>>(dir: P) -> Vec<Box<Dictionary>> {
+    let mut dictionaries = Vec::new();
+    load_aux(dir.as_ref(), "ifo", open_stardict, &mut dictionaries);
+    load_aux(dir.as_ref(), "index", open_dictd, &mut dictionaries);
+    dictionaries.sort_by(|a, b| a.name().cmp(b.name()));
+    dictionaries
+}
+
+fn load_aux<F>(dir: &Path, extension: &str, open: F, dictionaries: &mut Vec<Box<Dictionary>>)
+               where F: Fn(&Path) -> Result<Box<Dictionary>, Error> {
+    let pattern = dir.join(format!("**/*.{}", extension));
+    if let Ok(paths) = glob(&pattern.to_string_lossy()) {
+        for path in paths.filter_map(|p| p.ok()) {
+            match open(&path) {
+                Ok(dictionary) => dictionaries.push(dictionary),
+                Err(e) => eprintln!("Can't load dictionary {}: {}", path.display(), e),
+            }
+        }
+    }
+}
+
+fn open_stardict(path: &Path) -> Result<Box<Dictionary>, Error> {
+    StarDict::open(path).map(|d| Box::new(d) as Box<Dictionary>)
+}
+
+fn open_dictd(path: &Path) -> Result<Box<Dictionary>, Error> {
+    DictD::open(path).map(|d| Box::new(d) as Box<Dictionary>)
+}
+
+// Looks up *word* and, when it isn't found, its likely lemmas.
+pub fn lookup(dictionary: &Dictionary, word: &str) -> Result<Option<Definition>, Error> {
+    for candidate in candidates(word) {
+        let texts = dictionary.lookup(&candidate)?;
+        if !texts.is_empty() {
+            return Ok(Some(Definition {
+                dictionary: dictionary.name().to_string(),
+                headword: candidate,
+                texts,
+            }));
+        }
+    }
+    Ok(None)
+}
+
+const SUFFIXES: [(&str, &str); 15] = [
+    ("'s", ""), ("’s", ""),
+    ("ies", "y"), ("ied", "y"), ("ves", "f"), ("sses", "ss"),
+    ("es", ""), ("s", ""), ("ed", "e"), ("ed", ""),
+    ("ing", "e"), ("ing", ""), ("est", ""), ("er", ""), ("ly", ""),
+];
+
+// The words to try, in order: the word itself, its lowercase form,
+// and the forms obtained by stripping common English inflections.
+pub fn candidates(word: &str) -> Vec<String> {
+    let word = word.trim_matches(|c: char| !c.is_alphanumeric());
+    let mut result: Vec<String> = Vec::new();
+
+    if word.is_empty() {
+        return result;
+    }
+
+    let lower = word.to_lowercase();
+
+    for w in &[word, lower.as_str()] {
+        if !result.iter().any(|r| r == w) {
+            result.push(w.to_string());
+        }
+    }
+
+    for &(suffix, replacement) in &SUFFIXES {
+        if lower.ends_with(suffix) && lower.chars().count() > suffix.chars().count() + 1 {
+            let stem = &lower[..lower.len() - suffix.len()];
+            let mut forms = vec![format!("{}{}", stem, replacement)];
+            // Undo the doubling of the final consonant, e.g.: stopped → stop.
+            if replacement.is_empty() && ["ed", "ing", "er", "est"].contains(&suffix) {
+                let mut chars = stem.chars().rev();
+                if let (Some(a), Some(b)) = (chars.next(), chars.next()) {
+                    if a == b && !"aeiou".contains(a) {
+                        forms.push(stem[..stem.len() - a.len_utf8()].to_string());
+                    }
+                }
+            }
+            for form in forms {
+                if !result.contains(&form) {
+                    result.push(form);
+                }
+            }
+        }
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_candidates() {
+        assert_eq!(candidates("“Cats,”"), vec!["Cats", "cats", "cat"]);
+        assert!(candidates("stopped").contains(&"stop".to_string()));
+        assert!(candidates("ladies").contains(&"lady".to_string()));
+        assert!(candidates("making").contains(&"make".to_string()));
+        assert!(candidates("...").is_empty());
+    }
+}
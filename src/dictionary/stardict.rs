@@ -0,0 +1,195 @@
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use fnv::FnvHashMap;
+use flate2::read::GzDecoder;
+use failure::{Error, ResultExt};
+use document::escape_html;
+use super::Dictionary;
+use super::dictzip::DictFile;
+
+// The *.ifo*, *.idx(.gz)*, *.syn* and *.dict(.dz)* files of a StarDict dictionary.
+pub struct StarDict {
+    name: String,
+    same_type_sequence: Option<String>,
+    index: FnvHashMap<String, Vec<(u64, u32)>>,
+    data: DictFile,
+}
+
+impl StarDict {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<StarDict, Error> {
+        let path = path.as_ref();
+        let info = fs::read_to_string(path).context("Can't read info file.")?;
+        let mut name = None;
+        let mut offset_bits = 32;
+        let mut same_type_sequence = None;
+
+        for line in info.lines() {
+            let mut kv = line.splitn(2, '=');
+            match (kv.next().map(str::trim), kv.next().map(str::trim)) {
+                (Some("bookname"), Some(v)) => name = Some(v.to_string()),
+                (Some("idxoffsetbits"), Some(v)) => offset_bits = v.parse().unwrap_or(32),
+                (Some("sametypesequence"), Some(v)) if !v.is_empty() => same_type_sequence = Some(v.to_string()),
+                _ => (),
+            }
+        }
+
+        let name = name.or_else(|| path.file_stem().map(|s| s.to_string_lossy().into_owned()))
+                       .ok_or_else(|| format_err!("Missing book name."))?;
+
+        let idx = read_file(&[path.with_extension("idx")],
+                            &[path.with_extension("idx.gz")])
+                      .context("Can't read index file.")?;
+        let syn = read_file(&[path.with_extension("syn")], &[]).ok();
+
+        let mut words = Vec::new();
+        let mut index: FnvHashMap<String, Vec<(u64, u32)>> = FnvHashMap::default();
+        let offset_size = if offset_bits == 64 { 8 } else { 4 };
+        let mut i = 0;
+
+        while let Some(len) = idx[i..].iter().position(|&b| b == 0) {
+            let word = String::from_utf8_lossy(&idx[i..i+len]).to_lowercase();
+            i += len + 1;
+            if i + offset_size + 4 > idx.len() {
+                break;
+            }
+            let offset = if offset_size == 8 {
+                u64_be(&idx[i..i+8])
+            } else {
+                u64::from(u32_be(&idx[i..i+4]))
+            };
+            let size = u32_be(&idx[i+offset_size..i+offset_size+4]);
+            i += offset_size + 4;
+            index.entry(word.clone()).or_insert_with(Vec::new).push((offset, size));
+            words.push(word);
+        }
+
+        // Synonyms refer to entries of the index file by position.
+        if let Some(syn) = syn {
+            let mut i = 0;
+            while let Some(len) = syn[i..].iter().position(|&b| b == 0) {
+                let word = String::from_utf8_lossy(&syn[i..i+len]).to_lowercase();
+                i += len + 1;
+                if i + 4 > syn.len() {
+                    break;
+                }
+                let position = u32_be(&syn[i..i+4]) as usize;
+                i += 4;
+                if let Some(entry) = words.get(position).and_then(|w| index.get(w))
+                                          .and_then(|v| v.first()).cloned() {
+                    let entries = index.entry(word).or_insert_with(Vec::new);
+                    if !entries.contains(&entry) {
+                        entries.push(entry);
+                    }
+                }
+            }
+        }
+
+        let data_path = [path.with_extension("dict.dz"), path.with_extension("dict")]
+                            .iter().find(|p| p.exists()).cloned()
+                            .ok_or_else(|| format_err!("Missing data file."))?;
+        let data = DictFile::open(&data_path)?;
+
+        Ok(StarDict { name, same_type_sequence, index, data })
+    }
+
+    fn format(&self, entry: &[u8]) -> String {
+        let mut html = String::new();
+
+        if let Some(ref types) = self.same_type_sequence {
+            let mut rest = entry;
+            let count = types.len();
+            for (n, t) in types.chars().enumerate() {
+                // The size of the last field is implied by the size of the entry.
+                let (field, next) = if n == count - 1 {
+                    (rest, &rest[rest.len()..])
+                } else {
+                    split_field(t, rest)
+                };
+                push_field(t, field, &mut html);
+                rest = next;
+            }
+        } else {
+            let mut rest = entry;
+            while !rest.is_empty() {
+                let t = rest[0] as char;
+                let (field, next) = split_field(t, &rest[1..]);
+                push_field(t, field, &mut html);
+                rest = next;
+            }
+        }
+
+        html
+    }
+}
+
+impl Dictionary for StarDict {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn lookup(&self, word: &str) -> Result<Vec<String>, Error> {
+        let mut result = Vec::new();
+        if let Some(entries) = self.index.get(&word.to_lowercase()) {
+            for &(offset, size) in entries {
+                let entry = self.data.read(offset, size as usize)?;
+                result.push(self.format(&entry));
+            }
+        }
+        Ok(result)
+    }
+}
+
+// Lowercase types are terminated by a null byte, uppercase types are prefixed with their size.
+fn split_field(t: char, bytes: &[u8]) -> (&[u8], &[u8]) {
+    if t.is_ascii_uppercase() {
+        if bytes.len() < 4 {
+            return (&bytes[bytes.len()..], &bytes[bytes.len()..]);
+        }
+        let size = (u32_be(&bytes[..4]) as usize).min(bytes.len() - 4);
+        (&bytes[4..4+size], &bytes[4+size..])
+    } else {
+        let len = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+        (&bytes[..len], &bytes[(len+1).min(bytes.len())..])
+    }
+}
+
+fn push_field(t: char, field: &[u8], html: &mut String) {
+    let text = String::from_utf8_lossy(field);
+    match t {
+        'm' | 'l' | 't' | 'y' => {
+            html.push_str("<div class=\"text\">");
+            html.push_str(&escape_html(text.trim()));
+            html.push_str("</div>");
+        },
+        'h' | 'g' | 'x' => {
+            html.push_str("<div>");
+            html.push_str(&text);
+            html.push_str("</div>");
+        },
+        _ => (),
+    }
+}
+
+fn read_file(plain: &[PathBuf], gzipped: &[PathBuf]) -> Result<Vec<u8>, Error> {
+    let mut buf = Vec::new();
+    if let Some(path) = plain.iter().find(|p| p.exists()) {
+        File::open(path)?.read_to_end(&mut buf)?;
+    } else if let Some(path) = gzipped.iter().find(|p| p.exists()) {
+        GzDecoder::new(File::open(path)?).read_to_end(&mut buf)?;
+    } else {
+        return Err(format_err!("File not found."));
+    }
+    Ok(buf)
+}
+
+#[inline]
+fn u32_be(bytes: &[u8]) -> u32 {
+    u32::from(bytes[0]) << 24 | u32::from(bytes[1]) << 16 |
+    u32::from(bytes[2]) << 8 | u32::from(bytes[3])
+}
+
+#[inline]
+fn u64_be(bytes: &[u8]) -> u64 {
+    u64::from(u32_be(&bytes[..4])) << 32 | u64::from(u32_be(&bytes[4..8]))
+}
@@ -0,0 +1,119 @@
+use std::fs;
+use std::path::Path;
+use fnv::FnvHashMap;
+use failure::{Error, ResultExt};
+
+pub const SOFT_HYPHEN: char = '\u{00AD}';
+
+// Minimum number of characters before and after a hyphenation point.
+const LEFT_MIN: usize = 2;
+const RIGHT_MIN: usize = 3;
+
+// Frank Liang's hyphenation algorithm, fed with TeX patterns.
+pub struct Hyphenator {
+    patterns: FnvHashMap<String, Vec<u8>>,
+    max_length: usize,
+}
+
+impl Hyphenator {
+    pub fn from_patterns(text: &str) -> Hyphenator {
+        let mut patterns = FnvHashMap::default();
+        let mut max_length = 0;
+
+        for line in text.lines() {
+            let line = line.split('%').next().unwrap_or("");
+            for pattern in line.split_whitespace() {
+                let mut letters = String::new();
+                let mut values = vec![0];
+                for c in pattern.chars() {
+                    if let Some(d) = c.to_digit(10) {
+                        *values.last_mut().unwrap() = d as u8;
+                    } else {
+                        letters.push(c);
+                        values.push(0);
+                    }
+                }
+                max_length = max_length.max(values.len() - 1);
+                patterns.insert(letters, values);
+            }
+        }
+
+        Hyphenator { patterns, max_length }
+    }
+
+    // Looks for *hyphenation/LANG.pat*, then falls back to the primary subtag of *LANG*.
+    pub fn for_language<P: AsRef<Path>>(dir: P, language: &str) -> Result<Hyphenator, Error> {
+        let language = language.to_lowercase();
+        let primary = language.split(|c| c == '-' || c == '_').next().unwrap_or("");
+        let path = [&language[..], primary].iter()
+                       .map(|l| dir.as_ref().join(format!("{}.pat", l)))
+                       .find(|p| p.exists())
+                       .ok_or_else(|| format_err!("No hyphenation patterns for {}.", language))?;
+        let text = fs::read_to_string(path).context("Can't read hyphenation patterns.")?;
+        Ok(Hyphenator::from_patterns(&text))
+    }
+
+    // Returns the byte offsets, within *word*, where the word can be broken.
+    pub fn hyphenate(&self, word: &str) -> Vec<usize> {
+        let chars: Vec<(usize, char)> = word.char_indices().collect();
+        let len = chars.len();
+
+        if len < LEFT_MIN + RIGHT_MIN || !chars.iter().all(|&(_, c)| c.is_alphabetic()) {
+            return Vec::new();
+        }
+
+        let extended: Vec<char> = Some('.').into_iter()
+                                           .chain(word.chars().flat_map(|c| c.to_lowercase()))
+                                           .chain(Some('.'))
+                                           .collect();
+        // Lowercasing might change the number of characters.
+        if extended.len() != len + 2 {
+            return Vec::new();
+        }
+
+        let n = extended.len();
+        let mut points = vec![0u8; n + 1];
+        let mut key = String::new();
+
+        for i in 0..n {
+            key.clear();
+            for c in &extended[i..n.min(i + self.max_length)] {
+                key.push(*c);
+                if let Some(values) = self.patterns.get(&key) {
+                    for (k, v) in values.iter().enumerate() {
+                        points[i + k] = points[i + k].max(*v);
+                    }
+                }
+            }
+        }
+
+        (LEFT_MIN..=len - RIGHT_MIN).filter(|&p| points[p + 1] % 2 == 1)
+                                    .map(|p| chars[p].0)
+                                    .collect()
+    }
+}
+
+// Returns the byte offsets of the soft hyphens contained in *word*.
+pub fn soft_hyphens(word: &str) -> Vec<usize> {
+    word.char_indices().filter(|&(_, c)| c == SOFT_HYPHEN)
+        .map(|(i, _)| i).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hyphenate() {
+        let hyphenator = Hyphenator::from_patterns("hy3ph he2n hena4 hen5at 1na n2at\n1tio 2io o2n % Liang");
+        assert_eq!(hyphenator.hyphenate("hyphenation"), vec![2, 6]);
+        assert_eq!(hyphenator.hyphenate("Hyphenation"), vec![2, 6]);
+        assert!(hyphenator.hyphenate("hyphenation,").is_empty());
+        assert!(hyphenator.hyphenate("hyph").is_empty());
+    }
+
+    #[test]
+    fn test_soft_hyphens() {
+        assert_eq!(soft_hyphens("hy\u{00AD}phen\u{00AD}ation"), vec![2, 8]);
+    }
+}
@@ -0,0 +1,745 @@
+use std::mem;
+use fnv::FnvHashMap;
+use font::{Font, FontFamily, Variant, font_from_variant};
+use framebuffer::Framebuffer;
+use color::BLACK;
+use geom::Point;
+use super::xml::{Element, Node};
+use super::hyphenation::{Hyphenator, SOFT_HYPHEN, soft_hyphens};
+use super::resolve_path;
+
+// Sizes are expressed as percentages of the em.
+pub const NORMAL_SIZE: u8 = 100;
+const SMALL_SIZE: u8 = 70;
+const BIG_SIZE: u8 = 120;
+const HEADING_SIZES: [u8; 6] = [150, 130, 115, 100, 100, 100];
+
+// Vertical spaces and horizontal offsets, in ems.
+const HEADING_SPACE_BEFORE: f32 = 1.5;
+const HEADING_SPACE_AFTER: f32 = 0.75;
+const BLOCK_SPACE: f32 = 0.5;
+const BLOCK_INDENT: f32 = 1.5;
+const FIRST_LINE_INDENT: f32 = 1.0;
+
+const SCENE_BREAK: &str = "* * *";
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct TextStyle {
+    pub variant: Variant,
+    pub size: u8,
+}
+
+impl Default for TextStyle {
+    fn default() -> TextStyle {
+        TextStyle {
+            variant: Variant::REGULAR,
+            size: NORMAL_SIZE,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Run {
+    pub text: String,
+    pub style: TextStyle,
+    pub uri: Option<String>,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Align {
+    Left,
+    Center,
+    Justify,
+}
+
+#[derive(Debug, Clone)]
+pub struct Block {
+    pub runs: Vec<Run>,
+    pub align: Align,
+    pub indent: bool,
+    pub margin_left: f32,
+    pub space_before: f32,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Chapter {
+    pub blocks: Vec<Block>,
+    // Maps element identifiers to block indices.
+    pub anchors: FnvHashMap<String, usize>,
+}
+
+#[derive(Debug, Clone)]
+pub struct LineItem {
+    pub text: String,
+    // Index of the run, within the block, this item comes from.
+    pub run: usize,
+    pub x: i32,
+    pub width: i32,
+    pub hyphen: bool,
+    space: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct Line {
+    pub block: usize,
+    // Index of the line within its block.
+    pub offset: usize,
+    pub left: i32,
+    pub items: Vec<LineItem>,
+    pub ascent: i32,
+    pub height: i32,
+    pub space_before: i32,
+}
+
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
+pub struct PageStart {
+    pub chapter: usize,
+    pub block: usize,
+    pub offset: usize,
+    pub line: usize,
+}
+
+#[derive(Debug, Clone)]
+struct Context {
+    style: TextStyle,
+    uri: Option<String>,
+    margin_left: f32,
+    align: Align,
+    preformatted: bool,
+    ordered: bool,
+}
+
+#[derive(Debug, Copy, Clone)]
+struct Format {
+    align: Align,
+    indent: bool,
+    space_before: f32,
+    space_after: f32,
+    heading: bool,
+}
+
+const DEFAULT_FORMAT: Format = Format {
+    align: Align::Justify,
+    indent: false,
+    space_before: 0.0,
+    space_after: 0.0,
+    heading: false,
+};
+
+struct Builder<'a> {
+    path: &'a str,
+    chapter: Chapter,
+    runs: Vec<Run>,
+    formats: Vec<Format>,
+    counters: Vec<usize>,
+    pending_space: f32,
+    no_indent: bool,
+}
+
+pub fn build_chapter(root: &Element, path: &str) -> Chapter {
+    let body = root.find("body").unwrap_or(root);
+    let mut builder = Builder {
+        path,
+        chapter: Chapter::default(),
+        runs: Vec::new(),
+        formats: vec![DEFAULT_FORMAT],
+        counters: Vec::new(),
+        pending_space: 0.0,
+        no_indent: true,
+    };
+    let ctx = Context {
+        style: TextStyle::default(),
+        uri: None,
+        margin_left: 0.0,
+        align: Align::Justify,
+        preformatted: false,
+        ordered: false,
+    };
+    builder.walk(body, &ctx);
+    builder.flush(&ctx);
+    builder.chapter
+}
+
+fn heading_level(name: &str) -> Option<usize> {
+    match name {
+        "h1" => Some(1),
+        "h2" => Some(2),
+        "h3" => Some(3),
+        "h4" => Some(4),
+        "h5" => Some(5),
+        "h6" => Some(6),
+        _ => None,
+    }
+}
+
+fn is_block(name: &str) -> bool {
+    match name {
+        "p" | "div" | "section" | "article" | "aside" | "header" | "footer" | "nav" |
+        "main" | "blockquote" | "li" | "ul" | "ol" | "dl" | "dt" | "dd" | "pre" |
+        "figure" | "figcaption" | "table" | "tr" | "caption" | "center" | "address" |
+        "hgroup" | "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => true,
+        _ => false,
+    }
+}
+
+#[inline]
+fn is_xml_space(c: char) -> bool {
+    c == ' ' || c == '\t' || c == '\n' || c == '\r'
+}
+
+impl<'a> Builder<'a> {
+    fn walk(&mut self, element: &Element, ctx: &Context) {
+        for node in &element.children {
+            match *node {
+                Node::Text(ref text) => self.push_text(text, ctx),
+                Node::Element(ref child) => self.walk_element(child, ctx),
+            }
+        }
+    }
+
+    fn walk_element(&mut self, element: &Element, parent_ctx: &Context) {
+        let name = element.local_name().to_lowercase();
+        let mut ctx = parent_ctx.clone();
+
+        match name.as_ref() {
+            "head" | "script" | "style" | "title" => return,
+            "br" => {
+                self.push_raw("\n", &ctx);
+                return;
+            },
+            "hr" => {
+                self.flush(parent_ctx);
+                self.record_anchor(element);
+                self.chapter.blocks.push(Block {
+                    runs: vec![Run { text: SCENE_BREAK.to_string(), style: ctx.style, uri: None }],
+                    align: Align::Center,
+                    indent: false,
+                    margin_left: ctx.margin_left,
+                    space_before: self.pending_space.max(BLOCK_SPACE),
+                });
+                self.pending_space = BLOCK_SPACE;
+                self.no_indent = true;
+                return;
+            },
+            "img" | "image" => {
+                if let Some(alt) = element.attribute("alt").filter(|a| !a.trim().is_empty()) {
+                    ctx.style.variant.insert(Variant::ITALIC);
+                    self.push_text(&format!("[{}]", alt.trim()), &ctx);
+                }
+                return;
+            },
+            "i" | "em" | "cite" | "dfn" | "var" => ctx.style.variant.insert(Variant::ITALIC),
+            "b" | "strong" | "th" => ctx.style.variant.insert(Variant::BOLD),
+            "sup" | "sub" | "small" => ctx.style.size = (ctx.style.size as u32 * SMALL_SIZE as u32 / 100) as u8,
+            "big" => ctx.style.size = (ctx.style.size as u32 * BIG_SIZE as u32 / 100).min(255) as u8,
+            "a" => {
+                if let Some(href) = element.attribute("href") {
+                    ctx.uri = Some(resolve_path(self.path, href));
+                }
+            },
+            "td" => self.push_text(" ", &ctx),
+            _ => (),
+        }
+
+        if !is_block(&name) {
+            self.record_anchor(element);
+            self.walk(element, &ctx);
+            return;
+        }
+
+        self.flush(parent_ctx);
+        self.record_anchor(element);
+
+        let mut format = DEFAULT_FORMAT;
+
+        if let Some(level) = heading_level(&name) {
+            ctx.style.variant.insert(Variant::BOLD);
+            ctx.style.size = HEADING_SIZES[level - 1];
+            format.align = Align::Left;
+            format.space_before = HEADING_SPACE_BEFORE;
+            format.space_after = HEADING_SPACE_AFTER;
+            format.heading = true;
+        } else {
+            match name.as_ref() {
+                "p" => format.indent = true,
+                "blockquote" | "dd" => {
+                    ctx.margin_left += BLOCK_INDENT;
+                    format.space_before = BLOCK_SPACE;
+                    format.space_after = BLOCK_SPACE;
+                },
+                "ul" | "ol" => {
+                    ctx.margin_left += BLOCK_INDENT;
+                    ctx.ordered = name == "ol";
+                    format.space_before = BLOCK_SPACE;
+                    format.space_after = BLOCK_SPACE;
+                    self.counters.push(0);
+                },
+                "pre" => {
+                    ctx.preformatted = true;
+                    format.align = Align::Left;
+                    format.space_before = BLOCK_SPACE;
+                    format.space_after = BLOCK_SPACE;
+                },
+                "center" | "figcaption" | "caption" => format.align = Align::Center,
+                _ => (),
+            }
+        }
+
+        ctx.align = format.align;
+        self.pending_space = self.pending_space.max(format.space_before);
+        self.formats.push(format);
+
+        if name == "li" {
+            let bullet = match self.counters.last_mut() {
+                Some(n) if ctx.ordered => {
+                    *n += 1;
+                    format!("{}. ", n)
+                },
+                _ => "• ".to_string(),
+            };
+            self.push_text(&bullet, &ctx);
+        }
+
+        self.walk(element, &ctx);
+        self.flush(&ctx);
+
+        self.formats.pop();
+        if name == "ul" || name == "ol" {
+            self.counters.pop();
+        }
+        self.pending_space = self.pending_space.max(format.space_after);
+    }
+
+    fn record_anchor(&mut self, element: &Element) {
+        let index = self.chapter.blocks.len();
+        for key in &["id", "name"] {
+            if let Some(id) = element.attribute(key) {
+                self.chapter.anchors.entry(id.to_string()).or_insert(index);
+            }
+        }
+    }
+
+    fn ends_with_space(&self) -> bool {
+        self.runs.last().map_or(true, |r| r.text.ends_with(' ') || r.text.ends_with('\n'))
+    }
+
+    fn push_text(&mut self, text: &str, ctx: &Context) {
+        if ctx.preformatted {
+            self.push_raw(text, ctx);
+            return;
+        }
+        let mut space = self.ends_with_space();
+        let mut buf = String::with_capacity(text.len());
+        for c in text.chars() {
+            if is_xml_space(c) {
+                if !space {
+                    buf.push(' ');
+                    space = true;
+                }
+            } else {
+                buf.push(c);
+                space = false;
+            }
+        }
+        self.push_raw(&buf, ctx);
+    }
+
+    fn push_raw(&mut self, text: &str, ctx: &Context) {
+        if text.is_empty() {
+            return;
+        }
+        if let Some(last) = self.runs.last_mut() {
+            if last.style == ctx.style && last.uri == ctx.uri {
+                last.text.push_str(text);
+                return;
+            }
+        }
+        self.runs.push(Run { text: text.to_string(), style: ctx.style, uri: ctx.uri.clone() });
+    }
+
+    fn flush(&mut self, ctx: &Context) {
+        while let Some(mut last) = self.runs.pop() {
+            let len = last.text.trim_end_matches(is_xml_space).len();
+            last.text.truncate(len);
+            if !last.text.is_empty() {
+                self.runs.push(last);
+                break;
+            }
+        }
+
+        if self.runs.is_empty() {
+            return;
+        }
+
+        let format = *self.formats.last().unwrap_or(&DEFAULT_FORMAT);
+        let runs = mem::replace(&mut self.runs, Vec::new());
+        self.chapter.blocks.push(Block {
+            runs,
+            align: ctx.align,
+            indent: format.indent && !self.no_indent,
+            margin_left: ctx.margin_left,
+            space_before: self.pending_space,
+        });
+        self.pending_space = 0.0;
+        self.no_indent = format.heading;
+    }
+}
+
+enum Token {
+    Word { text: String, run: usize, space: bool },
+    Break,
+}
+
+fn tokenize(block: &Block) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut space = false;
+
+    for (run, r) in block.runs.iter().enumerate() {
+        let mut word = String::new();
+        for c in r.text.chars() {
+            if c == '\n' {
+                push_word(&mut tokens, &mut word, run, &mut space);
+                tokens.push(Token::Break);
+                space = false;
+            } else if is_xml_space(c) {
+                push_word(&mut tokens, &mut word, run, &mut space);
+                space = true;
+            } else {
+                word.push(c);
+            }
+        }
+        push_word(&mut tokens, &mut word, run, &mut space);
+    }
+
+    tokens
+}
+
+fn push_word(tokens: &mut Vec<Token>, word: &mut String, run: usize, space: &mut bool) {
+    if word.is_empty() {
+        return;
+    }
+    tokens.push(Token::Word { text: mem::replace(word, String::new()), run, space: *space });
+    *space = false;
+}
+
+pub fn font_for(family: &mut FontFamily, style: TextStyle, em: f32) -> &mut Font {
+    let font = font_from_variant(family, style.variant);
+    // At 72 DPI, one point is one pixel.
+    font.set_size((64.0 * em * style.size as f32 / 100.0).round() as u32, 72);
+    font
+}
+
+pub struct Typesetter<'a> {
+    family: &'a mut FontFamily,
+    hyphenator: Option<&'a Hyphenator>,
+    em: f32,
+    width: i32,
+    // A multiple of the font size, the font's own line height is used otherwise.
+    line_height: Option<f32>,
+    // Whether the justified blocks are actually justified.
+    justify: bool,
+    widths: FnvHashMap<TextStyle, FnvHashMap<String, i32>>,
+    metrics: FnvHashMap<u8, (i32, i32, i32)>,
+}
+
+// The line being built.
+struct Pending {
+    items: Vec<LineItem>,
+    x: i32,
+    offset: usize,
+    left: i32,
+    max_width: i32,
+}
+
+impl<'a> Typesetter<'a> {
+    pub fn new(family: &'a mut FontFamily, hyphenator: Option<&'a Hyphenator>, em: f32, width: i32,
+               line_height: Option<f32>, justify: bool) -> Typesetter<'a> {
+        Typesetter {
+            family,
+            hyphenator,
+            em,
+            width,
+            line_height,
+            justify,
+            widths: FnvHashMap::default(),
+            metrics: FnvHashMap::default(),
+        }
+    }
+
+    pub fn typeset(&mut self, chapter: &Chapter) -> Vec<Line> {
+        let mut lines = Vec::new();
+        for (index, block) in chapter.blocks.iter().enumerate() {
+            let start = lines.len();
+            self.typeset_block(index, block, &mut lines);
+            if let Some(line) = lines.get_mut(start) {
+                line.space_before = (block.space_before * self.em).round() as i32;
+            }
+        }
+        lines
+    }
+
+    fn measure(&mut self, text: &str, style: TextStyle) -> i32 {
+        if let Some(width) = self.widths.get(&style).and_then(|m| m.get(text)) {
+            return *width;
+        }
+        let width = font_for(self.family, style, self.em).plan(text, None, None).width as i32;
+        self.widths.entry(style).or_insert_with(FnvHashMap::default)
+            .insert(text.to_string(), width);
+        width
+    }
+
+    // Returns the ascent, descent and line height for the given size.
+    fn metrics(&mut self, size: u8) -> (i32, i32, i32) {
+        if let Some(m) = self.metrics.get(&size) {
+            return *m;
+        }
+        let style = TextStyle { variant: Variant::REGULAR, size };
+        let (ascent, descent, height) = {
+            let font = font_for(self.family, style, self.em);
+            (font.ascender() >> 6, -font.descender() >> 6, font.line_height() >> 6)
+        };
+        let m = match self.line_height {
+            Some(factor) => {
+                let height = (factor * self.em * size as f32 / 100.0).round() as i32;
+                // The leading is split evenly above and below the glyphs.
+                let leading = (height - ascent - descent).max(0);
+                (ascent + leading / 2, descent + leading - leading / 2, height)
+            },
+            None => (ascent, descent, height),
+        };
+        self.metrics.insert(size, m);
+        m
+    }
+
+    fn typeset_block(&mut self, index: usize, block: &Block, lines: &mut Vec<Line>) {
+        let left = (block.margin_left * self.em).round() as i32;
+        let max_width = (self.width - left).max(self.em as i32);
+        let indent = if block.indent { (FIRST_LINE_INDENT * self.em).round() as i32 } else { 0 };
+        let mut pending = Pending { items: Vec::new(), x: indent, offset: 0, left, max_width };
+
+        for token in tokenize(block) {
+            let (mut text, run, mut space) = match token {
+                Token::Word { text, run, space } => (text, run, space),
+                Token::Break => {
+                    self.finish_line(index, block, false, &mut pending, lines);
+                    continue;
+                },
+            };
+
+            let style = block.runs[run].style;
+
+            loop {
+                let gap = if space && !pending.items.is_empty() { self.measure(" ", style) } else { 0 };
+                let display = text.replace(SOFT_HYPHEN, "");
+                let width = self.measure(&display, style);
+
+                if pending.x + gap + width <= max_width {
+                    pending.items.push(LineItem { text: display, run, x: pending.x + gap,
+                                                  width, hyphen: false, space: gap > 0 });
+                    pending.x += gap + width;
+                    break;
+                }
+
+                let available = max_width - pending.x - gap;
+
+                if let Some((head, tail, width, hyphen)) = self.hyphenate(&text, style, available) {
+                    pending.items.push(LineItem { text: head, run, x: pending.x + gap,
+                                                  width, hyphen, space: gap > 0 });
+                    pending.x += gap + width;
+                    self.finish_line(index, block, true, &mut pending, lines);
+                    text = tail;
+                    space = false;
+                    continue;
+                }
+
+                if !pending.items.is_empty() {
+                    self.finish_line(index, block, true, &mut pending, lines);
+                    space = false;
+                    continue;
+                }
+
+                // The word is wider than the line.
+                let (head, tail) = self.cut(&display, style, max_width - pending.x);
+                let width = self.measure(&head, style);
+                pending.items.push(LineItem { text: head, run, x: pending.x,
+                                              width, hyphen: false, space: false });
+                pending.x += width;
+                self.finish_line(index, block, false, &mut pending, lines);
+                if tail.is_empty() {
+                    break;
+                }
+                text = tail;
+                space = false;
+            }
+        }
+
+        if !pending.items.is_empty() {
+            self.finish_line(index, block, false, &mut pending, lines);
+        }
+    }
+
+    // Finds the longest prefix of *text* that fits within *available*, followed by a hyphen.
+    // Returns the prefix, the remainder of the text, the width of the prefix and whether
+    // a hyphen needs to be drawn after the prefix.
+    fn hyphenate(&mut self, text: &str, style: TextStyle, available: i32) -> Option<(String, String, i32, bool)> {
+        if available <= 0 {
+            return None;
+        }
+
+        // Each candidate is a triple (head end, tail start, inserted hyphen).
+        let mut candidates: Vec<(usize, usize, bool)> = text.char_indices()
+                                                            .filter(|&(i, c)| c == '-' && i > 0 && i + 1 < text.len())
+                                                            .map(|(i, _)| (i + 1, i + 1, false))
+                                                            .collect();
+
+        let soft = soft_hyphens(text);
+
+        if !soft.is_empty() {
+            candidates.extend(soft.into_iter().map(|i| (i, i + SOFT_HYPHEN.len_utf8(), true)));
+        } else if let Some(hyphenator) = self.hyphenator {
+            let start = text.find(|c: char| c.is_alphabetic());
+            let end = text.rfind(|c: char| c.is_alphabetic())
+                          .map(|i| i + text[i..].chars().next().map_or(0, |c| c.len_utf8()));
+            if let (Some(start), Some(end)) = (start, end) {
+                if start < end && !text[start..end].contains('-') {
+                    candidates.extend(hyphenator.hyphenate(&text[start..end]).into_iter()
+                                                .map(|i| (start + i, start + i, true)));
+                }
+            }
+        }
+
+        candidates.sort();
+
+        for &(head_end, tail_start, hyphen) in candidates.iter().rev() {
+            let head = text[..head_end].replace(SOFT_HYPHEN, "");
+            let width = if hyphen {
+                self.measure(&format!("{}-", head), style)
+            } else {
+                self.measure(&head, style)
+            };
+            if width <= available {
+                return Some((head, text[tail_start..].to_string(), width, hyphen));
+            }
+        }
+
+        None
+    }
+
+    fn cut(&mut self, text: &str, style: TextStyle, max_width: i32) -> (String, String) {
+        let offset = {
+            let font = font_for(self.family, style, self.em);
+            let plan = font.plan(text, None, None);
+            let (index, _) = font.cut_point(&plan, max_width.max(0) as u32);
+            plan.cluster_at(index).unwrap_or_else(|| text.len())
+        };
+        // Make sure that we always make some progress.
+        let offset = if offset == 0 {
+            text.char_indices().nth(1).map_or_else(|| text.len(), |(i, _)| i)
+        } else {
+            offset
+        };
+        (text[..offset].to_string(), text[offset..].to_string())
+    }
+
+    fn finish_line(&mut self, index: usize, block: &Block, justify: bool, pending: &mut Pending, lines: &mut Vec<Line>) {
+        let mut items = mem::replace(&mut pending.items, Vec::new());
+        let mut sizes: Vec<u8> = items.iter().map(|item| block.runs[item.run].style.size).collect();
+
+        if sizes.is_empty() {
+            sizes.push(block.runs.first().map_or(NORMAL_SIZE, |r| r.style.size));
+        }
+
+        sizes.sort();
+        sizes.dedup();
+
+        let (mut ascent, mut descent, mut line_height) = (0, 0, 0);
+
+        for size in sizes {
+            let (a, d, h) = self.metrics(size);
+            ascent = ascent.max(a);
+            descent = descent.max(d);
+            line_height = line_height.max(h);
+        }
+
+        let extra = pending.max_width - pending.x;
+
+        if extra > 0 {
+            match block.align {
+                Align::Justify if justify && self.justify => {
+                    let gaps = items.iter().filter(|item| item.space).count() as i32;
+                    if gaps > 0 {
+                        let mut seen = 0;
+                        for item in &mut items {
+                            if item.space {
+                                seen += 1;
+                            }
+                            item.x += extra * seen / gaps;
+                        }
+                    }
+                },
+                Align::Center => {
+                    for item in &mut items {
+                        item.x += extra / 2;
+                    }
+                },
+                _ => (),
+            }
+        }
+
+        lines.push(Line {
+            block: index,
+            offset: pending.offset,
+            left: pending.left,
+            items,
+            ascent,
+            height: line_height.max(ascent + descent),
+            space_before: 0,
+        });
+
+        pending.x = 0;
+        pending.offset += 1;
+    }
+}
+
+// Appends the pages of the given chapter. Each chapter starts on a new page.
+pub fn paginate(chapter: usize, lines: &[Line], height: i32, pages: &mut Vec<PageStart>) {
+    let mut y = 0;
+    for (index, line) in lines.iter().enumerate() {
+        let needed = line.space_before + line.height;
+        if index == 0 || y + needed > height {
+            pages.push(PageStart { chapter, block: line.block, offset: line.offset, line: index });
+            y = line.height;
+        } else {
+            y += needed;
+        }
+    }
+}
+
+// Returns the top of each line, relatively to the top of the page.
+pub fn line_tops(lines: &[Line]) -> Vec<i32> {
+    let mut tops = Vec::with_capacity(lines.len());
+    let mut y = 0;
+    for (index, line) in lines.iter().enumerate() {
+        if index > 0 {
+            y += line.space_before;
+        }
+        tops.push(y);
+        y += line.height;
+    }
+    tops
+}
+
+pub fn render_line(fb: &mut Framebuffer, family: &mut FontFamily, block: &Block, line: &Line, origin: &Point, em: f32, scale: f32) {
+    for item in &line.items {
+        let style = block.runs[item.run].style;
+        let font = font_for(family, style, em * scale);
+        let plan = if item.hyphen {
+            font.plan(&format!("{}-", item.text), None, None)
+        } else {
+            font.plan(&item.text, None, None)
+        };
+        let pt = pt!(origin.x + ((line.left + item.x) as f32 * scale).round() as i32,
+                     origin.y);
+        font.render(fb, BLACK, &plan, &pt);
+    }
+}
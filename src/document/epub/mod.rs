@@ -0,0 +1,633 @@
+mod xml;
+mod layout;
+mod hyphenation;
+
+use std::fs::{self, File};
+use std::io::Read;
+use std::rc::Rc;
+use std::cell::RefCell;
+use std::path::{Path, PathBuf};
+use fnv::FnvHashMap;
+use zip::ZipArchive;
+use failure::{Error, ResultExt};
+use document::{Document, BoundedText, Link, TocEntry, Typography, is_external, is_note_mark, fingerprint};
+use framebuffer::Pixmap;
+use geom::Rectangle;
+use font::{FontOpener, FontFamily};
+use helpers::{load_json, save_json};
+use metadata::TextAlign;
+use self::xml::Element;
+use self::layout::{Chapter, Line, PageStart, Typesetter};
+use self::layout::{build_chapter, paginate, line_tops, render_line};
+use self::hyphenation::Hyphenator;
+
+const FONT_FAMILY: &str = "NotoSerif";
+const HYPHENATION_PATTERNS_DIR: &str = "hyphenation";
+const PAGINATION_CACHE_DIR: &str = "cache/pagination";
+// Must be incremented whenever the typesetting rules change.
+const LAYOUT_VERSION: usize = 1;
+// The margins are a fraction of the smallest dimension of the page.
+const MARGIN_RATIO: f32 = 0.05;
+// Number of typeset chapters kept in memory.
+const LINES_CACHE_SIZE: usize = 3;
+// Number of layouts whose page boundaries are kept in the pagination cache of a book.
+const PAGINATION_CACHE_SIZE: usize = 8;
+
+const DEFAULT_WIDTH: i32 = 600;
+const DEFAULT_HEIGHT: i32 = 800;
+const DEFAULT_EM: f32 = 16.0;
+
+// Page boundaries, with their layout keys, most recently used first.
+type PaginationCache = Vec<(String, Vec<PageStart>)>;
+
+#[derive(Debug, Clone)]
+struct NavEntry {
+    title: String,
+    target: String,
+    children: Vec<NavEntry>,
+}
+
+pub struct EpubDocument {
+    archive: RefCell<ZipArchive<File>>,
+    spine: Vec<String>,
+    spine_indices: FnvHashMap<String, usize>,
+    title: Option<String>,
+    author: Option<String>,
+    isbn: Option<String>,
+    nav: Vec<NavEntry>,
+    family: RefCell<FontFamily>,
+    hyphenator: Option<Hyphenator>,
+    chapters: RefCell<Vec<Option<Rc<Chapter>>>>,
+    lines: RefCell<Vec<(usize, Rc<Vec<Line>>)>>,
+    toc: RefCell<Option<Vec<TocEntry>>>,
+    width: i32,
+    height: i32,
+    em: f32,
+    margin: i32,
+    typography: Typography,
+    pages: Vec<PageStart>,
+    cache: PaginationCache,
+    cache_path: PathBuf,
+}
+
+// The `Rc` and `RefCell` fields are never shared outside of the document: the clones of the `Rc`s
+// don't outlive the method calls. Moving the document to another thread is therefore safe, but sharing
+// it isn't: the documents are always accessed through a `Mutex`.
+unsafe impl Send for EpubDocument {}
+
+impl EpubDocument {
+    pub fn new<P: AsRef<Path>>(path: P) -> Result<EpubDocument, Error> {
+        let path = path.as_ref();
+        let file = File::open(path).context("Can't open file.")?;
+        let fingerprint = fingerprint(path, &file)?;
+        let mut archive = ZipArchive::new(file).context("Can't read archive.")?;
+
+        let container = xml::parse(&read_entry(&mut archive, "META-INF/container.xml")?);
+        let opf_path = container.find("rootfile")
+                                .and_then(|e| e.attribute("full-path"))
+                                .map(String::from)
+                                .ok_or_else(|| format_err!("Can't find the package document."))?;
+        let opf = xml::parse(&read_entry(&mut archive, &opf_path)?);
+
+        let mut items = FnvHashMap::default();
+        let mut nav_path = None;
+
+        if let Some(manifest) = opf.find("manifest") {
+            for item in manifest.elements().filter(|e| e.local_name() == "item") {
+                if let (Some(id), Some(href)) = (item.attribute("id"), item.attribute("href")) {
+                    let href = resolve_path(&opf_path, href);
+                    if item.attribute("properties").map_or(false, |p| p.split_whitespace().any(|p| p == "nav")) {
+                        nav_path = Some(href.clone());
+                    }
+                    items.insert(id.to_string(), href);
+                }
+            }
+        }
+
+        let spine_element = opf.find("spine")
+                               .ok_or_else(|| format_err!("Can't find the spine."))?;
+        let spine: Vec<String> = spine_element.elements()
+                                              .filter(|e| e.local_name() == "itemref")
+                                              .filter_map(|e| e.attribute("idref").and_then(|id| items.get(id)))
+                                              .cloned()
+                                              .collect();
+        let spine_indices = spine.iter().enumerate()
+                                 .map(|(i, p)| (p.clone(), i))
+                                 .collect();
+
+        let ncx_path = spine_element.attribute("toc").and_then(|id| items.get(id));
+        let mut nav = Vec::new();
+
+        if let Some(ncx_path) = ncx_path {
+            if let Ok(text) = read_entry(&mut archive, ncx_path) {
+                nav = parse_ncx(&xml::parse(&text), ncx_path);
+            }
+        } else if let Some(ref nav_path) = nav_path {
+            if let Ok(text) = read_entry(&mut archive, nav_path) {
+                nav = parse_nav(&xml::parse(&text), nav_path);
+            }
+        }
+
+        let metadata = opf.find("metadata");
+        let meta_text = |name: &str| metadata.and_then(|m| m.find(name))
+                                             .map(|e| collapse_spaces(&e.text()))
+                                             .filter(|t| !t.is_empty());
+        let title = meta_text("title");
+        let language = meta_text("language");
+        let author = metadata.map(|m| {
+            let mut creators = Vec::new();
+            m.find_all("creator", &mut creators);
+            creators.iter().map(|e| collapse_spaces(&e.text()))
+                    .filter(|t| !t.is_empty())
+                    .collect::<Vec<String>>().join(", ")
+        }).filter(|a| !a.is_empty());
+        let isbn = metadata.and_then(|m| {
+            let mut identifiers = Vec::new();
+            m.find_all("identifier", &mut identifiers);
+            identifiers.iter().filter_map(|e| isbn_from_identifier(e)).next()
+        });
+
+        let family = FontFamily::from_name(&FontOpener::new()?, FONT_FAMILY)?;
+        let hyphenator = language.and_then(|l| Hyphenator::for_language(HYPHENATION_PATTERNS_DIR, &l).ok());
+        let cache_path = Path::new(PAGINATION_CACHE_DIR).join(format!("{:016x}.json", fingerprint));
+        let cache = load_json(&cache_path).unwrap_or_default();
+        let chapters = vec![None; spine.len()];
+
+        Ok(EpubDocument {
+            archive: RefCell::new(archive),
+            spine,
+            spine_indices,
+            title,
+            author,
+            isbn,
+            nav,
+            family: RefCell::new(family),
+            hyphenator,
+            chapters: RefCell::new(chapters),
+            lines: RefCell::new(Vec::new()),
+            toc: RefCell::new(None),
+            width: DEFAULT_WIDTH,
+            height: DEFAULT_HEIGHT,
+            em: DEFAULT_EM,
+            margin: margin_width(DEFAULT_WIDTH, DEFAULT_HEIGHT),
+            typography: Typography::default(),
+            pages: Vec::new(),
+            cache,
+            cache_path,
+        })
+    }
+
+    fn layout_key(&self) -> String {
+        let family = self.typography.font_family.as_ref().map_or(FONT_FAMILY, |&(_, ref name)| name);
+        let mut key = format!("{}-{}x{}-{}-{}-{}", LAYOUT_VERSION, self.width, self.height,
+                              (64.0 * self.em).round() as u32, self.margin, family);
+        if let Some(line_height) = self.typography.line_height {
+            key.push_str(&format!("-lh{}", (100.0 * line_height).round() as u32));
+        }
+        if !self.justify() {
+            key.push_str("-ragged");
+        }
+        if self.hyphenator().is_some() {
+            key.push_str("-hyphenated");
+        }
+        key
+    }
+
+    fn hyphenator(&self) -> Option<&Hyphenator> {
+        if self.typography.hyphenation == Some(false) {
+            None
+        } else {
+            self.hyphenator.as_ref()
+        }
+    }
+
+    fn justify(&self) -> bool {
+        self.typography.text_align != Some(TextAlign::Left)
+    }
+
+    fn typesetter<'a>(&'a self, family: &'a mut FontFamily) -> Typesetter<'a> {
+        Typesetter::new(family, self.hyphenator(), self.em, self.content_width(),
+                        self.typography.line_height, self.justify())
+    }
+
+    fn content_width(&self) -> i32 {
+        self.width - 2 * self.margin
+    }
+
+    fn content_height(&self) -> i32 {
+        self.height - 2 * self.margin
+    }
+
+    fn chapter(&self, index: usize) -> Option<Rc<Chapter>> {
+        if let Some(chapter) = self.chapters.borrow().get(index).and_then(|c| c.clone()) {
+            return Some(chapter);
+        }
+        let path = self.spine.get(index)?;
+        let chapter = read_entry(&mut self.archive.borrow_mut(), path)
+                          .map(|text| build_chapter(&xml::parse(&text), path))
+                          .map_err(|e| eprintln!("Can't read {}: {}", path, e))
+                          .unwrap_or_default();
+        let chapter = Rc::new(chapter);
+        self.chapters.borrow_mut()[index] = Some(chapter.clone());
+        Some(chapter)
+    }
+
+    fn chapter_lines(&self, index: usize) -> Option<Rc<Vec<Line>>> {
+        if let Some(&(_, ref lines)) = self.lines.borrow().iter().find(|&&(i, _)| i == index) {
+            return Some(lines.clone());
+        }
+        let chapter = self.chapter(index)?;
+        let lines = {
+            let mut family = self.family.borrow_mut();
+            let mut typesetter = self.typesetter(&mut family);
+            Rc::new(typesetter.typeset(&chapter))
+        };
+        let mut cache = self.lines.borrow_mut();
+        cache.insert(0, (index, lines.clone()));
+        cache.truncate(LINES_CACHE_SIZE);
+        Some(lines)
+    }
+
+    fn paginate(&self) -> Vec<PageStart> {
+        let mut pages = Vec::new();
+        let mut family = self.family.borrow_mut();
+        let mut typesetter = self.typesetter(&mut family);
+        for index in 0..self.spine.len() {
+            if let Some(chapter) = self.chapter(index) {
+                let lines = typesetter.typeset(&chapter);
+                paginate(index, &lines, self.content_height(), &mut pages);
+            }
+        }
+        pages
+    }
+
+    // Returns the chapter and the lines of the given page, as well as the top of each line.
+    fn page(&self, index: usize) -> Option<(Rc<Chapter>, Vec<(Line, i32)>)> {
+        let start = self.pages.get(index)?;
+        let chapter = self.chapter(start.chapter)?;
+        let lines = self.chapter_lines(start.chapter)?;
+        let end = self.pages.get(index + 1)
+                      .filter(|p| p.chapter == start.chapter)
+                      .map_or(lines.len(), |p| p.line);
+        let lines = &lines[start.line.min(end)..end];
+        let tops = line_tops(lines);
+        Some((chapter, lines.iter().cloned().zip(tops.into_iter()).collect()))
+    }
+
+    // The page on which the given block starts.
+    fn page_for(&self, chapter: usize, block: usize) -> usize {
+        let target = (chapter, block, 0);
+        let index = self.pages.iter()
+                        .rposition(|p| (p.chapter, p.block, p.offset) <= target);
+        match index {
+            Some(i) if self.pages[i].chapter < chapter => (i + 1).min(self.pages.len().saturating_sub(1)),
+            Some(i) => i,
+            None => 0,
+        }
+    }
+
+    fn target_page(&self, target: &str) -> Option<usize> {
+        let (path, fragment) = split_fragment(target);
+        let chapter = *self.spine_indices.get(path)?;
+        let block = fragment.and_then(|f| self.chapter(chapter)
+                                              .and_then(|c| c.anchors.get(f).cloned()))
+                            .unwrap_or(0);
+        Some(self.page_for(chapter, block))
+    }
+
+    // The rectangle, the raw target and the text of each link item of the given page.
+    fn page_links(&self, index: usize) -> Option<Vec<(Rectangle, String, String)>> {
+        let (chapter, lines) = self.page(index)?;
+        let mut links = Vec::new();
+        for (line, top) in lines {
+            let y = self.margin + top;
+            let block = &chapter.blocks[line.block];
+            for item in &line.items {
+                if let Some(ref uri) = block.runs[item.run].uri {
+                    let x = self.margin + line.left + item.x;
+                    links.push((rect![x, y, x + item.width, y + line.height],
+                                uri.clone(), item.text.clone()));
+                }
+            }
+        }
+        Some(links)
+    }
+
+    fn toc_entries(&self, entries: &[NavEntry]) -> Vec<TocEntry> {
+        entries.iter().map(|entry| {
+            TocEntry {
+                title: entry.title.clone(),
+                page: self.target_page(&entry.target).unwrap_or(0),
+                children: self.toc_entries(&entry.children),
+            }
+        }).collect()
+    }
+}
+
+impl Document for EpubDocument {
+    fn pages_count(&self) -> usize {
+        self.pages.len()
+    }
+
+    fn pixmap(&self, index: usize, scale: f32) -> Option<Pixmap> {
+        let (chapter, lines) = self.page(index)?;
+        let width = (self.width as f32 * scale).round() as i32;
+        let height = (self.height as f32 * scale).round() as i32;
+        let mut pixmap = Pixmap::new(width, height);
+        let mut family = self.family.borrow_mut();
+        for (line, top) in lines {
+            let origin = pt!((self.margin as f32 * scale).round() as i32,
+                             ((self.margin + top + line.ascent) as f32 * scale).round() as i32);
+            render_line(&mut pixmap, &mut family, &chapter.blocks[line.block],
+                        &line, &origin, self.em, scale);
+        }
+        Some(pixmap)
+    }
+
+    fn dims(&self, index: usize) -> Option<(f32, f32)> {
+        if index < self.pages.len() {
+            Some((self.width as f32, self.height as f32))
+        } else {
+            None
+        }
+    }
+
+    fn toc(&self) -> Option<Vec<TocEntry>> {
+        if self.pages.is_empty() {
+            return None;
+        }
+        if self.toc.borrow().is_none() {
+            let toc = self.toc_entries(&self.nav);
+            *self.toc.borrow_mut() = Some(toc);
+        }
+        self.toc.borrow().clone()
+    }
+
+    fn words(&self, index: usize) -> Option<Vec<BoundedText>> {
+        let (_, lines) = self.page(index)?;
+        let mut words = Vec::new();
+        for (line, top) in lines {
+            let y = self.margin + top;
+            for item in &line.items {
+                let x = self.margin + line.left + item.x;
+                words.push(BoundedText {
+                    rect: rect![x, y, x + item.width, y + line.height],
+                    text: item.text.clone(),
+                });
+            }
+        }
+        Some(words)
+    }
+
+    fn links(&self, index: usize) -> Option<Vec<Link>> {
+        let links = self.page_links(index)?;
+        Some(links.into_iter().map(|(rect, uri, _)| {
+            let uri = if is_external(&uri) {
+                uri
+            } else {
+                self.target_page(&uri).map_or(uri, |page| format!("#{}", page + 1))
+            };
+            Link { uri, rect }
+        }).collect())
+    }
+
+    fn footnote(&self, index: usize, link: &Link) -> Option<String> {
+        let (_, uri, text) = self.page_links(index)?.into_iter()
+                                 .find(|&(rect, _, _)| rect == link.rect)?;
+        if is_external(&uri) || !is_note_mark(&text) {
+            return None;
+        }
+        let (path, fragment) = split_fragment(&uri);
+        let chapter = self.chapter(*self.spine_indices.get(path)?)?;
+        let block = chapter.blocks.get(*chapter.anchors.get(fragment?)?)?;
+        let text = block.runs.iter().map(|r| r.text.as_str()).collect::<String>();
+        Some(collapse_spaces(&text)).filter(|t| !t.is_empty())
+    }
+
+    fn title(&self) -> Option<String> {
+        self.title.clone()
+    }
+
+    fn author(&self) -> Option<String> {
+        self.author.clone()
+    }
+
+    fn is_reflowable(&self) -> bool {
+        true
+    }
+
+    fn set_typography(&mut self, typography: &Typography) {
+        if *typography == self.typography {
+            return;
+        }
+
+        let mut typography = typography.clone();
+
+        if typography.font_family != self.typography.font_family {
+            let family = FontOpener::new().and_then(|fo| {
+                match typography.font_family {
+                    Some((ref dir, ref name)) => FontFamily::from_dir(&fo, dir, name),
+                    None => FontFamily::from_name(&fo, FONT_FAMILY),
+                }
+            });
+            match family {
+                Ok(family) => *self.family.borrow_mut() = family,
+                Err(e) => {
+                    eprintln!("Can't load font family: {}", e);
+                    typography.font_family = self.typography.font_family.clone();
+                },
+            }
+        }
+
+        self.typography = typography;
+        // Forces the next layout.
+        self.pages.clear();
+    }
+
+    fn layout(&mut self, width: f32, height: f32, em: f32) {
+        let width = width as i32;
+        let height = height as i32;
+
+        if !self.pages.is_empty() && self.width == width &&
+           self.height == height && self.em == em {
+            return;
+        }
+
+        self.width = width;
+        self.height = height;
+        self.em = em;
+        self.margin = self.typography.margin_width
+                          .unwrap_or_else(|| margin_width(width, height));
+        self.lines.borrow_mut().clear();
+        *self.toc.borrow_mut() = None;
+
+        let key = self.layout_key();
+
+        if let Some(index) = self.cache.iter().position(|&(ref k, _)| *k == key) {
+            let entry = self.cache.remove(index);
+            self.pages = entry.1.clone();
+            self.cache.insert(0, entry);
+            return;
+        }
+
+        let mut pages = self.paginate();
+
+        if pages.is_empty() {
+            pages.push(PageStart { chapter: 0, block: 0, offset: 0, line: 0 });
+        }
+
+        self.pages = pages.clone();
+        self.cache.insert(0, (key, pages));
+        self.cache.truncate(PAGINATION_CACHE_SIZE);
+
+        let result = fs::create_dir_all(PAGINATION_CACHE_DIR).map_err(Error::from)
+                        .and_then(|_| save_json(&self.cache, &self.cache_path));
+
+        if let Err(e) = result {
+            eprintln!("Can't save pagination cache: {}", e);
+        }
+    }
+
+    fn has_text(&self) -> bool {
+        true
+    }
+
+    fn isbn(&self) -> Option<String> {
+        self.isbn.clone()
+    }
+}
+
+fn margin_width(width: i32, height: i32) -> i32 {
+    (width.min(height) as f32 * MARGIN_RATIO).round() as i32
+}
+
+fn read_entry(archive: &mut ZipArchive<File>, name: &str) -> Result<String, Error> {
+    let mut entry = archive.by_name(name)
+                           .with_context(|_| format!("Can't find {}.", name))?;
+    let mut buf = Vec::new();
+    entry.read_to_end(&mut buf)?;
+    Ok(String::from_utf8_lossy(&buf).into_owned())
+}
+
+fn parse_ncx(root: &Element, path: &str) -> Vec<NavEntry> {
+    root.find("navMap").map(|map| ncx_points(map, path)).unwrap_or_default()
+}
+
+fn ncx_points(parent: &Element, path: &str) -> Vec<NavEntry> {
+    parent.elements().filter(|e| e.local_name() == "navPoint").map(|point| {
+        let title = point.elements().find(|e| e.local_name() == "navLabel")
+                         .map(|label| collapse_spaces(&label.text()))
+                         .unwrap_or_default();
+        let target = point.elements().find(|e| e.local_name() == "content")
+                          .and_then(|content| content.attribute("src"))
+                          .map(|src| resolve_path(path, src))
+                          .unwrap_or_default();
+        NavEntry { title, target, children: ncx_points(point, path) }
+    }).collect()
+}
+
+fn parse_nav(root: &Element, path: &str) -> Vec<NavEntry> {
+    let mut navs = Vec::new();
+    root.find_all("nav", &mut navs);
+    navs.iter().find(|nav| nav.attribute("type") == Some("toc"))
+        .or_else(|| navs.first())
+        .and_then(|nav| nav.find("ol"))
+        .map(|list| nav_items(list, path))
+        .unwrap_or_default()
+}
+
+fn nav_items(list: &Element, path: &str) -> Vec<NavEntry> {
+    list.elements().filter(|e| e.local_name() == "li").map(|item| {
+        let label = item.elements().find(|e| e.local_name() == "a" || e.local_name() == "span");
+        let title = label.map(|l| collapse_spaces(&l.text())).unwrap_or_default();
+        let target = label.and_then(|l| l.attribute("href"))
+                          .map(|href| resolve_path(path, href))
+                          .unwrap_or_default();
+        let children = item.elements().find(|e| e.local_name() == "ol")
+                           .map(|l| nav_items(l, path))
+                           .unwrap_or_default();
+        NavEntry { title, target, children }
+    }).collect()
+}
+
+fn isbn_from_identifier(element: &Element) -> Option<String> {
+    let text = element.text();
+    let is_isbn = element.attribute("scheme").map_or(false, |s| s.eq_ignore_ascii_case("isbn")) ||
+                  text.to_lowercase().contains("isbn");
+    if !is_isbn {
+        return None;
+    }
+    let digits: String = text.chars().filter(|&c| c.is_digit(10) || c == 'X').collect();
+    if digits.len() == 10 || digits.len() == 13 {
+        Some(digits)
+    } else {
+        None
+    }
+}
+
+fn collapse_spaces(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<&str>>().join(" ")
+}
+
+fn split_fragment(uri: &str) -> (&str, Option<&str>) {
+    match uri.find('#') {
+        Some(i) => (&uri[..i], Some(&uri[i+1..])),
+        None => (uri, None),
+    }
+}
+
+// Resolves *href* relatively to the archive path *base*.
+fn resolve_path(base: &str, href: &str) -> String {
+    if is_external(href) {
+        return href.to_string();
+    }
+
+    let (path, fragment) = split_fragment(href);
+
+    let mut result = if path.is_empty() {
+        split_fragment(base).0.to_string()
+    } else {
+        let dir = if path.starts_with('/') {
+            ""
+        } else {
+            base.rfind('/').map_or("", |i| &base[..i])
+        };
+        let mut parts = Vec::new();
+        for part in dir.split('/').chain(path.split('/')) {
+            match part {
+                "" | "." => (),
+                ".." => { parts.pop(); },
+                _ => parts.push(part),
+            }
+        }
+        percent_decode(&parts.join("/"))
+    };
+
+    if let Some(fragment) = fragment {
+        result.push('#');
+        result.push_str(fragment);
+    }
+
+    result
+}
+
+fn percent_decode(text: &str) -> String {
+    if !text.contains('%') {
+        return text.to_string();
+    }
+    let bytes = text.as_bytes();
+    let mut buf = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            if let Some(b) = text.get(i+1..i+3).and_then(|h| u8::from_str_radix(h, 16).ok()) {
+                buf.push(b);
+                i += 3;
+                continue;
+            }
+        }
+        buf.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&buf).into_owned()
+}
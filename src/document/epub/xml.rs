@@ -0,0 +1,323 @@
+// A forgiving XML parser, sufficient for the OPF, NCX and XHTML documents found in ePUBs.
+
+#[derive(Debug, Clone)]
+pub enum Node {
+    Element(Element),
+    Text(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct Element {
+    pub name: String,
+    pub attributes: Vec<(String, String)>,
+    pub children: Vec<Node>,
+}
+
+impl Element {
+    fn new(name: &str) -> Element {
+        Element {
+            name: name.to_string(),
+            attributes: Vec::new(),
+            children: Vec::new(),
+        }
+    }
+
+    // The name without its namespace prefix.
+    pub fn local_name(&self) -> &str {
+        local_name(&self.name)
+    }
+
+    pub fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes.iter()
+            .find(|&&(ref k, _)| k == name || local_name(k) == name)
+            .map(|&(_, ref v)| v.as_str())
+    }
+
+    pub fn elements(&self) -> impl Iterator<Item=&Element> {
+        self.children.iter().filter_map(|node| {
+            match *node {
+                Node::Element(ref e) => Some(e),
+                _ => None,
+            }
+        })
+    }
+
+    // Depth-first search for the first descendant with the given local name.
+    pub fn find(&self, name: &str) -> Option<&Element> {
+        for child in self.elements() {
+            if child.local_name() == name {
+                return Some(child);
+            }
+            if let Some(e) = child.find(name) {
+                return Some(e);
+            }
+        }
+        None
+    }
+
+    pub fn find_all<'a>(&'a self, name: &str, result: &mut Vec<&'a Element>) {
+        for child in self.elements() {
+            if child.local_name() == name {
+                result.push(child);
+            }
+            child.find_all(name, result);
+        }
+    }
+
+    pub fn text(&self) -> String {
+        let mut buf = String::new();
+        self.text_aux(&mut buf);
+        buf
+    }
+
+    fn text_aux(&self, buf: &mut String) {
+        for child in &self.children {
+            match *child {
+                Node::Text(ref t) => buf.push_str(t),
+                Node::Element(ref e) => e.text_aux(buf),
+            }
+        }
+    }
+}
+
+fn local_name(name: &str) -> &str {
+    name.rfind(':').map_or(name, |i| &name[i+1..])
+}
+
+// Returns a synthetic root element holding the top level nodes of the document.
+pub fn parse(text: &str) -> Element {
+    let mut stack = vec![Element::new("")];
+    let mut rest = text;
+
+    while !rest.is_empty() {
+        if rest.starts_with("<!--") {
+            rest = skip_past(rest, "-->");
+        } else if rest.starts_with("<![CDATA[") {
+            let end = rest.find("]]>").unwrap_or(rest.len());
+            push_text(&mut stack, &rest[9..end], false);
+            rest = skip_past(rest, "]]>");
+        } else if rest.starts_with("<?") {
+            rest = skip_past(rest, "?>");
+        } else if rest.starts_with("<!") {
+            rest = skip_declaration(rest);
+        } else if rest.starts_with("</") {
+            let end = rest.find('>').unwrap_or(rest.len());
+            let name = rest[2..end].trim();
+            close_element(&mut stack, name);
+            rest = &rest[(end+1).min(rest.len())..];
+        } else if rest.starts_with('<') {
+            let (element, self_closing, next) = parse_tag(rest);
+            rest = next;
+            if self_closing || is_void_element(element.local_name()) {
+                stack.last_mut().unwrap().children.push(Node::Element(element));
+            } else {
+                stack.push(element);
+            }
+        } else {
+            let end = rest.find('<').unwrap_or(rest.len());
+            push_text(&mut stack, &rest[..end], true);
+            rest = &rest[end..];
+        }
+    }
+
+    while stack.len() > 1 {
+        let element = stack.pop().unwrap();
+        stack.last_mut().unwrap().children.push(Node::Element(element));
+    }
+
+    stack.pop().unwrap()
+}
+
+fn skip_past<'a>(text: &'a str, pattern: &str) -> &'a str {
+    text.find(pattern).map_or("", |i| &text[i+pattern.len()..])
+}
+
+// Handles internal subsets, e.g.: <!DOCTYPE html [ <!ENTITY ...> ]>.
+fn skip_declaration(text: &str) -> &str {
+    let mut depth = 0;
+    for (i, c) in text.char_indices() {
+        match c {
+            '[' => depth += 1,
+            ']' => depth -= 1,
+            '>' if depth <= 0 => return &text[i+1..],
+            _ => (),
+        }
+    }
+    ""
+}
+
+fn push_text(stack: &mut Vec<Element>, text: &str, decode: bool) {
+    if text.is_empty() {
+        return;
+    }
+    let text = if decode { decode_entities(text) } else { text.to_string() };
+    let parent = stack.last_mut().unwrap();
+    if let Some(&mut Node::Text(ref mut t)) = parent.children.last_mut() {
+        t.push_str(&text);
+        return;
+    }
+    parent.children.push(Node::Text(text));
+}
+
+fn close_element(stack: &mut Vec<Element>, name: &str) {
+    // Ignore unmatched closing tags.
+    if !stack.iter().skip(1).any(|e| e.name == name) {
+        return;
+    }
+    while stack.len() > 1 {
+        let element = stack.pop().unwrap();
+        let done = element.name == name;
+        stack.last_mut().unwrap().children.push(Node::Element(element));
+        if done {
+            break;
+        }
+    }
+}
+
+fn is_void_element(name: &str) -> bool {
+    match name {
+        "br" | "hr" | "img" | "meta" | "link" | "input" | "col" | "area" | "base" | "wbr" => true,
+        _ => false,
+    }
+}
+
+fn parse_tag(text: &str) -> (Element, bool, &str) {
+    let bytes = text.as_bytes();
+    let len = bytes.len();
+    let mut i = 1;
+
+    while i < len && !is_name_end(bytes[i]) {
+        i += 1;
+    }
+
+    let mut element = Element::new(&text[1..i]);
+
+    loop {
+        while i < len && (bytes[i] as char).is_whitespace() {
+            i += 1;
+        }
+        if i >= len {
+            return (element, false, "");
+        }
+        match bytes[i] {
+            b'>' => return (element, false, &text[i+1..]),
+            b'/' => {
+                let rest = skip_past(&text[i..], ">");
+                return (element, true, rest);
+            },
+            _ => (),
+        }
+        let start = i;
+        while i < len && !is_name_end(bytes[i]) && bytes[i] != b'=' {
+            i += 1;
+        }
+        let key = text[start..i].to_string();
+        while i < len && (bytes[i] as char).is_whitespace() {
+            i += 1;
+        }
+        let mut value = String::new();
+        if i < len && bytes[i] == b'=' {
+            i += 1;
+            while i < len && (bytes[i] as char).is_whitespace() {
+                i += 1;
+            }
+            if i < len && (bytes[i] == b'"' || bytes[i] == b'\'') {
+                let quote = bytes[i];
+                i += 1;
+                let start = i;
+                while i < len && bytes[i] != quote {
+                    i += 1;
+                }
+                value = decode_entities(&text[start..i]);
+                i += 1;
+            } else {
+                let start = i;
+                while i < len && !is_name_end(bytes[i]) {
+                    i += 1;
+                }
+                value = decode_entities(&text[start..i]);
+            }
+        }
+        if key.is_empty() {
+            i += 1;
+        } else {
+            element.attributes.push((key, value));
+        }
+    }
+}
+
+#[inline]
+fn is_name_end(b: u8) -> bool {
+    b == b'>' || b == b'/' || (b as char).is_whitespace()
+}
+
+pub fn decode_entities(text: &str) -> String {
+    if !text.contains('&') {
+        return text.to_string();
+    }
+
+    let mut buf = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(i) = rest.find('&') {
+        buf.push_str(&rest[..i]);
+        rest = &rest[i..];
+        let end = rest.find(';').filter(|&j| j <= 12);
+        let decoded = end.and_then(|j| decode_entity(&rest[1..j]));
+        match (end, decoded) {
+            (Some(j), Some(c)) => {
+                buf.push(c);
+                rest = &rest[j+1..];
+            },
+            _ => {
+                buf.push('&');
+                rest = &rest[1..];
+            },
+        }
+    }
+
+    buf.push_str(rest);
+    buf
+}
+
+fn decode_entity(name: &str) -> Option<char> {
+    if name.starts_with("#x") || name.starts_with("#X") {
+        return u32::from_str_radix(&name[2..], 16).ok().and_then(::std::char::from_u32);
+    }
+    if name.starts_with('#') {
+        return name[1..].parse::<u32>().ok().and_then(::std::char::from_u32);
+    }
+    match name {
+        "amp" => Some('&'),
+        "lt" => Some('<'),
+        "gt" => Some('>'),
+        "quot" => Some('"'),
+        "apos" => Some('\''),
+        "nbsp" => Some('\u{00A0}'),
+        "shy" => Some('\u{00AD}'),
+        "ensp" => Some('\u{2002}'),
+        "emsp" => Some('\u{2003}'),
+        "thinsp" => Some('\u{2009}'),
+        "ndash" => Some('–'),
+        "mdash" => Some('—'),
+        "lsquo" => Some('‘'),
+        "rsquo" => Some('’'),
+        "ldquo" => Some('“'),
+        "rdquo" => Some('”'),
+        "laquo" => Some('«'),
+        "raquo" => Some('»'),
+        "hellip" => Some('…'),
+        "copy" => Some('©'),
+        "reg" => Some('®'),
+        "trade" => Some('™'),
+        "deg" => Some('°'),
+        "middot" => Some('·'),
+        "bull" => Some('•'),
+        "times" => Some('×'),
+        "eacute" => Some('é'),
+        "egrave" => Some('è'),
+        "agrave" => Some('à'),
+        "ccedil" => Some('ç'),
+        _ => None,
+    }
+}
@@ -1,20 +1,37 @@
 pub mod djvu;
 pub mod pdf;
+pub mod epub;
 
 mod djvulibre_sys;
 mod mupdf_sys;
 
 use std::ptr;
-use std::path::Path;
+use std::fs::File;
+use std::hash::{Hash, Hasher};
+use std::time::UNIX_EPOCH;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
-use fnv::FnvHashSet;
+use fnv::{FnvHashSet, FnvHasher};
 use isbn::Isbn;
+use regex::Regex;
 use unicode_normalization::UnicodeNormalization;
 use unicode_normalization::char::{is_combining_mark};
 use geom::{Rectangle, CycleDir};
 use document::djvu::{DjvuOpener};
 use document::pdf::{PdfOpener};
+use document::epub::EpubDocument;
 use framebuffer::Pixmap;
+use metadata::{Annotation, ReaderInfo, TextAlign};
+use device::CURRENT_DEVICE;
+use font::{DEFAULT_FONT_SIZE, FONTS_DIR, family_names};
+use unit::{pt_to_px, mm_to_in};
+use failure::Error;
+
+pub const USER_STYLESHEET: &str = "user.css";
+// The longest note shown when a footnote link is followed, in words.
+const MAX_NOTE_WORDS: usize = 250;
+// The number of words kept on each side of a search result, in its snippet.
+const SNIPPET_WORDS: usize = 8;
 
 #[derive(Debug, Clone)]
 pub struct BoundedText {
@@ -22,12 +39,62 @@ pub struct BoundedText {
     pub text: String,
 }
 
+// An excerpt of a page, around a search result.
+#[derive(Debug, Clone)]
+pub struct Snippet {
+    pub text: String,
+    // The position of the result within the text, in characters.
+    pub start: usize,
+}
+
 #[derive(Debug, Clone)]
 pub struct Link {
     pub uri: String,
     pub rect: Rectangle,
 }
 
+// The typographic settings of reflowable documents, unset values are left to the document.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Typography {
+    // The directory holding the files of the font family, and the name of the family.
+    pub font_family: Option<(PathBuf, String)>,
+    // A multiple of the font size.
+    pub line_height: Option<f32>,
+    // In pixels.
+    pub margin_width: Option<i32>,
+    pub text_align: Option<TextAlign>,
+    pub hyphenation: Option<bool>,
+}
+
+impl Typography {
+    // The style sheet that applies these settings to the documents rendered by MuPDF.
+    pub fn css(&self) -> String {
+        let mut css = String::new();
+        if let Some(width) = self.margin_width {
+            css.push_str(&format!("@page {{ margin: {}px; }}\n", width));
+            css.push_str("body { margin: 0; }\n");
+        }
+        let mut rules = Vec::new();
+        if let Some((_, ref name)) = self.font_family {
+            rules.push(format!("font-family: \"{}\", serif !important;", name));
+        }
+        if let Some(line_height) = self.line_height {
+            rules.push(format!("line-height: {} !important;", line_height));
+        }
+        if let Some(text_align) = self.text_align {
+            let value = match text_align {
+                TextAlign::Justify => "justify",
+                TextAlign::Left => "left",
+            };
+            rules.push(format!("text-align: {} !important;", value));
+        }
+        if !rules.is_empty() {
+            css.push_str(&format!("p, li, blockquote, dd {{ {} }}\n", rules.join(" ")));
+        }
+        css
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct TocEntry {
     pub title: String,
@@ -66,6 +133,33 @@ pub fn toc_as_html_aux(toc: &[TocEntry], buf: &mut String, chap: Option<&TocEntr
     buf.push_str("</ul>");
 }
 
+pub fn annotations_as_html(annotations: &[Annotation], toc: Option<&[TocEntry]>) -> String {
+    let mut buf = r#"<html>
+                         <head>
+                             <title>Annotations</title>
+                             <link rel="stylesheet" type="text/css" href="css/annotations.css"/>
+                         </head>
+                     <body><ul>"#.to_string();
+    for annot in annotations {
+        buf.push_str(&format!(r#"<li><a href="@{}">"#, annot.page));
+        let chapter = toc.and_then(|t| chapter_at(t, annot.page))
+                         .map(|c| format!(" — {}", escape_html(&c.title)))
+                         .unwrap_or_default();
+        buf.push_str(&format!("<strong>{}{}</strong><br/>", annot.page + 1, chapter));
+        buf.push_str(&escape_html(&annot.text));
+        if !annot.note.is_empty() {
+            buf.push_str(&format!("<br/><em>{}</em>", escape_html(&annot.note)));
+        }
+        buf.push_str("</a></li>");
+    }
+    buf.push_str("</ul></body></html>");
+    buf
+}
+
+pub fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
 pub fn chapter_at(toc: &[TocEntry], index: usize) -> Option<&TocEntry> {
     let mut chap = None;
     chapter_at_aux(toc, index, &mut chap);
@@ -111,7 +205,7 @@ fn chapter_relative_prev<'a>(toc: &'a [TocEntry], index: usize, page: &mut Optio
     }
 }
 
-pub trait Document: Send+Sync {
+pub trait Document: Send {
     fn pages_count(&self) -> usize;
     fn pixmap(&self, index: usize, scale: f32) -> Option<Pixmap>;
     fn dims(&self, index: usize) -> Option<(f32, f32)>;
@@ -126,6 +220,28 @@ pub trait Document: Send+Sync {
     fn is_reflowable(&self) -> bool;
     fn layout(&mut self, width: f32, height: f32, em: f32);
 
+    // Takes effect with the next call to `layout`.
+    fn set_typography(&mut self, _typography: &Typography) {
+    }
+
+    // The text of the note referred to by the given link of the given page, if it's a footnote mark.
+    fn footnote(&self, index: usize, link: &Link) -> Option<String> {
+        let (page, y) = parse_destination(&link.uri)?;
+        let mark: String = self.words(index)?.into_iter()
+                               .filter(|w| link.rect.includes(&w.rect.center()))
+                               .map(|w| w.text)
+                               .collect();
+        if !is_note_mark(&mark) {
+            return None;
+        }
+        note_text(&self.words(page)?, y)
+    }
+
+    // The bounding box of the objects of the given page, when the format provides it.
+    fn boundary_box(&self, _index: usize) -> Option<Rectangle> {
+        None
+    }
+
     fn has_text(&self) -> bool {
         (0..self.pages_count()).any(|i| self.words(i).map_or(false, |w| !w.is_empty()))
     }
@@ -161,6 +277,119 @@ pub trait Document: Send+Sync {
     }
 }
 
+// The page index and the vertical position of the destination of an internal link: `#page,x,y`.
+fn parse_destination(uri: &str) -> Option<(usize, i32)> {
+    if !uri.starts_with('#') {
+        return None;
+    }
+    let mut parts = uri[1..].split(',');
+    let page = parts.next()?.parse::<usize>().ok()?;
+    let y = parts.nth(1)?.parse::<f32>().ok()?;
+    Some((page.checked_sub(1)?, y as i32))
+}
+
+// Footnote marks are short numbers or typographic symbols, possibly between brackets.
+pub fn is_note_mark(text: &str) -> bool {
+    let text = text.trim_matches(|c: char| "[](){}<>.,:;".contains(c));
+    let count = text.chars().count();
+    count > 0 && count <= 3 &&
+    (text.chars().all(|c| c.is_numeric()) || text.chars().all(|c| "*†‡§¶".contains(c)))
+}
+
+// The words of the note that starts at *y*: the note ends with its paragraph,
+// or when a line starts with another mark.
+pub fn note_text(words: &[BoundedText], y: i32) -> Option<String> {
+    let start = words.iter().position(|w| w.rect.center().y >= y)?;
+    let mut text = String::new();
+    let mut previous: Option<&Rectangle> = None;
+
+    for word in words[start..].iter().take(MAX_NOTE_WORDS) {
+        if let Some(rect) = previous {
+            if word.rect.min.y >= rect.max.y {
+                let gap = word.rect.min.y - rect.max.y;
+                if gap > rect.height() as i32 / 2 || is_note_mark(&word.text) {
+                    break;
+                }
+            }
+            text.push(' ');
+        }
+        text.push_str(&word.text);
+        previous = Some(&word.rect);
+    }
+
+    if text.is_empty() {
+        None
+    } else {
+        Some(text)
+    }
+}
+
+// The text of the given words, separated by spaces, and the byte range of each word within it.
+pub fn words_text_ranges(words: &[BoundedText]) -> (String, Vec<(usize, usize)>) {
+    let mut text = String::new();
+    let mut ranges = Vec::with_capacity(words.len());
+    for word in words {
+        if !text.is_empty() {
+            text.push(' ');
+        }
+        let start = text.len();
+        text.push_str(&word.text);
+        ranges.push((start, text.len()));
+    }
+    (text, ranges)
+}
+
+// The ranges of words (the end is excluded) covered by the matches of *query*,
+// which can span several words.
+pub fn find_phrases(query: &Regex, words: &[BoundedText]) -> Vec<(usize, usize)> {
+    let (text, ranges) = words_text_ranges(words);
+    query.find_iter(&text).filter_map(|m| {
+        let start = ranges.iter().position(|&(_, end)| end > m.start())?;
+        let end = ranges.iter().rposition(|&(start, _)| start < m.end())? + 1;
+        if start < end {
+            Some((start, end))
+        } else {
+            None
+        }
+    }).collect()
+}
+
+// The snippet of the words from *start* to *end* (excluded).
+pub fn snippet(words: &[BoundedText], start: usize, end: usize) -> Snippet {
+    let before = &words[start.saturating_sub(SNIPPET_WORDS)..start];
+    let after = &words[end..(end + SNIPPET_WORDS).min(words.len())];
+    let mut text = words_text_ranges(before).0;
+    if !text.is_empty() {
+        text.push(' ');
+    }
+    let start_char = text.chars().count();
+    text.push_str(&words_text_ranges(&words[start..end]).0);
+    if !after.is_empty() {
+        text.push(' ');
+        text.push_str(&words_text_ranges(after).0);
+    }
+    Snippet { text, start: start_char }
+}
+
+pub fn is_external(uri: &str) -> bool {
+    uri.find(':').map_or(false, |i| {
+        i > 0 && uri[..i].chars().all(|c| c.is_ascii_alphanumeric() || c == '+' || c == '-' || c == '.')
+    })
+}
+
+// Identifies a file through its name, size and modification time.
+pub fn fingerprint(path: &Path, file: &File) -> Result<u64, Error> {
+    let metadata = file.metadata()?;
+    let mut hasher = FnvHasher::default();
+    path.file_name().hash(&mut hasher);
+    metadata.len().hash(&mut hasher);
+    metadata.modified().ok()
+            .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+            .map(|d| d.as_secs())
+            .hash(&mut hasher);
+    Ok(hasher.finish())
+}
+
 pub fn file_kind<P: AsRef<Path>>(path: P) -> Option<String> {
     path.as_ref().extension()
         .and_then(|os_ext| os_ext.to_str())
@@ -192,6 +421,38 @@ pub fn asciify(name: &str) -> String {
         .replace('’', "'")
 }
 
+// The typographic settings of the given book, the font families of the user, found in *font_path*,
+// take precedence over the bundled ones.
+pub fn book_typography(reader: Option<&ReaderInfo>, font_path: &Path) -> Typography {
+    let dpi = CURRENT_DEVICE.dpi;
+    Typography {
+        font_family: reader.and_then(|r| r.font_family.clone()).map(|name| {
+            let dir = if family_names(font_path).contains(&name) {
+                font_path.to_path_buf()
+            } else {
+                PathBuf::from(FONTS_DIR)
+            };
+            (dir, name)
+        }),
+        line_height: reader.and_then(|r| r.line_height),
+        margin_width: reader.and_then(|r| r.margin_width)
+                            .map(|w| (mm_to_in(w as f32) * dpi as f32).round() as i32),
+        text_align: reader.and_then(|r| r.text_align),
+        hyphenation: reader.and_then(|r| r.hyphenation),
+    }
+}
+
+// Paginates a reflowable book as the reader does on the full screen, with the book's settings.
+pub fn layout_book(doc: &mut Document, reader: Option<&ReaderInfo>, font_path: &Path) {
+    if !doc.is_reflowable() {
+        return;
+    }
+    let (width, height) = CURRENT_DEVICE.display_dims();
+    let font_size = reader.and_then(|r| r.font_size).unwrap_or(DEFAULT_FONT_SIZE);
+    doc.set_typography(&book_typography(reader, font_path));
+    doc.layout(width as f32, height as f32, pt_to_px(font_size, CURRENT_DEVICE.dpi));
+}
+
 pub fn open<P: AsRef<Path>>(path: P) -> Option<Box<Document>> {
     file_kind(path.as_ref()).and_then(|k| {
         match k.as_ref() {
@@ -200,21 +461,31 @@ pub fn open<P: AsRef<Path>>(path: P) -> Option<Box<Document>> {
                     .and_then(|o| o.open(path)
                                    .map(|d| Box::new(d) as Box<Document>))
             },
-            _ => {
-                PdfOpener::new()
-                    .and_then(|mut o| {
-                        let css_path = Path::new("user.css");
-                        if css_path.exists() && o.set_user_css(css_path).is_err() {
-                            return None;
-                        }
-                        o.open(path)
-                         .map(|d| Box::new(d) as Box<Document>)
-                    })
+            // Fall back to MuPDF for the documents we can't handle.
+            "epub" => {
+                EpubDocument::new(path.as_ref())
+                    .map_err(|e| eprintln!("Can't open {}: {}", path.as_ref().display(), e))
+                    .ok()
+                    .map(|d| Box::new(d) as Box<Document>)
+                    .or_else(|| open_with_mupdf(path))
             },
+            _ => open_with_mupdf(path),
         }
     })
 }
 
+fn open_with_mupdf<P: AsRef<Path>>(path: P) -> Option<Box<Document>> {
+    PdfOpener::new()
+        .and_then(|mut o| {
+            let css_path = Path::new(USER_STYLESHEET);
+            if css_path.exists() && o.set_user_css(css_path).is_err() {
+                return None;
+            }
+            o.open(path)
+             .map(|d| Box::new(d) as Box<Document>)
+        })
+}
+
 // cd mupdf/source && awk '/_extensions\[/,/}/' */*.c
 lazy_static! {
 pub static ref RECOGNIZED_KINDS: FnvHashSet<&'static str> =
@@ -270,3 +541,60 @@ pub static ref RECOGNIZED_KINDS: FnvHashSet<&'static str> =
     "xps",
     ].iter().cloned().collect();
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn word(text: &str, x: i32, y: i32) -> BoundedText {
+        BoundedText { rect: rect![x, y, x + 10 * text.len() as i32, y + 20], text: text.to_string() }
+    }
+
+    #[test]
+    fn test_is_note_mark() {
+        assert!(is_note_mark("12"));
+        assert!(is_note_mark("[3]"));
+        assert!(is_note_mark("²"));
+        assert!(is_note_mark("†"));
+        assert!(!is_note_mark("1789."));
+        assert!(!is_note_mark("a"));
+        assert!(!is_note_mark("()"));
+    }
+
+    #[test]
+    fn test_find_phrases() {
+        let words: Vec<BoundedText> = ["Of", "free", "will,", "or", "free", "willed."].iter()
+                                                                                      .map(|t| word(t, 0, 0))
+                                                                                      .collect();
+        let query = Regex::new(r"(?i)free\s+will\b").unwrap();
+        assert_eq!(find_phrases(&query, &words), vec![(1, 3)]);
+        let query = Regex::new("ree wi").unwrap();
+        assert_eq!(find_phrases(&query, &words), vec![(1, 3), (4, 6)]);
+        let query = Regex::new(r"\s").unwrap();
+        assert!(find_phrases(&query, &words).is_empty());
+    }
+
+    #[test]
+    fn test_snippet() {
+        let words: Vec<BoundedText> = (0..20).map(|i| word(&i.to_string(), 0, 0)).collect();
+        let s = snippet(&words, 10, 12);
+        assert_eq!(s.text, "2 3 4 5 6 7 8 9 10 11 12 13 14 15 16 17 18 19");
+        assert_eq!(s.start, 16);
+        let s = snippet(&words[..3], 0, 1);
+        assert_eq!(s.text, "0 1 2");
+        assert_eq!(s.start, 0);
+    }
+
+    #[test]
+    fn test_note_text() {
+        let words = vec![word("Body", 0, 0), word("text.", 50, 0),
+                         word("1", 0, 400), word("First", 20, 400), word("note", 80, 400),
+                         word("goes", 0, 422), word("on.", 50, 422),
+                         word("2", 0, 444), word("Second.", 20, 444)];
+        assert_eq!(note_text(&words, 400), Some("1 First note goes on.".to_string()));
+        assert_eq!(note_text(&words, 444), Some("2 Second.".to_string()));
+        assert_eq!(note_text(&words, 500), None);
+        assert_eq!(parse_destination("#3,72,400"), Some((2, 400)));
+        assert_eq!(parse_destination("@3"), None);
+    }
+}
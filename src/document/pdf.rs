@@ -6,13 +6,13 @@ use std::ptr;
 use std::slice;
 use std::char;
 use std::rc::Rc;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::io::Read;
-use std::fs::File;
+use std::fs::{self, File};
 use std::ffi::{CString, CStr};
 use std::os::unix::ffi::OsStrExt;
 use failure::Error;
-use document::{Document, BoundedText, TocEntry, Link};
+use document::{Document, BoundedText, TocEntry, Link, Typography, USER_STYLESHEET};
 use framebuffer::Pixmap;
 use geom::Rectangle;
 
@@ -45,6 +45,9 @@ pub struct PdfOpener(Rc<PdfContext>);
 pub struct PdfDocument {
     ctx: Rc<PdfContext>,
     doc: *mut FzDocument,
+    // Needed to reopen the document when the user style sheet changes.
+    path: Option<PathBuf>,
+    typography: Typography,
 }
 
 pub struct PdfPage<'a> {
@@ -78,6 +81,8 @@ impl PdfOpener {
                 Some(PdfDocument {
                     ctx: self.0.clone(),
                     doc,
+                    path: Some(path.as_ref().to_path_buf()),
+                    typography: Typography::default(),
                 })
             }
         }
@@ -95,6 +100,8 @@ impl PdfOpener {
                 Some(PdfDocument {
                     ctx: self.0.clone(),
                     doc,
+                    path: None,
+                    typography: Typography::default(),
                 })
             }
         }
@@ -196,6 +203,10 @@ impl Document for PdfDocument {
         self.page(index).map(|page| page.dims())
     }
 
+    fn boundary_box(&self, index: usize) -> Option<Rectangle> {
+        self.page(index).and_then(|page| page.boundary_box())
+    }
+
     fn toc(&self) -> Option<Vec<TocEntry>> {
         unsafe {
             let outline = mp_load_outline(self.ctx.0, self.doc);
@@ -229,6 +240,34 @@ impl Document for PdfDocument {
         unsafe { fz_is_document_reflowable(self.ctx.0, self.doc) == 1 }
     }
 
+    // The user style sheet is only parsed when a document is opened.
+    fn set_typography(&mut self, typography: &Typography) {
+        if *typography == self.typography || !self.is_reflowable() {
+            return;
+        }
+
+        let path = match self.path {
+            Some(ref path) => CString::new(path.as_os_str().as_bytes()).unwrap(),
+            None => return,
+        };
+
+        let mut css = fs::read_to_string(USER_STYLESHEET).unwrap_or_default();
+        css.push_str(&typography.css());
+
+        if let Ok(css) = CString::new(css) {
+            unsafe {
+                fz_set_user_css(self.ctx.0, css.as_ptr());
+                let doc = mp_open_document(self.ctx.0, path.as_ptr());
+                if doc.is_null() {
+                    return;
+                }
+                fz_drop_document(self.ctx.0, self.doc);
+                self.doc = doc;
+            }
+            self.typography = typography.clone();
+        }
+    }
+
     fn layout(&mut self, width: f32, height: f32, em: f32) {
         unsafe {
             fz_layout_document(self.ctx.0, self.doc,
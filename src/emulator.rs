@@ -15,6 +15,8 @@ extern crate glob;
 extern crate sdl2;
 extern crate fnv;
 extern crate png;
+extern crate zip;
+extern crate flate2;
 extern crate isbn;
 extern crate titlecase;
 
@@ -30,12 +32,14 @@ mod device;
 mod font;
 mod helpers;
 mod document;
+mod dictionary;
 mod metadata;
 mod settings;
 mod frontlight;
 mod lightsensor;
 mod symbolic_path;
 mod trash;
+mod statistics;
 mod app;
 
 use std::process;
@@ -55,7 +59,7 @@ use sdl2::render::{WindowCanvas, BlendMode};
 use sdl2::pixels::{Color as SdlColor, PixelFormatEnum};
 use sdl2::rect::Point as SdlPoint;
 use sdl2::rect::Rect as SdlRect;
-use framebuffer::{Framebuffer, UpdateMode};
+use framebuffer::{Framebuffer, UpdateMode, normalize_rotation};
 use input::{DeviceEvent, FingerStatus};
 use view::{View, Event, ViewId, EntryId, EntryKind};
 use view::{render, render_no_wait, handle_event, fill_crack};
@@ -173,6 +177,14 @@ impl Framebuffer for WindowCanvas {
 
     fn toggle_monochrome(&mut self) {}
 
+    fn set_rotation(&mut self, n: i8) -> Result<(u32, u32), Error> {
+        let (width, height) = CURRENT_DEVICE.dims;
+        let dims = if n % 2 == 0 { (width, height) } else { (height, width) };
+        self.window_mut().set_size(dims.0, dims.1)
+            .map_err(|e| format_err!("Can't resize the window: {}.", e))?;
+        Ok(dims)
+    }
+
     fn dims(&self) -> (u32, u32) {
         self.window().size()
     }
@@ -192,6 +204,13 @@ pub fn run() -> Result<(), Error> {
     let mut fb = window.into_canvas().software().build().unwrap();
     fb.set_blend_mode(BlendMode::Blend);
 
+    if context.settings.rotation != 0 {
+        match fb.set_rotation(context.settings.rotation) {
+            Ok(_) => CURRENT_DEVICE.set_rotation(context.settings.rotation),
+            Err(e) => eprintln!("Can't rotate the framebuffer: {}", e),
+        }
+    }
+
     let (tx, rx) = mpsc::channel();
     let (ty, ry) = mpsc::channel();
     let touch_screen = gesture_events(ry);
@@ -211,7 +230,7 @@ pub fn run() -> Result<(), Error> {
         }
     });
 
-    let fb_rect = fb.rect();
+    let mut fb_rect = fb.rect();
 
     let mut history: Vec<Box<View>> = Vec::new();
     let mut view: Box<View> = Box::new(Home::new(fb_rect, &tx, &mut context)?);
@@ -319,6 +338,16 @@ pub fn run() -> Result<(), Error> {
                     history.push(view as Box<View>);
                     view = Box::new(r) as Box<View>;
                 },
+                Event::OpenAnnotations(ref annotations, ref toc) => {
+                    let r = Reader::from_annotations(fb_rect, annotations, toc, &tx, &mut context);
+                    history.push(view as Box<View>);
+                    view = Box::new(r) as Box<View>;
+                },
+                Event::OpenStatistics(ref report) => {
+                    let r = Reader::from_statistics(fb_rect, report, &tx, &mut context);
+                    history.push(view as Box<View>);
+                    view = Box::new(r) as Box<View>;
+                },
                 Event::Back => {
                     if let Some(v) = history.pop() {
                         view = v;
@@ -371,6 +400,24 @@ pub fn run() -> Result<(), Error> {
                     context.monochrome = !context.monochrome;
                     tx.send(Event::Render(fb_rect, UpdateMode::Gui)).unwrap();
                 },
+                Event::Select(EntryId::Rotate(n)) => {
+                    let n = normalize_rotation(n);
+                    if n == CURRENT_DEVICE.rotation() {
+                        continue;
+                    }
+                    if let Err(e) = fb.set_rotation(n) {
+                        eprintln!("Can't rotate the framebuffer: {}", e);
+                        continue;
+                    }
+                    CURRENT_DEVICE.set_rotation(n);
+                    context.settings.rotation = n;
+                    fb_rect = fb.rect();
+                    for v in history.iter_mut() {
+                        v.resize(fb_rect, &tx, &mut context);
+                    }
+                    view.resize(fb_rect, &tx, &mut context);
+                    tx.send(Event::Render(fb_rect, UpdateMode::Full)).unwrap();
+                },
                 Event::Select(EntryId::TakeScreenshot) => {
                     let name = Local::now().format("screenshot-%Y%m%d_%H%M%S.png");
                     let msg = match fb.save(&name.to_string()) {
@@ -36,7 +36,7 @@ extern {
 pub struct HbGlyphInfo {
     pub codepoint: u32,
     mask: u32,
-    cluster: u32,
+    pub cluster: u32,
     var1: u32,
     var2: u32,
 }
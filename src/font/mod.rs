@@ -7,14 +7,18 @@ use font::harfbuzz_sys::*;
 use font::freetype_sys::*;
 
 use std::ptr;
+use std::fs;
 use std::ffi::CString;
 use std::os::unix::ffi::OsStrExt;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::collections::BTreeSet;
 use std::rc::Rc;
 use failure::Error;
 use geom::Point;
 use framebuffer::Framebuffer;
 
+pub const FONTS_DIR: &str = "fonts";
+
 // Default font size in points
 pub const DEFAULT_FONT_SIZE: f32 = 11.0;
 
@@ -99,22 +103,51 @@ pub struct Fonts {
     display: Font,
 }
 
+impl FontFamily {
+    pub fn from_name(fo: &FontOpener, name: &str) -> Result<FontFamily, Error> {
+        FontFamily::from_dir(fo, Path::new(FONTS_DIR), name)
+    }
+
+    // The missing styles are replaced by the regular one.
+    pub fn from_dir(fo: &FontOpener, dir: &Path, name: &str) -> Result<FontFamily, Error> {
+        let regular = font_path(dir, name, "Regular")
+                          .ok_or_else(|| format_err!("Can't find the font family {}.", name))?;
+        let open = |style| fo.open(font_path(dir, name, style).unwrap_or_else(|| regular.clone()));
+        Ok(FontFamily {
+            regular: fo.open(&regular)?,
+            italic: open("Italic")?,
+            bold: open("Bold")?,
+            bold_italic: open("BoldItalic")?,
+        })
+    }
+}
+
+fn font_path(dir: &Path, name: &str, style: &str) -> Option<PathBuf> {
+    ["ttf", "otf"].iter()
+                  .map(|ext| dir.join(format!("{}-{}.{}", name, style, ext)))
+                  .find(|path| path.exists())
+}
+
+// The names of the font families that have a regular style in the given directory.
+pub fn family_names<P: AsRef<Path>>(dir: P) -> BTreeSet<String> {
+    fs::read_dir(dir).map(|entries| {
+        entries.filter_map(|entry| entry.ok())
+               .filter_map(|entry| {
+                   let name = entry.file_name().to_string_lossy().into_owned();
+                   ["-Regular.ttf", "-Regular.otf"].iter()
+                                                   .find(|suffix| name.ends_with(*suffix))
+                                                   .map(|suffix| name[..name.len() - suffix.len()].to_string())
+               })
+               .collect()
+    }).unwrap_or_default()
+}
+
 impl Fonts {
     pub fn load() -> Result<Fonts, Error> {
         let fo = FontOpener::new()?;
         Ok(Fonts {
-            sans_serif: FontFamily {
-                regular: fo.open("fonts/NotoSans-Regular.ttf")?,
-                italic: fo.open("fonts/NotoSans-Italic.ttf")?,
-                bold: fo.open("fonts/NotoSans-Bold.ttf")?,
-                bold_italic: fo.open("fonts/NotoSans-BoldItalic.ttf")?,
-            },
-            serif: FontFamily {
-                regular: fo.open("fonts/NotoSerif-Regular.ttf")?,
-                italic: fo.open("fonts/NotoSerif-Italic.ttf")?,
-                bold: fo.open("fonts/NotoSerif-Bold.ttf")?,
-                bold_italic: fo.open("fonts/NotoSerif-BoldItalic.ttf")?,
-            },
+            sans_serif: FontFamily::from_name(&fo, "NotoSans")?,
+            serif: FontFamily::from_name(&fo, "NotoSerif")?,
             keyboard: fo.open("fonts/VarelaRound-Regular.ttf")?,
             display: fo.open("fonts/Cormorant-Regular.ttf")?,
         })
@@ -364,6 +397,7 @@ impl Font {
                 render_plan.width += (pos_i.x_advance >> 6) as u32;
                 let glyph = GlyphPlan {
                     codepoint: info_i.codepoint,
+                    cluster: info_i.cluster as usize,
                     advance: pt!(pos_i.x_advance >> 6, pos_i.y_advance >> 6),
                     offset: pt!(pos_i.x_offset >> 6, -pos_i.y_offset >> 6),
                 };
@@ -545,6 +579,8 @@ impl Font {
 #[derive(Debug, Copy, Clone)]
 pub struct GlyphPlan {
     codepoint: u32,
+    // byte offset of the corresponding text
+    cluster: usize,
     offset: Point,
     advance: Point,
 }
@@ -569,6 +605,10 @@ impl RenderPlan {
     pub fn advance_at(&self, index: usize) -> i32 {
         self.glyphs.iter().take(index).map(|g| g.advance.x).sum()
     }
+
+    pub fn cluster_at(&self, index: usize) -> Option<usize> {
+        self.glyphs.get(index).map(|g| g.cluster)
+    }
 }
 
 impl Default for RenderPlan {
@@ -2,7 +2,7 @@ extern crate png;
 
 use std::fs::File;
 use png::HasParameters;
-use framebuffer::{Framebuffer, UpdateMode};
+use framebuffer::{Framebuffer, UpdateMode, rotate_point, rotate_rectangle, normalize_rotation};
 use color::WHITE;
 use geom::{Rectangle, lerp};
 use failure::{Error, ResultExt};
@@ -11,6 +11,7 @@ pub struct ImageFramebuffer {
     width: u32,
     height: u32,
     data: Vec<u8>,
+    rotation: i8,
     inverted: bool,
     monochrome: bool,
 }
@@ -22,6 +23,7 @@ impl ImageFramebuffer {
             width,
             height,
             data: vec![WHITE; len],
+            rotation: 0,
             inverted: false,
             monochrome: false,
         }
@@ -44,6 +46,7 @@ fn transform_color(color: u8, inverted: bool, monochrome: bool) -> u8 {
 
 impl Framebuffer for ImageFramebuffer {
     fn set_pixel(&mut self, x: u32, y: u32, color: u8) {
+        let (x, y) = rotate_point(x, y, self.rotation, (self.width, self.height));
         let addr = (y * self.width + x) as usize;
         self.data[addr] = color;
     }
@@ -53,12 +56,14 @@ impl Framebuffer for ImageFramebuffer {
             self.set_pixel(x, y, color);
             return;
         }
+        let (x, y) = rotate_point(x, y, self.rotation, (self.width, self.height));
         let addr = (y * self.width + x) as usize;
         let blended_color = lerp(self.data[addr] as f32, color as f32, alpha) as u8;
         self.data[addr] = blended_color;
     }
 
     fn invert_region(&mut self, rect: &Rectangle) {
+        let rect = rotate_rectangle(rect, self.rotation, (self.width, self.height));
         for y in rect.min.y..rect.max.y {
             for x in rect.min.x..rect.max.x {
                 let addr = (y * self.width as i32 + x) as usize;
@@ -77,7 +82,7 @@ impl Framebuffer for ImageFramebuffer {
     }
 
     fn save(&self, path: &str) -> Result<(), Error> {
-        let (width, height) = self.dims();
+        let (width, height) = (self.width, self.height);
         let file = File::create(path).context("Can't create output file.")?;
         let mut encoder = png::Encoder::new(file, width, height);
         encoder.set(png::ColorType::Grayscale).set(png::BitDepth::Eight);
@@ -95,7 +100,16 @@ impl Framebuffer for ImageFramebuffer {
         self.monochrome = !self.monochrome;
     }
 
+    fn set_rotation(&mut self, n: i8) -> Result<(u32, u32), Error> {
+        self.rotation = normalize_rotation(n);
+        Ok(self.dims())
+    }
+
     fn dims(&self) -> (u32, u32) {
-        (self.width, self.height)
+        if self.rotation % 2 == 1 {
+            (self.height, self.width)
+        } else {
+            (self.width, self.height)
+        }
     }
 }
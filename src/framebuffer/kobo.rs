@@ -12,7 +12,7 @@ use std::ops::Drop;
 use libc::ioctl;
 use png::HasParameters;
 use geom::Rectangle;
-use framebuffer::{UpdateMode, Framebuffer};
+use framebuffer::{UpdateMode, Framebuffer, rotate_point, rotate_rectangle, normalize_rotation};
 use framebuffer::mxcfb_sys::*;
 use failure::{Error, ResultExt};
 
@@ -37,6 +37,7 @@ pub struct KoboFramebuffer {
     frame_size: libc::size_t, 
     token: u32,
     flags: u32,
+    rotation: i8,
     set_pixel_rgb: SetPixelRgb,
     get_pixel_rgb: GetPixelRgb,
     as_rgb: AsRgb,
@@ -47,6 +48,7 @@ pub struct KoboFramebuffer {
 
 impl Framebuffer for KoboFramebuffer {
     fn set_pixel(&mut self, x: u32, y: u32, color: u8) {
+        let (x, y) = self.physical_point(x, y);
         (self.set_pixel_rgb)(self, x, y, [color, color, color]);
     }
 
@@ -55,6 +57,7 @@ impl Framebuffer for KoboFramebuffer {
             self.set_pixel(x, y, color);
             return;
         }
+        let (x, y) = self.physical_point(x, y);
         let rgb = (self.get_pixel_rgb)(self, x, y);
         let color_alpha = color as f32 * alpha;
         let r = color_alpha + (1.0 - alpha) * rgb[0] as f32;
@@ -64,6 +67,7 @@ impl Framebuffer for KoboFramebuffer {
     }
 
     fn invert_region(&mut self, rect: &Rectangle) {
+        let rect = rotate_rectangle(rect, self.rotation, self.physical_dims());
         for y in rect.min.y..rect.max.y {
             for x in rect.min.x..rect.max.x {
                 let rgb = (self.get_pixel_rgb)(self, x as u32, y as u32);
@@ -104,7 +108,7 @@ impl Framebuffer for KoboFramebuffer {
             flags |= EPDC_FLAG_FORCE_MONOCHROME;
         }
         let update_data = MxcfbUpdateData {
-            update_region: (*rect).into(),
+            update_region: rotate_rectangle(rect, self.rotation, self.physical_dims()).into(),
             waveform_mode,
             update_mode,
             update_marker,
@@ -138,7 +142,7 @@ impl Framebuffer for KoboFramebuffer {
     }
 
     fn save(&self, path: &str) -> Result<(), Error> {
-        let (width, height) = self.dims();
+        let (width, height) = self.physical_dims();
         let file = File::create(path).context("Can't create output file.")?;
         let mut encoder = png::Encoder::new(file, width, height);
         encoder.set(png::ColorType::RGB).set(png::BitDepth::Eight);
@@ -155,12 +159,25 @@ impl Framebuffer for KoboFramebuffer {
         self.flags ^= EPDC_FLAG_FORCE_MONOCHROME;
     }
 
+    fn set_rotation(&mut self, n: i8) -> Result<(u32, u32), Error> {
+        self.rotation = normalize_rotation(n);
+        Ok(self.dims())
+    }
+
     fn width(&self) -> u32 {
-        self.var_info.xres
+        if self.rotation % 2 == 1 {
+            self.var_info.yres
+        } else {
+            self.var_info.xres
+        }
     }
 
     fn height(&self) -> u32 {
-        self.var_info.yres
+        if self.rotation % 2 == 1 {
+            self.var_info.xres
+        } else {
+            self.var_info.yres
+        }
     }
 }
 
@@ -207,6 +224,7 @@ impl KoboFramebuffer {
                    frame_size,
                    token: 1,
                    flags: 0,
+                   rotation: 0,
                    set_pixel_rgb,
                    get_pixel_rgb,
                    as_rgb,
@@ -221,6 +239,15 @@ impl KoboFramebuffer {
         unsafe { slice::from_raw_parts(self.frame as *const u8, self.frame_size) }
     }
 
+    fn physical_dims(&self) -> (u32, u32) {
+        (self.var_info.xres, self.var_info.yres)
+    }
+
+    #[inline]
+    fn physical_point(&self, x: u32, y: u32) -> (u32, u32) {
+        rotate_point(x, y, self.rotation, self.physical_dims())
+    }
+
     pub fn id(&self) -> Cow<str> {
         String::from_utf8_lossy(&self.fix_info.id)
     }
@@ -283,7 +310,7 @@ fn get_pixel_rgb_32(fb: &KoboFramebuffer, x: u32, y: u32) -> [u8; 3] {
 }
 
 fn as_rgb_16(fb: &KoboFramebuffer) -> Vec<u8> {
-    let (width, height) = fb.dims();
+    let (width, height) = fb.physical_dims();
     let mut rgb888 = Vec::with_capacity((width * height * 3) as usize);
     let rgb565 = fb.as_bytes();
     let virtual_width = fb.var_info.xres_virtual as usize;
@@ -298,7 +325,7 @@ fn as_rgb_16(fb: &KoboFramebuffer) -> Vec<u8> {
 }
 
 fn as_rgb_32(fb: &KoboFramebuffer) -> Vec<u8> {
-    let (width, height) = fb.dims();
+    let (width, height) = fb.physical_dims();
     let mut rgb888 = Vec::with_capacity((width * height * 3) as usize);
     let bgra8888 = fb.as_bytes();
     let virtual_width = fb.var_info.xres_virtual as usize;
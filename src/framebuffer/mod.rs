@@ -2,6 +2,7 @@ mod mxcfb_sys;
 mod kobo;
 mod remarkable;
 mod image;
+mod pixmap;
 
 use failure::Error;
 use geom::{Point, Rectangle, surface_area, nearest_segment_point, lerp};
@@ -27,6 +28,34 @@ pub struct Pixmap {
     pub buf: Vec<u8>,
 }
 
+// Maps a point of a frame rotated by *rotation* clockwise quarter turns
+// to the unrotated frame of dimensions *dims*.
+#[inline]
+pub fn rotate_point(x: u32, y: u32, rotation: i8, dims: (u32, u32)) -> (u32, u32) {
+    let (width, height) = dims;
+    match rotation {
+        1 => (width - 1 - y, x),
+        2 => (width - 1 - x, height - 1 - y),
+        3 => (y, height - 1 - x),
+        _ => (x, y),
+    }
+}
+
+pub fn rotate_rectangle(rect: &Rectangle, rotation: i8, dims: (u32, u32)) -> Rectangle {
+    let (width, height) = (dims.0 as i32, dims.1 as i32);
+    match rotation {
+        1 => rect![width - rect.max.y, rect.min.x, width - rect.min.y, rect.max.x],
+        2 => rect![width - rect.max.x, height - rect.max.y, width - rect.min.x, height - rect.min.y],
+        3 => rect![rect.min.y, height - rect.max.x, rect.max.y, height - rect.min.x],
+        _ => *rect,
+    }
+}
+
+#[inline]
+pub fn normalize_rotation(n: i8) -> i8 {
+    ((n % 4) + 4) % 4
+}
+
 pub trait Framebuffer {
     fn set_pixel(&mut self, x: u32, y: u32, color: u8);
     fn set_blended_pixel(&mut self, x: u32, y: u32, color: u8, alpha: f32);
@@ -36,6 +65,8 @@ pub trait Framebuffer {
     fn save(&self, path: &str) -> Result<(), Error>;
     fn toggle_inverted(&mut self);
     fn toggle_monochrome(&mut self);
+    // Rotates the frame by *n* clockwise quarter turns and returns its new dimensions.
+    fn set_rotation(&mut self, n: i8) -> Result<(u32, u32), Error>;
 
     fn width(&self) -> u32 {
         let (width, _) = self.dims();
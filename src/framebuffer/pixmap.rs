@@ -0,0 +1,85 @@
+use std::fs::File;
+use png;
+use png::HasParameters;
+use framebuffer::{Framebuffer, UpdateMode, Pixmap};
+use color::WHITE;
+use geom::{Rectangle, lerp};
+use failure::{Error, ResultExt};
+
+impl Pixmap {
+    pub fn new(width: i32, height: i32) -> Pixmap {
+        let len = (width * height).max(0) as usize;
+        Pixmap {
+            width,
+            height,
+            buf: vec![WHITE; len],
+        }
+    }
+}
+
+// Pixels outside of the pixmap are silently ignored: glyphs can overflow the
+// boundaries of the page.
+impl Framebuffer for Pixmap {
+    fn set_pixel(&mut self, x: u32, y: u32, color: u8) {
+        if x >= self.width as u32 || y >= self.height as u32 {
+            return;
+        }
+        let addr = (y * self.width as u32 + x) as usize;
+        self.buf[addr] = color;
+    }
+
+    fn set_blended_pixel(&mut self, x: u32, y: u32, color: u8, alpha: f32) {
+        if alpha == 1.0 {
+            self.set_pixel(x, y, color);
+            return;
+        }
+        if x >= self.width as u32 || y >= self.height as u32 {
+            return;
+        }
+        let addr = (y * self.width as u32 + x) as usize;
+        let blended_color = lerp(self.buf[addr] as f32, color as f32, alpha) as u8;
+        self.buf[addr] = blended_color;
+    }
+
+    fn invert_region(&mut self, rect: &Rectangle) {
+        for y in rect.min.y.max(0)..rect.max.y.min(self.height) {
+            for x in rect.min.x.max(0)..rect.max.x.min(self.width) {
+                let addr = (y * self.width + x) as usize;
+                let color = 255 - self.buf[addr];
+                self.buf[addr] = color;
+            }
+        }
+    }
+
+    fn update(&mut self, _rect: &Rectangle, _mode: UpdateMode) -> Result<u32, Error> {
+        Ok(1)
+    }
+
+    fn wait(&mut self, _: u32) -> Result<i32, Error> {
+        Ok(1)
+    }
+
+    fn save(&self, path: &str) -> Result<(), Error> {
+        let (width, height) = self.dims();
+        let file = File::create(path).context("Can't create output file.")?;
+        let mut encoder = png::Encoder::new(file, width, height);
+        encoder.set(png::ColorType::Grayscale).set(png::BitDepth::Eight);
+        let mut writer = encoder.write_header().context("Can't write header.")?;
+        writer.write_image_data(&self.buf).context("Can't write data to file.")?;
+        Ok(())
+    }
+
+    fn toggle_inverted(&mut self) {
+    }
+
+    fn toggle_monochrome(&mut self) {
+    }
+
+    fn set_rotation(&mut self, _n: i8) -> Result<(u32, u32), Error> {
+        Err(format_err!("Pixmaps can't be rotated."))
+    }
+
+    fn dims(&self) -> (u32, u32) {
+        (self.width as u32, self.height as u32)
+    }
+}
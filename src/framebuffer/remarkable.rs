@@ -4,7 +4,7 @@ use self::libremarkable::framebuffer as remarkable_fb;
 use self::libremarkable::framebuffer::{FramebufferIO, FramebufferRefresh, FramebufferBase};
 
 use geom::Rectangle;
-use framebuffer::{UpdateMode, Framebuffer};
+use framebuffer::{UpdateMode, Framebuffer, rotate_point, rotate_rectangle, normalize_rotation};
 use failure::{Error};
 
 use self::libremarkable::framebuffer::common::*;
@@ -13,7 +13,8 @@ use self::libremarkable::framebuffer::cgmath::*;
 
 
 pub struct RemarkableFramebuffer<'a>  {
-	 fb: remarkable_fb::core::Framebuffer<'a>
+	 fb: remarkable_fb::core::Framebuffer<'a>,
+	 rotation: i8,
 }
 
 
@@ -22,6 +23,7 @@ impl<'a> Framebuffer for RemarkableFramebuffer<'a> {
     fn set_pixel(&mut self, x: u32, y: u32, color: u8) {
 //        print!("-set_pixel {} {} {}\n", x, y, color);
 //        
+        let (x, y) = self.physical_point(x, y);
         self.fb.write_pixel(Point2 {x: (x as i32), y: (y as i32)}, color::GRAY(255 - color));
 
     }
@@ -31,6 +33,7 @@ impl<'a> Framebuffer for RemarkableFramebuffer<'a> {
             self.set_pixel(x, y, color);
             return;
         }
+        let (x, y) = self.physical_point(x, y);
         let dst_color = self.fb.read_pixel(Point2{y: (y as u32) , x: (x as u32)} ).to_rgb8();
         let (dst_r, dst_g, dst_b) = (dst_color[0], dst_color[1], dst_color[2]);
         let src_alpha = color as f32 * alpha;
@@ -49,6 +52,7 @@ impl<'a> Framebuffer for RemarkableFramebuffer<'a> {
 
     fn update(&mut self, rect: &Rectangle, mode: UpdateMode) -> Result<u32, Error> {
 //        println!("update (mode {:?})",  mode);
+        let rect = rotate_rectangle(rect, self.rotation, self.physical_dims());
 
         let rm_mxcfb_rect = mxcfb_rect {
             top: rect.min.y as u32,
@@ -103,12 +107,25 @@ impl<'a> Framebuffer for RemarkableFramebuffer<'a> {
         println!("toggle_monochrome");
     }
 
+    fn set_rotation(&mut self, n: i8) -> Result<(u32, u32), Error> {
+        self.rotation = normalize_rotation(n);
+        Ok(self.dims())
+    }
+
     fn width(&self) -> u32 {
-        self.fb.var_screen_info.xres
+        if self.rotation % 2 == 1 {
+            self.fb.var_screen_info.yres
+        } else {
+            self.fb.var_screen_info.xres
+        }
     }
 
     fn height(&self) -> u32 {
-        self.fb.var_screen_info.yres
+        if self.rotation % 2 == 1 {
+            self.fb.var_screen_info.xres
+        } else {
+            self.fb.var_screen_info.yres
+        }
     }
 
 }
@@ -117,7 +134,17 @@ impl<'a> RemarkableFramebuffer <'a> {
     pub fn new()  -> Result<RemarkableFramebuffer<'static>, Error>  {
         let framebuffer = remarkable_fb::core::Framebuffer::new("/dev/fb0");
         Ok(RemarkableFramebuffer {
-             fb: framebuffer
+             fb: framebuffer,
+             rotation: 0,
         })
     }
+
+    fn physical_dims(&self) -> (u32, u32) {
+        (self.fb.var_screen_info.xres, self.fb.var_screen_info.yres)
+    }
+
+    #[inline]
+    fn physical_point(&self, x: u32, y: u32) -> (u32, u32) {
+        rotate_point(x, y, self.rotation, self.physical_dims())
+    }
 }
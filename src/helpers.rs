@@ -50,4 +50,25 @@ pub mod simple_date_format {
         let s = String::deserialize(deserializer)?;
         Local.datetime_from_str(&s, FORMAT).map_err(serde::de::Error::custom)
     }
+
+    // The same format for optional dates.
+    pub mod option {
+        use chrono::{DateTime, Local, TimeZone};
+        use serde::{self, Deserialize, Serializer, Deserializer};
+        use super::FORMAT;
+
+        pub fn serialize<S>(date: &Option<DateTime<Local>>, serializer: S) -> Result<S::Ok, S::Error> where S: Serializer {
+            match *date {
+                Some(ref date) => super::serialize(date, serializer),
+                None => serializer.serialize_none(),
+            }
+        }
+
+        pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<DateTime<Local>>, D::Error> where D: Deserializer<'de> {
+            match Option::<String>::deserialize(deserializer)? {
+                Some(s) => Local.datetime_from_str(&s, FORMAT).map(Some).map_err(serde::de::Error::custom),
+                None => Ok(None),
+            }
+        }
+    }
 }
@@ -3,6 +3,7 @@ extern crate serde;
 #[macro_use] extern crate serde_derive;
 extern crate serde_json;
 #[macro_use] extern crate lazy_static;
+#[macro_use] extern crate bitflags;
 extern crate unicode_normalization;
 extern crate libc;
 extern crate regex;
@@ -10,6 +11,7 @@ extern crate isbn;
 extern crate titlecase;
 extern crate fnv;
 extern crate png;
+extern crate zip;
 extern crate chrono;
 extern crate reqwest;
 extern crate getopts;
@@ -21,6 +23,7 @@ mod device;
 mod input;
 mod unit;
 mod framebuffer;
+mod font;
 mod helpers;
 mod document;
 mod metadata;
@@ -32,18 +35,21 @@ mod symbolic_path;
 use std::env;
 use std::fs;
 use std::process;
-use std::io::Read;
-use std::path::Path;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use chrono::{Local, DateTime};
 use failure::{Error, ResultExt};
 use regex::Regex;
 use getopts::Options;
 use html_entities::decode_html_entities;
 use titlecase::titlecase;
-use helpers::{load_json, save_json};
-use settings::ImportSettings;
+use helpers::{load_json, save_json, simple_date_format};
+use settings::{ImportSettings, ReaderSettings};
 use metadata::{Info, Metadata, METADATA_FILENAME, IMPORTED_MD_FILENAME};
-use metadata::{import};
-use document::{open, asciify};
+use metadata::{import, TextIndex};
+use document::{open, asciify, chapter_at, layout_book};
+
+const ANNOTATIONS_FILENAME: &str = "annotations.json";
 
 pub fn run() -> Result<(), Error> {
     let args: Vec<String> = env::args().skip(1).collect();
@@ -60,6 +66,8 @@ pub fn run() -> Result<(), Error> {
     opts.optflag("N", "rename", "Rename files based on their info.");
     opts.optflag("Y", "synchronize", "Synchronize libraries.");
     opts.optflag("Z", "initialize", "Initialize a database.");
+    opts.optflag("E", "export-annotations", "Export the highlights and notes.");
+    opts.optflag("X", "index", "Build or update the full-text index.");
     opts.optopt("a", "allowed-kinds", "Comma separated list of allowed kinds.", "ALLOWED_KINDS");
     opts.optopt("i", "input", "Input file name.", "INPUT_NAME");
     opts.optopt("o", "output", "Output file name.", "OUTPUT_NAME");
@@ -67,7 +75,7 @@ pub fn run() -> Result<(), Error> {
     let matches = opts.parse(&args).context("Failed to parse the command line arguments.")?;
 
     if matches.opt_present("h") {
-        println!("{}", opts.usage("Usage: plato-import -h|-I|-S|-R[s]|-M|-C|-N|-Z|-Y [-a ALLOWED_KINDS] [-i INPUT_NAME] [-o OUTPUT_NAME] LIBRARY_PATH [DEST_LIBRARY_PATH]"));
+        println!("{}", opts.usage("Usage: plato-import -h|-I|-S|-R[s]|-M|-C|-N|-Z|-Y|-E|-X [-a ALLOWED_KINDS] [-i INPUT_NAME] [-o OUTPUT_NAME] LIBRARY_PATH [DEST_PATH]"));
         return Ok(());
     }
 
@@ -81,6 +89,8 @@ pub fn run() -> Result<(), Error> {
 
     let input_path = library_path.join(&input_name);
     let output_path = library_path.join(&output_name);
+    // Where the user's font families are found when the library is on the device.
+    let font_path = ReaderSettings::default().font_path;
 
 
     if matches.opt_present("Z") {
@@ -95,6 +105,31 @@ pub fn run() -> Result<(), Error> {
                                    .unwrap_or_else(|| ImportSettings::default().allowed_kinds);
         let metadata = import(library_path, &metadata, &allowed_kinds)?;
         save_json(&metadata, output_path)?;
+        // The new books are added to the full-text index, once it has been built.
+        let mut index = TextIndex::load(library_path);
+        if !index.is_empty() {
+            let count = index.add(library_path, &metadata, &font_path);
+            if count > 0 {
+                println!("Indexed {} book(s).", count);
+                index.save(library_path)?;
+            }
+        }
+    } else if matches.opt_present("X") {
+        let metadata = load_json(input_path)?;
+        let mut index = TextIndex::load(library_path);
+        let removed = index.retain(&metadata);
+        let added = index.add(library_path, &metadata, &font_path);
+        println!("Indexed {} book(s), removed {} book(s).", added, removed);
+        index.save(library_path)?;
+    } else if matches.opt_present("E") {
+        if matches.free.len() < 2 {
+            return Err(format_err!("Missing required argument: destination path."));
+        }
+
+        let metadata = load_json(input_path)?;
+        let dest_path = Path::new(&matches.free[1]);
+
+        export_annotations(library_path, dest_path, &metadata, &font_path)?;
     } else {
         let mut metadata = load_json(&output_path)?;
 
@@ -318,6 +353,119 @@ pub fn synchronize(src_dir: &Path, dest_dir: &Path, metadata: &Metadata) {
     }
 }
 
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BookAnnotations {
+    pub title: String,
+    pub author: String,
+    pub path: PathBuf,
+    pub annotations: Vec<ExportedAnnotation>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportedAnnotation {
+    pub page: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub chapter: Option<String>,
+    pub text: String,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub note: String,
+    #[serde(with = "simple_date_format")]
+    pub modified: DateTime<Local>,
+}
+
+pub fn export_annotations(dir: &Path, dest_dir: &Path, metadata: &Metadata, font_path: &Path) -> Result<(), Error> {
+    if !dest_dir.exists() {
+        fs::create_dir_all(dest_dir)
+          .context(format!("Can't create {}.", dest_dir.display()))?;
+    }
+
+    let mut books = Vec::new();
+
+    for info in metadata {
+        let reader_info = match info.reader {
+            Some(ref r) if !r.annotations.is_empty() => r,
+            _ => continue,
+        };
+
+        let path = dir.join(&info.file.path);
+        let mut pages_count = reader_info.pages_count;
+        let toc = open(&path).and_then(|mut doc| {
+            layout_book(doc.as_mut(), Some(reader_info), font_path);
+            pages_count = doc.pages_count();
+            doc.toc()
+        });
+        // The pagination can still differ from the reader's, e.g. on a rotated screen.
+        let ratio = pages_count as f32 / reader_info.pages_count.max(1) as f32;
+
+        if toc.is_none() {
+            println!("Can't read the table of contents of {}.", path.display());
+        }
+
+        let annotations = reader_info.annotations.iter().map(|annot| {
+            ExportedAnnotation {
+                page: annot.page + 1,
+                chapter: toc.as_ref().and_then(|t| chapter_at(t, (annot.page as f32 * ratio) as usize))
+                            .map(|c| c.title.clone()),
+                text: annot.text.clone(),
+                note: annot.note.clone(),
+                modified: annot.modified,
+            }
+        }).collect();
+
+        let book = BookAnnotations {
+            title: info.title(),
+            author: info.author().to_string(),
+            path: info.file.path.clone(),
+            annotations,
+        };
+
+        let md_path = dest_dir.join(format!("{}.md", export_stem(&info.file.path)));
+        let mut file = fs::File::create(&md_path)
+                            .context(format!("Can't create {}.", md_path.display()))?;
+        file.write_all(annotations_as_markdown(&book).as_bytes())
+            .context(format!("Can't write {}.", md_path.display()))?;
+        println!("{}", md_path.display());
+
+        books.push(book);
+    }
+
+    save_json(&books, dest_dir.join(ANNOTATIONS_FILENAME))
+}
+
+// The books with the same name in different directories get different files.
+fn export_stem(path: &Path) -> String {
+    path.with_extension("").iter()
+        .map(|c| c.to_string_lossy().into_owned())
+        .collect::<Vec<String>>()
+        .join(" - ")
+}
+
+pub fn annotations_as_markdown(book: &BookAnnotations) -> String {
+    let mut buf = format!("# {}\n\n*{}*\n", book.title, book.author);
+    let mut chapter = None;
+
+    for annot in &book.annotations {
+        if annot.chapter.is_some() && annot.chapter != chapter {
+            chapter = annot.chapter.clone();
+            buf.push_str(&format!("\n## {}\n", chapter.as_ref().unwrap()));
+        }
+
+        buf.push_str(&format!("\n### Page {}\n\n", annot.page));
+
+        for line in annot.text.lines() {
+            buf.push_str(&format!("> {}\n", line));
+        }
+
+        if !annot.note.is_empty() {
+            buf.push_str(&format!("\n{}\n", annot.note));
+        }
+    }
+
+    buf
+}
+
 pub fn file_name_from_info(info: &Info) -> String {
     if info.title.is_empty() {
         return "".to_string();
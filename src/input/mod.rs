@@ -242,6 +242,57 @@ fn parse_usb_events(tx: &Sender<DeviceEvent>) {
 
 pub fn device_events(rx: Receiver<InputEvent>, dims: (u32, u32)) -> Receiver<DeviceEvent> {
     let (ty, ry) = mpsc::channel();
+    let (tz, rz) = mpsc::channel();
     thread::spawn(move || CURRENT_DEVICE.parse_device_events(&rx, &ty, dims));
-    ry
+    thread::spawn(move || {
+        while let Ok(evt) = ry.recv() {
+            let evt = match evt {
+                DeviceEvent::Finger { id, time, status, position } => {
+                    let position = rotate_position(position, CURRENT_DEVICE.rotation(), dims);
+                    DeviceEvent::Finger { id, time, status, position }
+                },
+                _ => evt,
+            };
+            tz.send(evt).unwrap();
+        }
+    });
+    rz
+}
+
+// Maps a position on the physical screen to the frame rotated
+// by *rotation* clockwise quarter turns.
+fn rotate_position(position: Point, rotation: i8, dims: (u32, u32)) -> Point {
+    let (width, height) = (dims.0 as i32, dims.1 as i32);
+    match rotation {
+        1 => pt!(position.y, width - 1 - position.x),
+        2 => pt!(width - 1 - position.x, height - 1 - position.y),
+        3 => pt!(height - 1 - position.y, position.x),
+        _ => position,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use framebuffer::{rotate_point, rotate_rectangle};
+
+    #[test]
+    fn test_rotations_are_inverses() {
+        let dims = (6, 4);
+        for rotation in 0..4 {
+            let (width, height) = if rotation % 2 == 1 { (dims.1, dims.0) } else { dims };
+            for x in 0..width {
+                for y in 0..height {
+                    let (px, py) = rotate_point(x, y, rotation, dims);
+                    assert!(px < dims.0 && py < dims.1);
+                    assert_eq!(rotate_position(pt!(px as i32, py as i32), rotation, dims),
+                               pt!(x as i32, y as i32));
+                    let (x, y) = (x as i32, y as i32);
+                    let (px, py) = (px as i32, py as i32);
+                    assert_eq!(rotate_rectangle(&rect![x, y, x + 1, y + 1], rotation, dims),
+                               rect![px, py, px + 1, py + 1]);
+                }
+            }
+        }
+    }
 }
@@ -15,6 +15,8 @@ extern crate chrono;
 extern crate glob;
 extern crate fnv;
 extern crate png;
+extern crate zip;
+extern crate flate2;
 extern crate isbn;
 extern crate titlecase;
 
@@ -30,10 +32,12 @@ mod input;
 mod gesture;
 mod helpers;
 mod document;
+mod dictionary;
 mod metadata;
 mod symbolic_path;
 mod settings;
 mod trash;
+mod statistics;
 mod view;
 mod font;
 mod app;
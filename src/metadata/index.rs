@@ -0,0 +1,199 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::collections::BTreeMap;
+use document::{Document, open, asciify, layout_book};
+use helpers::{load_json, save_json};
+use failure::{Error, ResultExt};
+use super::{Info, Metadata};
+
+pub const INDEX_FILENAME: &str = ".text-index.json";
+
+// The full-text index of the library: for each book, the pages on which each word appears.
+//
+// The pages of the reflowable documents are those of the layout given by the book's reader settings.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct TextIndex {
+    pub books: Vec<IndexedBook>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct IndexedBook {
+    pub path: PathBuf,
+    // The size of the file when it was indexed: the book is indexed again if it changes.
+    pub size: u64,
+    pub pages_count: usize,
+    pub words: BTreeMap<String, Vec<usize>>,
+}
+
+// The pages of a book that contain the searched words.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BookHits {
+    pub path: PathBuf,
+    pub pages_count: usize,
+    pub pages: Vec<usize>,
+}
+
+impl TextIndex {
+    // A missing or invalid index is an empty one.
+    pub fn load(dir: &Path) -> TextIndex {
+        let path = dir.join(INDEX_FILENAME);
+        if !path.exists() {
+            return TextIndex::default();
+        }
+        load_json(&path).map_err(|e| eprintln!("Can't load the text index: {}", e))
+                        .unwrap_or_default()
+    }
+
+    // The index is written to a temporary file first: it can be read while being updated.
+    pub fn save(&self, dir: &Path) -> Result<(), Error> {
+        let path = dir.join(INDEX_FILENAME);
+        let tmp_path = path.with_extension("tmp");
+        save_json(self, &tmp_path)?;
+        fs::rename(&tmp_path, &path).context("Can't rename the text index.")?;
+        Ok(())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.books.is_empty()
+    }
+
+    // Indexes the books that aren't indexed yet, or that changed since.
+    // Returns the number of indexed books. The user's font families are searched in *font_path*.
+    pub fn add(&mut self, dir: &Path, metadata: &Metadata, font_path: &Path) -> usize {
+        let mut count = 0;
+
+        for info in metadata {
+            let position = self.books.iter().position(|b| b.path == info.file.path);
+
+            if let Some(index) = position {
+                if self.books[index].size == info.file.size {
+                    continue;
+                }
+                self.books.remove(index);
+            }
+
+            let path = dir.join(&info.file.path);
+
+            if let Some(mut doc) = open(&path) {
+                let words = index_document(doc.as_mut(), info, font_path);
+                self.books.push(IndexedBook {
+                    path: info.file.path.clone(),
+                    size: info.file.size,
+                    pages_count: doc.pages_count(),
+                    words,
+                });
+                count += 1;
+            }
+        }
+
+        count
+    }
+
+    // Removes the books that aren't in the library anymore.
+    // Returns the number of removed books.
+    pub fn retain(&mut self, metadata: &Metadata) -> usize {
+        let count = self.books.len();
+        self.books.retain(|b| metadata.iter().any(|info| info.file.path == b.path));
+        count - self.books.len()
+    }
+
+    // The books that contain all the words of the query on a same page, with those pages.
+    // The books with the most pages come first.
+    pub fn search(&self, text: &str) -> Vec<BookHits> {
+        let words: Vec<String> = text.split_whitespace().filter_map(normalize_word).collect();
+
+        if words.is_empty() {
+            return Vec::new();
+        }
+
+        let mut results: Vec<BookHits> = self.books.iter().filter_map(|book| {
+            let mut pages = book.words.get(&words[0])?.clone();
+            for word in &words[1..] {
+                let other = book.words.get(word)?;
+                pages.retain(|p| other.binary_search(p).is_ok());
+            }
+            if pages.is_empty() {
+                None
+            } else {
+                Some(BookHits {
+                    path: book.path.clone(),
+                    pages_count: book.pages_count,
+                    pages,
+                })
+            }
+        }).collect();
+
+        results.sort_by(|a, b| b.pages.len().cmp(&a.pages.len()));
+        results
+    }
+}
+
+fn index_document(doc: &mut Document, info: &Info, font_path: &Path) -> BTreeMap<String, Vec<usize>> {
+    layout_book(doc, info.reader.as_ref(), font_path);
+
+    let mut words: BTreeMap<String, Vec<usize>> = BTreeMap::new();
+
+    for index in 0..doc.pages_count() {
+        for word in doc.words(index).unwrap_or_default() {
+            if let Some(key) = normalize_word(&word.text) {
+                let pages = words.entry(key).or_insert_with(Vec::new);
+                // The pages are visited in order.
+                if pages.last() != Some(&index) {
+                    pages.push(index);
+                }
+            }
+        }
+    }
+
+    words
+}
+
+// The form under which the words are indexed: without the surrounding punctuation,
+// the accents and the case.
+pub fn normalize_word(word: &str) -> Option<String> {
+    let word = word.trim_matches(|c: char| !c.is_alphanumeric());
+    if word.is_empty() {
+        None
+    } else {
+        Some(asciify(word).to_lowercase())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn book(path: &str, words: &[(&str, Vec<usize>)]) -> IndexedBook {
+        IndexedBook {
+            path: PathBuf::from(path),
+            size: 0,
+            pages_count: 10,
+            words: words.iter().map(|&(w, ref p)| (w.to_string(), p.clone())).collect(),
+        }
+    }
+
+    #[test]
+    fn test_normalize_word() {
+        assert_eq!(normalize_word("«Été»,"), Some("ete".to_string()));
+        assert_eq!(normalize_word("Cœur"), Some("coeur".to_string()));
+        assert_eq!(normalize_word("—"), None);
+    }
+
+    #[test]
+    fn test_search() {
+        let index = TextIndex {
+            books: vec![book("a.pdf", &[("whale", vec![3, 8]), ("white", vec![8, 9])]),
+                        book("b.epub", &[("whale", vec![1, 2, 5]), ("white", vec![1, 2, 7])]),
+                        book("c.djvu", &[("white", vec![4])])],
+        };
+        let hits: Vec<(PathBuf, Vec<usize>)> = index.search("White WHALE").into_iter()
+                                                      .map(|h| (h.path, h.pages)).collect();
+        assert_eq!(hits, vec![(PathBuf::from("b.epub"), vec![1, 2]),
+                              (PathBuf::from("a.pdf"), vec![8])]);
+        assert_eq!(index.search("white").len(), 3);
+        assert!(index.search("moby").is_empty());
+        assert!(index.search(" ").is_empty());
+    }
+}
@@ -1,5 +1,8 @@
 extern crate serde_json;
 
+mod query;
+mod index;
+
 use std::fs;
 use std::path::{self, Path, PathBuf};
 use std::collections::BTreeSet;
@@ -7,10 +10,14 @@ use std::cmp::Ordering;
 use fnv::{FnvHashMap, FnvHashSet};
 use chrono::{Local, DateTime};
 use helpers::simple_date_format;
-use regex::Regex;
+use regex::{self, Regex};
 use document::file_kind;
 use symbolic_path;
 use failure::{Error, ResultExt};
+use serde::{Deserialize, Deserializer};
+
+pub use self::query::Query;
+pub use self::index::{TextIndex, BookHits};
 
 pub const METADATA_FILENAME: &str = ".metadata.json";
 pub const IMPORTED_MD_FILENAME: &str = ".metadata-imported.json";
@@ -100,6 +107,29 @@ pub enum PageScheme {
     EvenOdd,
 }
 
+// The custom zoom is relative to the width of the screen.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ZoomMode {
+    FitToPage,
+    FitToWidth,
+    Custom(f32),
+}
+
+impl Default for ZoomMode {
+    fn default() -> ZoomMode {
+        ZoomMode::FitToPage
+    }
+}
+
+// The alignment of the paragraphs of reflowable documents.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TextAlign {
+    Justify,
+    Left,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(untagged)]
 pub enum CroppingMargins {
@@ -151,16 +181,162 @@ pub struct ReaderInfo {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub font_size: Option<f32>,
     #[serde(skip_serializing_if = "Option::is_none")]
+    pub font_family: Option<String>,
+    // A multiple of the font size.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub line_height: Option<f32>,
+    // In millimeters.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub margin_width: Option<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub text_align: Option<TextAlign>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hyphenation: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub zoom_mode: Option<ZoomMode>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub first_page: Option<usize>,
-    #[serde(skip_serializing_if = "BTreeSet::is_empty")]
-    pub bookmarks: BTreeSet<usize>,
+    // The pages left by jumping elsewhere, most recent last.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub history: Vec<usize>,
+    // The pages left by going back, most recent last.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub forward_history: Vec<usize>,
+    // Sorted by page.
+    #[serde(skip_serializing_if = "Vec::is_empty", deserialize_with = "deserialize_bookmarks")]
+    pub bookmarks: Vec<Bookmark>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub annotations: Vec<Annotation>,
+    // The rolling average of the time spent per page, in seconds.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seconds_per_page: Option<f32>,
     pub finished: bool,
 }
 
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Bookmark {
+    pub page: usize,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub label: String,
+    // The beginning of the text of the page.
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub excerpt: String,
+    // Unknown for the bookmarks that were a page.
+    #[serde(skip_serializing_if = "Option::is_none", with = "simple_date_format::option")]
+    pub created: Option<DateTime<Local>>,
+}
+
+// The bookmarks used to be a set of pages.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum BookmarkRepr {
+    Page(usize),
+    Bookmark(Bookmark),
+}
+
+fn deserialize_bookmarks<'de, D>(deserializer: D) -> Result<Vec<Bookmark>, D::Error> where D: Deserializer<'de> {
+    let reprs = Vec::<BookmarkRepr>::deserialize(deserializer)?;
+    let mut bookmarks: Vec<Bookmark> = reprs.into_iter().map(|repr| {
+        match repr {
+            BookmarkRepr::Page(page) => Bookmark { page, .. Default::default() },
+            BookmarkRepr::Bookmark(bookmark) => bookmark,
+        }
+    }).collect();
+    bookmarks.sort_by_key(|b| b.page);
+    bookmarks.dedup_by_key(|b| b.page);
+    Ok(bookmarks)
+}
+
+// A highlighted range of words, *start* and *end* are the (inclusive) indices
+// of the first and last words in the list returned by `Document::words`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Annotation {
+    pub page: usize,
+    pub start: usize,
+    pub end: usize,
+    pub text: String,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub note: String,
+    #[serde(with = "simple_date_format")]
+    pub modified: DateTime<Local>,
+}
+
+impl Default for Annotation {
+    fn default() -> Self {
+        Annotation {
+            page: 0,
+            start: 0,
+            end: 0,
+            text: String::default(),
+            note: String::default(),
+            modified: Local::now(),
+        }
+    }
+}
+
 impl ReaderInfo {
     pub fn progress(&self) -> f32 {
         (self.current_page + 1) as f32 / self.pages_count as f32
     }
+
+    // Keeps the annotations sorted, returns the index of the new one.
+    pub fn add_annotation(&mut self, annot: Annotation) -> usize {
+        let index = self.annotations.iter()
+                        .position(|a| (a.page, a.start) > (annot.page, annot.start))
+                        .unwrap_or_else(|| self.annotations.len());
+        self.annotations.insert(index, annot);
+        index
+    }
+
+    pub fn bookmark_index(&self, page: usize) -> Option<usize> {
+        self.bookmarks.binary_search_by_key(&page, |b| b.page).ok()
+    }
+
+    // Adds a bookmark to the current page, or removes the existing one.
+    pub fn toggle_bookmark(&mut self, excerpt: String) {
+        match self.bookmarks.binary_search_by_key(&self.current_page, |b| b.page) {
+            Ok(index) => {
+                self.bookmarks.remove(index);
+            },
+            Err(index) => {
+                let bookmark = Bookmark {
+                    page: self.current_page,
+                    excerpt,
+                    created: Some(Local::now()),
+                    .. Default::default()
+                };
+                self.bookmarks.insert(index, bookmark);
+            },
+        }
+    }
+
+    // Maps the pages to a new pagination of the document.
+    pub fn rescale_pages(&mut self, pages_count: usize) {
+        let ratio = pages_count as f32 / self.pages_count as f32;
+        let rescale = |page: usize| ((page as f32 * ratio) as usize).min(pages_count.saturating_sub(1));
+        self.current_page = rescale(self.current_page);
+        if let Some(ref mut page) = self.first_page {
+            *page = rescale(*page);
+        }
+        for page in self.history.iter_mut().chain(self.forward_history.iter_mut()) {
+            *page = rescale(*page);
+        }
+        for bookmark in &mut self.bookmarks {
+            bookmark.page = rescale(bookmark.page);
+        }
+        // Several bookmarks can end up on the same page.
+        self.bookmarks.dedup_by_key(|b| b.page);
+        for annot in &mut self.annotations {
+            annot.page = rescale(annot.page);
+        }
+        // Shorter pages are read faster.
+        if let Some(ref mut spp) = self.seconds_per_page {
+            *spp /= ratio;
+        }
+        self.pages_count = pages_count;
+    }
 }
 
 impl Default for ReaderInfo {
@@ -170,9 +346,19 @@ impl Default for ReaderInfo {
             current_page: 0,
             pages_count: 1,
             font_size: None,
+            font_family: None,
+            line_height: None,
+            margin_width: None,
+            text_align: None,
+            hyphenation: None,
+            zoom_mode: None,
             first_page: None,
             cropping_margins: None,
-            bookmarks: BTreeSet::new(),
+            history: Vec::new(),
+            forward_history: Vec::new(),
+            bookmarks: Vec::new(),
+            annotations: Vec::new(),
+            seconds_per_page: None,
             finished: false,
         }
     }
@@ -220,6 +406,11 @@ impl Info {
         }
     }
 
+    #[inline]
+    pub fn is_finished(&self) -> bool {
+        self.reader.as_ref().map_or(false, |r| r.finished)
+    }
+
     pub fn file_stem(&self) -> String {
         self.file.path.file_stem().unwrap().to_string_lossy().into_owned()
     }
@@ -264,17 +455,8 @@ impl Info {
     }
 
     #[inline]
-    pub fn is_match(&self, query: &Option<Regex>) -> bool {
-        if let Some(ref query) = *query {
-            query.is_match(&self.title) ||
-            query.is_match(&self.subtitle) ||
-            query.is_match(&self.author) ||
-            query.is_match(&self.series) ||
-            self.categories.iter().any(|c| query.is_match(c)) ||
-            self.file.path.to_str().map(|s| query.is_match(s)).unwrap_or(false)
-        } else {
-            true
-        }
+    pub fn is_match(&self, query: &Option<Query>, fuzzy: bool) -> bool {
+        query.as_ref().map_or(true, |q| q.is_match(self, fuzzy))
     }
 
     // TODO: handle the following case: *Walter M. Miller Jr.*?
@@ -300,22 +482,46 @@ impl Info {
     }
 }
 
-pub fn make_query(text: &str) -> Option<Regex> {
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
+pub struct SearchOptions {
+    pub case_sensitive: bool,
+    pub whole_word: bool,
+    // The query is used as is, instead of being matched literally, up to the accents.
+    pub regex: bool,
+}
+
+pub fn make_query(text: &str, options: &SearchOptions) -> Option<Regex> {
     let any = Regex::new(r"^\.*$").unwrap();
 
-    if any.is_match(text) {
+    if any.is_match(text) || text.trim().is_empty() {
         return None;
     }
 
-    let text = text.replace('a', "[aáàâä]")
-                   .replace('e', "[eéèêë]")
-                   .replace('i', "[iíìîï]")
-                   .replace('o', "[oóòôö]")
-                   .replace('u', "[uúùûü]")
-                   .replace('c', "[cç]")
-                   .replace("ae", "(ae|æ)")
-                   .replace("oe", "(oe|œ)");
-    Regex::new(&format!("(?i){}", text))
+    let mut text = if options.regex {
+        text.to_string()
+    } else {
+        // The words of the phrase can be separated by any white space, line breaks included.
+        text.split_whitespace().map(|word| {
+            regex::escape(word).replace('a', "[aáàâä]")
+                               .replace('e', "[eéèêë]")
+                               .replace('i', "[iíìîï]")
+                               .replace('o', "[oóòôö]")
+                               .replace('u', "[uúùûü]")
+                               .replace('c', "[cç]")
+                               .replace("ae", "(ae|æ)")
+                               .replace("oe", "(oe|œ)")
+        }).collect::<Vec<String>>().join(r"\s+")
+    };
+
+    if options.whole_word {
+        text = format!(r"\b(?:{})\b", text);
+    }
+
+    if !options.case_sensitive {
+        text = format!("(?i){}", text);
+    }
+
+    Regex::new(&text)
           .map_err(|e| eprintln!("{}", e))
           .ok()
 }
@@ -435,6 +641,31 @@ pub fn sort_year(i1: &Info, i2: &Info) -> Ordering {
     i1.year.cmp(&i2.year)
 }
 
+// Numbered volumes come first, in numeric order.
+pub fn sort_volumes(i1: &Info, i2: &Info) -> Ordering {
+    match (i1.number.trim().parse::<f32>().ok(), i2.number.trim().parse::<f32>().ok()) {
+        (Some(n1), Some(n2)) => n1.partial_cmp(&n2).unwrap_or(Ordering::Equal),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => sort_title(i1, i2),
+    }
+}
+
+// The volume that follows *info* in its series.
+pub fn next_volume<'a>(metadata: &'a [Info], info: &Info) -> Option<&'a Info> {
+    if info.series.is_empty() {
+        return None;
+    }
+
+    let mut volumes: Vec<&Info> = metadata.iter()
+                                          .filter(|i| i.series == info.series)
+                                          .collect();
+    volumes.sort_by(|a, b| sort_volumes(a, b));
+
+    let index = volumes.iter().position(|i| i.file.path == info.file.path)?;
+    volumes.get(index + 1).cloned()
+}
+
 lazy_static! {
     pub static ref TITLE_PREFIXES: FnvHashMap<&'static str, Regex> = {
         let mut p = FnvHashMap::default();
@@ -501,3 +732,68 @@ fn find_files(root: &Path, dir: &Path) -> Result<Vec<FileInfo>, Error> {
 
     Ok(result)
 }
+
+// A book of a series, for the tests.
+#[cfg(test)]
+pub fn volume(path: &str, series: &str, number: &str, title: &str, finished: bool) -> Info {
+    let mut info = Info::default();
+    info.file.path = PathBuf::from(path);
+    info.series = series.to_string();
+    info.number = number.to_string();
+    info.title = title.to_string();
+    if finished {
+        info.reader = Some(ReaderInfo { finished, .. Default::default() });
+    }
+    info
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sort_volumes() {
+        let mut volumes = vec![volume("c", "S", "", "Extra", false),
+                               volume("b", "S", "10", "Ten", false),
+                               volume("d", "S", "", "Appendix", false),
+                               volume("a", "S", "2.5", "Two and a half", false)];
+        volumes.sort_by(sort_volumes);
+        let paths: Vec<&str> = volumes.iter().map(|i| i.file.path.to_str().unwrap()).collect();
+        assert_eq!(paths, vec!["a", "b", "d", "c"]);
+    }
+
+    #[test]
+    fn test_next_volume() {
+        let metadata = vec![volume("b", "S", "2", "", false),
+                            volume("x", "T", "3", "", false),
+                            volume("a", "S", "1", "", false),
+                            volume("c", "S", "3", "", false),
+                            volume("y", "", "", "", false)];
+        let next = |path: &str| {
+            let info = metadata.iter().find(|i| i.file.path == Path::new(path)).unwrap();
+            next_volume(&metadata, info).map(|i| i.file.path.to_str().unwrap().to_string())
+        };
+        assert_eq!(next("a"), Some("b".to_string()));
+        assert_eq!(next("b"), Some("c".to_string()));
+        assert_eq!(next("c"), None);
+        assert_eq!(next("x"), None);
+        assert_eq!(next("y"), None);
+    }
+
+    #[test]
+    fn test_bookmarks_migration() {
+        let info: ReaderInfo = serde_json::from_str(r#"{"bookmarks": [12, 3, 12]}"#).unwrap();
+        let pages: Vec<usize> = info.bookmarks.iter().map(|b| b.page).collect();
+        assert_eq!(pages, vec![3, 12]);
+        assert!(info.bookmarks.iter().all(|b| b.label.is_empty() && b.created.is_none()));
+
+        let info: ReaderInfo = serde_json::from_str(r#"{"bookmarks": [{"page": 7, "label": "Storm",
+                                                                       "created": "2018-05-02 10:00:00"}, 2]}"#).unwrap();
+        let pages: Vec<usize> = info.bookmarks.iter().map(|b| b.page).collect();
+        assert_eq!(pages, vec![2, 7]);
+        assert_eq!(info.bookmarks[1].label, "Storm");
+        assert!(info.bookmarks[0].created.is_none());
+        assert_eq!(info.bookmarks[1].created.map(|d| d.format("%Y-%m-%d").to_string()),
+                   Some("2018-05-02".to_string()));
+    }
+}
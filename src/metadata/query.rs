@@ -0,0 +1,543 @@
+use std::iter::Peekable;
+use std::str::Chars;
+use regex::{self, Regex};
+use failure::{Error, ResultExt};
+use super::{Info, Status};
+
+// One typo is tolerated for every four characters of a word.
+const CHARS_PER_TYPO: usize = 4;
+
+// A library search query.
+//
+// Terms are separated by spaces and must all match, unless they're separated by *OR*.
+// A term can be a word, a quoted phrase or a regular expression written between slashes.
+// It can be prefixed by a field name (e.g. *author:*) and negated with a leading hyphen.
+// Terms can be grouped with parentheses.
+//
+// In fuzzy mode, words and phrases also match the titles, authors and series
+// that are within a few typos of them.
+#[derive(Debug, Clone)]
+pub enum Query {
+    Text(Option<Field>, Regex, Option<Vec<String>>),
+    Year(Comparison, i32),
+    Kind(String),
+    Status(StatusFilter),
+    Not(Box<Query>),
+    And(Vec<Query>),
+    Or(Vec<Query>),
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Field {
+    Author,
+    Title,
+    Series,
+    Category,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Comparison {
+    Less,
+    LessOrEqual,
+    Equal,
+    GreaterOrEqual,
+    Greater,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum StatusFilter {
+    New,
+    Reading,
+    Finished,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+enum Token {
+    OpenParen,
+    CloseParen,
+    Or,
+    Not,
+    Term(Option<String>, String, bool),
+}
+
+impl Query {
+    pub fn parse(text: &str) -> Result<Query, Error> {
+        let tokens = tokenize(text)?;
+
+        if tokens.is_empty() {
+            return Err(format_err!("Empty query."));
+        }
+
+        let mut parser = Parser { tokens, position: 0 };
+        let query = parser.parse_or()?;
+
+        if parser.position < parser.tokens.len() {
+            return Err(format_err!("Unbalanced parentheses."));
+        }
+
+        Ok(query)
+    }
+
+    pub fn is_match(&self, info: &Info, fuzzy: bool) -> bool {
+        match *self {
+            Query::Text(field, ref re, ref words) => {
+                text_match(field, re, info) ||
+                (fuzzy && words.as_ref().map_or(false, |w| fuzzy_score(field, w, info).is_some()))
+            },
+            Query::Year(cmp, year) => parse_year(&info.year).map_or(false, |y| {
+                match cmp {
+                    Comparison::Less => y < year,
+                    Comparison::LessOrEqual => y <= year,
+                    Comparison::Equal => y == year,
+                    Comparison::GreaterOrEqual => y >= year,
+                    Comparison::Greater => y > year,
+                }
+            }),
+            Query::Kind(ref kind) => info.file.kind == *kind,
+            Query::Status(status) => match (status, info.status()) {
+                (StatusFilter::New, Status::New) |
+                (StatusFilter::Reading, Status::Reading(..)) |
+                (StatusFilter::Finished, Status::Finished) => true,
+                _ => false,
+            },
+            // A typo shouldn't exclude more books than asked for.
+            Query::Not(ref query) => !query.is_match(info, false),
+            Query::And(ref queries) => queries.iter().all(|q| q.is_match(info, fuzzy)),
+            Query::Or(ref queries) => queries.iter().any(|q| q.is_match(info, fuzzy)),
+        }
+    }
+
+    // Exact matches score one point per term, fuzzy matches score less.
+    pub fn score(&self, info: &Info) -> f32 {
+        match *self {
+            Query::Text(field, ref re, ref words) => {
+                if text_match(field, re, info) {
+                    1.0
+                } else {
+                    words.as_ref().and_then(|w| fuzzy_score(field, w, info)).unwrap_or(0.0)
+                }
+            },
+            Query::And(ref queries) => queries.iter().map(|q| q.score(info)).sum(),
+            Query::Or(ref queries) => queries.iter().map(|q| q.score(info)).fold(0.0, f32::max),
+            _ => 0.0,
+        }
+    }
+}
+
+fn text_match(field: Option<Field>, re: &Regex, info: &Info) -> bool {
+    match field {
+        None => {
+            re.is_match(&info.title) ||
+            re.is_match(&info.subtitle) ||
+            re.is_match(&info.author) ||
+            re.is_match(&info.series) ||
+            info.categories.iter().any(|c| re.is_match(c)) ||
+            info.file.path.to_str().map_or(false, |s| re.is_match(s))
+        },
+        Some(Field::Author) => re.is_match(&info.author),
+        Some(Field::Title) => re.is_match(&info.title) || re.is_match(&info.subtitle),
+        Some(Field::Series) => re.is_match(&info.series),
+        Some(Field::Category) => info.categories.iter().any(|c| re.is_match(c)),
+    }
+}
+
+// The average similarity of each word with the closest word of the searched fields.
+// Returns `None` if a word has no counterpart within the allowed number of typos.
+fn fuzzy_score(field: Option<Field>, words: &[String], info: &Info) -> Option<f32> {
+    let fields = match field {
+        None => vec![info.title.as_str(), info.subtitle.as_str(),
+                     info.author.as_str(), info.series.as_str()],
+        Some(Field::Author) => vec![info.author.as_str()],
+        Some(Field::Title) => vec![info.title.as_str(), info.subtitle.as_str()],
+        Some(Field::Series) => vec![info.series.as_str()],
+        Some(Field::Category) => return None,
+    };
+
+    let candidates: Vec<String> = fields.iter().flat_map(|f| fuzzy_words(f)).collect();
+    let mut total = 0.0;
+
+    for word in words {
+        let len = word.chars().count();
+        let distance = candidates.iter().map(|c| edit_distance(word, c)).min()?;
+        if distance > len / CHARS_PER_TYPO {
+            return None;
+        }
+        total += 1.0 - distance as f32 / len as f32;
+    }
+
+    Some(total / words.len() as f32)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next_token(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn parse_or(&mut self) -> Result<Query, Error> {
+        let mut queries = vec![self.parse_and()?];
+
+        while self.peek() == Some(&Token::Or) {
+            self.position += 1;
+            queries.push(self.parse_and()?);
+        }
+
+        if queries.len() == 1 {
+            Ok(queries.pop().unwrap())
+        } else {
+            Ok(Query::Or(queries))
+        }
+    }
+
+    fn parse_and(&mut self) -> Result<Query, Error> {
+        let mut queries = Vec::new();
+
+        loop {
+            match self.peek() {
+                None | Some(&Token::Or) | Some(&Token::CloseParen) => break,
+                _ => queries.push(self.parse_unary()?),
+            }
+        }
+
+        match queries.len() {
+            0 => Err(format_err!("Missing search term.")),
+            1 => Ok(queries.pop().unwrap()),
+            _ => Ok(Query::And(queries)),
+        }
+    }
+
+    fn parse_unary(&mut self) -> Result<Query, Error> {
+        match self.next_token() {
+            Some(Token::Not) => Ok(Query::Not(Box::new(self.parse_unary()?))),
+            Some(Token::OpenParen) => {
+                let query = self.parse_or()?;
+                if self.next_token() != Some(Token::CloseParen) {
+                    return Err(format_err!("Unbalanced parentheses."));
+                }
+                Ok(query)
+            },
+            Some(Token::Term(field, value, quoted)) => make_term(field.as_ref().map(String::as_str), &value, quoted),
+            _ => Err(format_err!("Missing search term.")),
+        }
+    }
+}
+
+fn tokenize(text: &str) -> Result<Vec<Token>, Error> {
+    let mut tokens = Vec::new();
+    let mut chars = text.chars().peekable();
+
+    while let Some(&c) = chars.peek() {
+        match c {
+            _ if c.is_whitespace() => { chars.next(); },
+            '(' => { chars.next(); tokens.push(Token::OpenParen); },
+            ')' => { chars.next(); tokens.push(Token::CloseParen); },
+            '|' => { chars.next(); tokens.push(Token::Or); },
+            '-' => { chars.next(); tokens.push(Token::Not); },
+            '"' => {
+                chars.next();
+                let phrase = read_phrase(&mut chars)?;
+                tokens.push(Token::Term(None, phrase, true));
+            },
+            _ => {
+                let mut word = String::new();
+                while let Some(&c) = chars.peek() {
+                    if c.is_whitespace() || c == '(' || c == ')' || c == '"' {
+                        break;
+                    }
+                    word.push(c);
+                    chars.next();
+                    // A field name followed by a quoted phrase.
+                    if c == ':' && chars.peek() == Some(&'"') {
+                        break;
+                    }
+                }
+
+                if word == "OR" {
+                    tokens.push(Token::Or);
+                    continue;
+                }
+
+                let (field, value) = split_field(&word)?;
+
+                if value.is_empty() && chars.peek() == Some(&'"') {
+                    chars.next();
+                    let phrase = read_phrase(&mut chars)?;
+                    tokens.push(Token::Term(field, phrase, true));
+                } else if value.is_empty() {
+                    return Err(format_err!("Missing value for {}.", word));
+                } else {
+                    tokens.push(Token::Term(field, value, false));
+                }
+            },
+        }
+    }
+
+    Ok(tokens)
+}
+
+fn read_phrase(chars: &mut Peekable<Chars>) -> Result<String, Error> {
+    let mut phrase = String::new();
+
+    for c in chars {
+        if c == '"' {
+            return Ok(phrase);
+        }
+        phrase.push(c);
+    }
+
+    Err(format_err!("Unterminated quote."))
+}
+
+// Splits *author:le* into its field name and value.
+fn split_field(word: &str) -> Result<(Option<String>, String), Error> {
+    if let Some(index) = word.find(':') {
+        let name = &word[..index];
+        if !name.is_empty() && name.chars().all(|c| c.is_ascii_alphabetic()) {
+            let name = name.to_lowercase();
+            match name.as_str() {
+                "author" | "title" | "series" | "year" |
+                "kind" | "status" | "category" => {
+                    return Ok((Some(name), word[index+1..].to_string()));
+                },
+                _ => return Err(format_err!("Unknown field: {}.", name)),
+            }
+        }
+    }
+
+    Ok((None, word.to_string()))
+}
+
+fn make_term(field: Option<&str>, value: &str, quoted: bool) -> Result<Query, Error> {
+    match field {
+        Some("year") => {
+            let (cmp, rest) = if value.starts_with(">=") {
+                (Comparison::GreaterOrEqual, &value[2..])
+            } else if value.starts_with("<=") {
+                (Comparison::LessOrEqual, &value[2..])
+            } else if value.starts_with('>') {
+                (Comparison::Greater, &value[1..])
+            } else if value.starts_with('<') {
+                (Comparison::Less, &value[1..])
+            } else if value.starts_with('=') {
+                (Comparison::Equal, &value[1..])
+            } else {
+                (Comparison::Equal, value)
+            };
+            let year = rest.trim().parse::<i32>()
+                           .map_err(|_| format_err!("Invalid year: {}.", value))?;
+            Ok(Query::Year(cmp, year))
+        },
+        Some("kind") => Ok(Query::Kind(value.trim().to_lowercase())),
+        Some("status") => {
+            let status = match value.trim().to_lowercase().as_str() {
+                "new" | "unread" => StatusFilter::New,
+                "reading" => StatusFilter::Reading,
+                "finished" | "read" => StatusFilter::Finished,
+                _ => return Err(format_err!("Unknown status: {}.", value)),
+            };
+            Ok(Query::Status(status))
+        },
+        _ => {
+            let field = match field {
+                Some("author") => Some(Field::Author),
+                Some("title") => Some(Field::Title),
+                Some("series") => Some(Field::Series),
+                Some("category") => Some(Field::Category),
+                _ => None,
+            };
+            let is_regex = !quoted && value.len() > 2 && value.starts_with('/') && value.ends_with('/');
+            let re = if is_regex {
+                Regex::new(&format!("(?i){}", &value[1..value.len()-1]))
+                      .context("Invalid regular expression.")?
+            } else {
+                Regex::new(&format!("(?i){}", accent_insensitive(value))).unwrap()
+            };
+            let words = if is_regex || field == Some(Field::Category) {
+                None
+            } else {
+                Some(fuzzy_words(value)).filter(|w| !w.is_empty())
+            };
+            Ok(Query::Text(field, re, words))
+        },
+    }
+}
+
+// Builds a pattern that matches *text* regardless of diacritics and ligatures.
+fn accent_insensitive(text: &str) -> String {
+    let mut pattern = String::new();
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        let c = c.to_lowercase().next().unwrap_or(c);
+        match c {
+            'a' if chars.peek() == Some(&'e') => { chars.next(); pattern.push_str("(?:ae|æ)"); },
+            'o' if chars.peek() == Some(&'e') => { chars.next(); pattern.push_str("(?:oe|œ)"); },
+            'a' => pattern.push_str("[aáàâä]"),
+            'e' => pattern.push_str("[eéèêë]"),
+            'i' => pattern.push_str("[iíìîï]"),
+            'o' => pattern.push_str("[oóòôö]"),
+            'u' => pattern.push_str("[uúùûü]"),
+            'c' => pattern.push_str("[cç]"),
+            _ if c.is_whitespace() => pattern.push_str(r"\s+"),
+            _ => pattern.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+
+    pattern
+}
+
+// Lowercase words without diacritics.
+fn fuzzy_words(text: &str) -> Vec<String> {
+    let mut folded = String::new();
+
+    for c in text.chars().flat_map(char::to_lowercase) {
+        match c {
+            'á' | 'à' | 'â' | 'ä' | 'ã' | 'å' => folded.push('a'),
+            'é' | 'è' | 'ê' | 'ë' => folded.push('e'),
+            'í' | 'ì' | 'î' | 'ï' => folded.push('i'),
+            'ó' | 'ò' | 'ô' | 'ö' | 'õ' | 'ø' => folded.push('o'),
+            'ú' | 'ù' | 'û' | 'ü' => folded.push('u'),
+            'ý' | 'ÿ' => folded.push('y'),
+            'ç' => folded.push('c'),
+            'ñ' => folded.push('n'),
+            'æ' => folded.push_str("ae"),
+            'œ' => folded.push_str("oe"),
+            'ß' => folded.push_str("ss"),
+            _ => folded.push(c),
+        }
+    }
+
+    folded.split(|c: char| !c.is_alphanumeric())
+          .filter(|w| !w.is_empty())
+          .map(String::from)
+          .collect()
+}
+
+// The number of insertions, deletions, substitutions and transpositions
+// needed to turn *a* into *b*.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut d = vec![vec![0; b.len() + 1]; a.len() + 1];
+
+    for (i, row) in d.iter_mut().enumerate() {
+        row[0] = i;
+    }
+
+    for (j, cell) in d[0].iter_mut().enumerate() {
+        *cell = j;
+    }
+
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = if a[i-1] == b[j-1] { 0 } else { 1 };
+            d[i][j] = (d[i-1][j] + 1).min(d[i][j-1] + 1).min(d[i-1][j-1] + cost);
+            if i > 1 && j > 1 && a[i-1] == b[j-2] && a[i-2] == b[j-1] {
+                d[i][j] = d[i][j].min(d[i-2][j-2] + 1);
+            }
+        }
+    }
+
+    d[a.len()][b.len()]
+}
+
+// The first number found in *text*: *c. 1850* gives 1850.
+fn parse_year(text: &str) -> Option<i32> {
+    let start = text.find(|c: char| c.is_ascii_digit())?;
+    let digits: String = text[start..].chars().take_while(|c| c.is_ascii_digit()).collect();
+    digits.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use metadata::ReaderInfo;
+
+    fn info() -> Info {
+        let mut info = Info::default();
+        info.title = "Les Misérables".to_string();
+        info.author = "Victor Hugo".to_string();
+        info.year = "1862".to_string();
+        info.categories.insert("Fiction.French".to_string());
+        info.file.kind = "epub".to_string();
+        info
+    }
+
+    fn is_match(text: &str, info: &Info) -> bool {
+        Query::parse(text).unwrap().is_match(info, false)
+    }
+
+    fn is_fuzzy_match(text: &str, info: &Info) -> bool {
+        Query::parse(text).unwrap().is_match(info, true)
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(Query::parse("").is_err());
+        assert!(Query::parse("  ").is_err());
+        assert!(Query::parse("\"les mis").is_err());
+        assert!(Query::parse("(hugo").is_err());
+        assert!(Query::parse("hugo)").is_err());
+        assert!(Query::parse("hugo OR").is_err());
+        assert!(Query::parse("-").is_err());
+        assert!(Query::parse("publisher:penguin").is_err());
+        assert!(Query::parse("year:soon").is_err());
+        assert!(Query::parse("status:later").is_err());
+        assert!(Query::parse("/(/").is_err());
+    }
+
+    #[test]
+    fn test_matches() {
+        let mut info = info();
+        assert!(is_match("miserables", &info));
+        assert!(is_match("hugo miserables", &info));
+        assert!(!is_match("hugo dumas", &info));
+        assert!(is_match("dumas OR hugo", &info));
+        assert!(is_match("dumas | (hugo -zola)", &info));
+        assert!(is_match("author:hugo -title:hugo", &info));
+        assert!(is_match("title:\"les mis\"", &info));
+        assert!(!is_match("author:\"les mis\"", &info));
+        assert!(is_match("year:>1850 year:<=1862", &info));
+        assert!(!is_match("year:<1862", &info));
+        assert!(is_match("kind:EPUB category:french", &info));
+        assert!(is_match("status:new", &info));
+        assert!(is_match("title:/^les\\s/", &info));
+        info.reader = Some(ReaderInfo::default());
+        assert!(is_match("status:reading -status:new", &info));
+    }
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("kafka", "kafka"), 0);
+        assert_eq!(edit_distance("kakfa", "kafka"), 1);
+        assert_eq!(edit_distance("dostoyevski", "dostoevsky"), 2);
+        assert_eq!(edit_distance("", "abc"), 3);
+    }
+
+    #[test]
+    fn test_fuzzy_matches() {
+        let mut info = info();
+        info.author = "Fyodor Dostoevsky".to_string();
+        assert!(!is_match("dostoyevski", &info));
+        assert!(is_fuzzy_match("dostoyevski", &info));
+        assert!(is_fuzzy_match("author:dostoyevski", &info));
+        assert!(!is_fuzzy_match("title:dostoyevski", &info));
+        assert!(is_fuzzy_match("miserabels", &info));
+        assert!(!is_fuzzy_match("tolstoy", &info));
+        assert!(is_fuzzy_match("miserables -dostoyevski", &info));
+        let query = Query::parse("misérables OR dostoyevski").unwrap();
+        assert!(query.score(&info) > 0.5);
+        let query = Query::parse("title:miserables author:dostoyevski").unwrap();
+        assert!(query.score(&info) > 1.5 && query.score(&info) < 2.0);
+    }
+}
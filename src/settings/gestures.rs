@@ -0,0 +1,266 @@
+use geom::{Point, Rectangle, Dir};
+use input::ButtonCode;
+
+// The actions that can be bound to the reader's gestures.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum GestureAction {
+    None,
+    NextPage,
+    PreviousPage,
+    NextChapter,
+    PreviousChapter,
+    ToggleBookmark,
+    NextBookmark,
+    PreviousBookmark,
+    PreviousLocation,
+    NextLocation,
+    TableOfContents,
+    GoToPage,
+    Thumbnails,
+    Bookmarks,
+    Search,
+    ToggleBars,
+    Frontlight,
+    Invert,
+    Refresh,
+    Screenshot,
+    Back,
+    ZoomIn,
+    ZoomOut,
+    ResetZoom,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Region {
+    TopLeftCorner,
+    TopRightCorner,
+    BottomLeftCorner,
+    BottomRightCorner,
+    LeftEar,
+    RightEar,
+    MiddleBand,
+}
+
+impl Region {
+    // The ears are the vertical bands of width *ear_width* on each side of *rect*,
+    // the corners are the triangles cut at the top and bottom of the ears by the diagonals.
+    pub fn locate(point: &Point, rect: &Rectangle, ear_width: i32) -> Region {
+        let x1 = rect.min.x + ear_width;
+        let x2 = rect.max.x - ear_width;
+
+        if point.x < x1 {
+            let dx = x1 - point.x;
+            if point.y < rect.min.y + dx {
+                Region::TopLeftCorner
+            } else if point.y > rect.max.y - dx {
+                Region::BottomLeftCorner
+            } else {
+                Region::LeftEar
+            }
+        } else if point.x > x2 {
+            let dx = point.x - x2;
+            if point.y < rect.min.y + dx {
+                Region::TopRightCorner
+            } else if point.y > rect.max.y - dx {
+                Region::BottomRightCorner
+            } else {
+                Region::RightEar
+            }
+        } else {
+            Region::MiddleBand
+        }
+    }
+}
+
+// The unset entries fall back to the default layout.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct RegionActions {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_left_corner: Option<GestureAction>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_right_corner: Option<GestureAction>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bottom_left_corner: Option<GestureAction>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bottom_right_corner: Option<GestureAction>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub left_ear: Option<GestureAction>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub right_ear: Option<GestureAction>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub middle_band: Option<GestureAction>,
+}
+
+impl RegionActions {
+    pub fn get(&self, region: Region) -> Option<GestureAction> {
+        match region {
+            Region::TopLeftCorner => self.top_left_corner,
+            Region::TopRightCorner => self.top_right_corner,
+            Region::BottomLeftCorner => self.bottom_left_corner,
+            Region::BottomRightCorner => self.bottom_right_corner,
+            Region::LeftEar => self.left_ear,
+            Region::RightEar => self.right_ear,
+            Region::MiddleBand => self.middle_band,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct DirActions {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub north: Option<GestureAction>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub east: Option<GestureAction>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub south: Option<GestureAction>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub west: Option<GestureAction>,
+}
+
+impl DirActions {
+    pub fn get(&self, dir: Dir) -> Option<GestureAction> {
+        match dir {
+            Dir::North => self.north,
+            Dir::East => self.east,
+            Dir::South => self.south,
+            Dir::West => self.west,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct ButtonActions {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub home: Option<GestureAction>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub left: Option<GestureAction>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub right: Option<GestureAction>,
+}
+
+impl ButtonActions {
+    pub fn get(&self, code: ButtonCode) -> Option<GestureAction> {
+        match code {
+            ButtonCode::Home => self.home,
+            ButtonCode::Left => self.left,
+            ButtonCode::Right => self.right,
+            _ => None,
+        }
+    }
+}
+
+// The tables come last: TOML doesn't allow values after tables.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct GestureSettings {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pinch: Option<GestureAction>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub spread: Option<GestureAction>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rotate: Option<GestureAction>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub multi_tap: Option<GestureAction>,
+    pub tap: RegionActions,
+    pub hold_finger: RegionActions,
+    pub swipe: DirActions,
+    pub multi_swipe: DirActions,
+    pub hold_button: ButtonActions,
+}
+
+impl GestureSettings {
+    pub fn tap_action(&self, region: Region) -> GestureAction {
+        self.tap.get(region).unwrap_or(match region {
+            Region::TopLeftCorner => GestureAction::PreviousLocation,
+            Region::TopRightCorner => GestureAction::ToggleBookmark,
+            Region::BottomLeftCorner => GestureAction::TableOfContents,
+            Region::LeftEar => GestureAction::PreviousPage,
+            Region::BottomRightCorner | Region::RightEar => GestureAction::NextPage,
+            Region::MiddleBand => GestureAction::ToggleBars,
+        })
+    }
+
+    pub fn hold_finger_action(&self, region: Region) -> GestureAction {
+        self.hold_finger.get(region).unwrap_or(match region {
+            Region::TopLeftCorner => GestureAction::PreviousBookmark,
+            Region::TopRightCorner => GestureAction::NextBookmark,
+            Region::BottomLeftCorner => GestureAction::Frontlight,
+            Region::BottomRightCorner => GestureAction::Invert,
+            Region::LeftEar => GestureAction::PreviousChapter,
+            Region::RightEar => GestureAction::NextChapter,
+            Region::MiddleBand => GestureAction::Refresh,
+        })
+    }
+
+    pub fn swipe_action(&self, dir: Dir) -> GestureAction {
+        self.swipe.get(dir).unwrap_or(match dir {
+            Dir::West => GestureAction::NextPage,
+            Dir::East => GestureAction::PreviousPage,
+            Dir::North | Dir::South => GestureAction::None,
+        })
+    }
+
+    pub fn multi_swipe_action(&self, dir: Dir) -> GestureAction {
+        self.multi_swipe.get(dir).unwrap_or(match dir {
+            Dir::East => GestureAction::PreviousLocation,
+            Dir::West => GestureAction::NextLocation,
+            Dir::North | Dir::South => GestureAction::None,
+        })
+    }
+
+    pub fn pinch_action(&self) -> GestureAction {
+        self.pinch.unwrap_or(GestureAction::ZoomOut)
+    }
+
+    pub fn spread_action(&self) -> GestureAction {
+        self.spread.unwrap_or(GestureAction::ZoomIn)
+    }
+
+    pub fn multi_tap_action(&self) -> GestureAction {
+        self.multi_tap.unwrap_or(GestureAction::ResetZoom)
+    }
+
+    pub fn rotate_action(&self) -> GestureAction {
+        self.rotate.unwrap_or(GestureAction::None)
+    }
+
+    pub fn hold_button_action(&self, code: ButtonCode) -> GestureAction {
+        self.hold_button.get(code).unwrap_or(GestureAction::None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_locate_region() {
+        let rect = rect![0, 0, 500, 800];
+        assert_eq!(Region::locate(&pt!(10, 20), &rect, 100), Region::TopLeftCorner);
+        assert_eq!(Region::locate(&pt!(10, 400), &rect, 100), Region::LeftEar);
+        assert_eq!(Region::locate(&pt!(50, 780), &rect, 100), Region::BottomLeftCorner);
+        assert_eq!(Region::locate(&pt!(250, 10), &rect, 100), Region::MiddleBand);
+        assert_eq!(Region::locate(&pt!(490, 5), &rect, 100), Region::TopRightCorner);
+        assert_eq!(Region::locate(&pt!(410, 400), &rect, 100), Region::RightEar);
+        assert_eq!(Region::locate(&pt!(480, 790), &rect, 100), Region::BottomRightCorner);
+    }
+
+    #[test]
+    fn test_fallback_actions() {
+        let mut settings = GestureSettings::default();
+        settings.tap.left_ear = Some(GestureAction::NextPage);
+        settings.swipe.west = Some(GestureAction::None);
+        assert_eq!(settings.tap_action(Region::LeftEar), GestureAction::NextPage);
+        assert_eq!(settings.tap_action(Region::RightEar), GestureAction::NextPage);
+        assert_eq!(settings.swipe_action(Dir::West), GestureAction::None);
+        assert_eq!(settings.swipe_action(Dir::East), GestureAction::PreviousPage);
+        assert_eq!(settings.hold_button_action(ButtonCode::Power), GestureAction::None);
+        assert_eq!(settings.pinch_action(), GestureAction::ZoomOut);
+        assert_eq!(settings.multi_swipe_action(Dir::East), GestureAction::PreviousLocation);
+        assert_eq!(settings.multi_swipe_action(Dir::North), GestureAction::None);
+    }
+}
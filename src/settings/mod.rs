@@ -1,10 +1,12 @@
 mod preset;
+mod gestures;
 
 use std::path::PathBuf;
 use fnv::FnvHashSet;
 use frontlight::LightLevels;
 
 pub use self::preset::{LightPreset, guess_frontlight};
+pub use self::gestures::{GestureSettings, GestureAction, Region};
 
 pub const SETTINGS_PATH: &str = "Settings.toml";
 
@@ -13,8 +15,10 @@ pub const SETTINGS_PATH: &str = "Settings.toml";
 pub struct Settings {
     pub library_path: PathBuf,
     pub summary_size: u8,
+    pub fuzzy_search: bool,
     pub frontlight: bool,
     pub wifi: bool,
+    pub rotation: i8,
     #[serde(skip_serializing_if = "Vec::is_empty")]
     pub frontlight_presets: Vec<LightPreset>,
     pub reader: ReaderSettings,
@@ -26,6 +30,8 @@ pub struct Settings {
 #[serde(default, rename_all = "kebab-case")]
 pub struct ImportSettings {
     pub unmount_trigger: bool,
+    // Index the new books in the background, for the searches inside the books.
+    pub text_index: bool,
     pub allowed_kinds: FnvHashSet<String>,
 }
 
@@ -35,6 +41,14 @@ pub struct ReaderSettings {
     pub refresh_every: u8,
     pub finished: FinishedAction,
     pub sleep: u16,
+    pub landscape: LandscapeMode,
+    // Where the user's font families are searched, in addition to the bundled ones.
+    pub font_path: PathBuf,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dictionary: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub time_left: Option<TimeLeft>,
+    pub gestures: GestureSettings,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -42,6 +56,22 @@ pub struct ReaderSettings {
 pub enum FinishedAction {
     Notify,
     Close,
+    NextVolume,
+}
+
+// The estimate shown in the bottom bar of the reader.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TimeLeft {
+    Chapter,
+    Book,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum LandscapeMode {
+    TwoPages,
+    FitToWidth,
 }
 
 impl Default for ReaderSettings {
@@ -50,6 +80,11 @@ impl Default for ReaderSettings {
             refresh_every: 0,
             finished: FinishedAction::Notify,
             sleep: 60,
+            landscape: LandscapeMode::TwoPages,
+            font_path: PathBuf::from("/mnt/onboard/fonts"),
+            dictionary: None,
+            time_left: None,
+            gestures: GestureSettings::default(),
         }
     }
 }
@@ -58,6 +93,7 @@ impl Default for ImportSettings {
     fn default() -> Self {
         ImportSettings {
             unmount_trigger: true,
+            text_index: false,
             allowed_kinds: ["pdf", "djvu", "epub",
                             "fb2", "cbz"].iter().map(|k| k.to_string()).collect(),
         }
@@ -69,12 +105,14 @@ impl Default for Settings {
         Settings {
             library_path: PathBuf::from("/mnt/onboard"),
             summary_size: 1,
+            fuzzy_search: true,
             import: ImportSettings::default(),
             reader: ReaderSettings::default(),
             frontlight_levels: LightLevels::default(),
             frontlight_presets: Vec::new(),
             frontlight: true,
             wifi: false,
+            rotation: 0,
         }
     }
 }
@@ -0,0 +1,311 @@
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+use std::collections::BTreeMap;
+use chrono::{DateTime, Local, NaiveDate, Datelike, Duration};
+use failure::Error;
+use helpers::{load_json, save_json, simple_date_format};
+use document::escape_html;
+
+pub const STATISTICS_FILENAME: &str = ".statistics.json";
+
+// The time spent on a single page is capped: the device might have been left unattended.
+const MAX_PAGE_DURATION: u64 = 5 * 60;
+// Sessions shorter than this, in seconds, aren't recorded.
+const MIN_SESSION_DURATION: u64 = 10;
+// Pages left faster than this, in seconds, were probably skimmed.
+const MIN_PAGE_DURATION: u64 = 3;
+// The weight of the latest page in the rolling average of the reading speed.
+const SPEED_SMOOTHING: f32 = 0.1;
+const DAYS_COUNT: i64 = 7;
+const WEEKS_COUNT: i64 = 4;
+
+// The reading sessions of each book, indexed by path.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Statistics {
+    pub books: BTreeMap<PathBuf, Vec<Session>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Session {
+    #[serde(with = "simple_date_format")]
+    pub start: DateTime<Local>,
+    #[serde(with = "simple_date_format")]
+    pub end: DateTime<Local>,
+    // The active reading time, in seconds.
+    pub duration: u64,
+    pub pages_turned: usize,
+    // The time spent on each page, in seconds.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub pages: BTreeMap<usize, u64>,
+}
+
+// Measures the time spent on each page while a book is opened.
+pub struct SessionTracker {
+    start: DateTime<Local>,
+    page: usize,
+    page_start: Instant,
+    pages_turned: usize,
+    pages: BTreeMap<usize, u64>,
+}
+
+impl SessionTracker {
+    pub fn new(page: usize) -> SessionTracker {
+        SessionTracker {
+            start: Local::now(),
+            page,
+            page_start: Instant::now(),
+            pages_turned: 0,
+            pages: BTreeMap::new(),
+        }
+    }
+
+    // Returns the page that was left and the time spent on it.
+    pub fn turn_page(&mut self, page: usize) -> Option<(usize, u64)> {
+        if page == self.page {
+            return None;
+        }
+        let elapsed = self.stop_page();
+        let previous = self.page;
+        self.page = page;
+        self.pages_turned += 1;
+        Some((previous, elapsed))
+    }
+
+    fn stop_page(&mut self) -> u64 {
+        let elapsed = self.page_start.elapsed().as_secs().min(MAX_PAGE_DURATION);
+        if elapsed > 0 {
+            *self.pages.entry(self.page).or_insert(0) += elapsed;
+        }
+        self.page_start = Instant::now();
+        elapsed
+    }
+
+    pub fn finish(mut self) -> Option<Session> {
+        self.stop_page();
+        let duration = self.pages.values().sum();
+        if duration < MIN_SESSION_DURATION {
+            return None;
+        }
+        Some(Session {
+            start: self.start,
+            end: Local::now(),
+            duration,
+            pages_turned: self.pages_turned,
+            pages: self.pages,
+        })
+    }
+}
+
+// The figures shown by the statistics view.
+#[derive(Debug, Clone)]
+pub struct Report {
+    pub title: String,
+    pub time_spent: u64,
+    pub sessions_count: usize,
+    pub seconds_per_page: Option<f32>,
+    // The title of the current chapter and the number of pages left in it.
+    pub chapter: Option<(String, usize)>,
+    pub pages_left: usize,
+    // The reading time of the last days and weeks, across the library.
+    pub days: Vec<(NaiveDate, u64)>,
+    pub weeks: Vec<(NaiveDate, u64)>,
+}
+
+impl Statistics {
+    pub fn sessions(&self, path: &Path) -> &[Session] {
+        self.books.get(path).map_or(&[][..], Vec::as_slice)
+    }
+
+    // The average time spent per page turn, in seconds.
+    pub fn seconds_per_page(&self, path: &Path) -> Option<f32> {
+        let (duration, pages) = self.sessions(path).iter()
+                                    .filter(|s| s.pages_turned > 0)
+                                    .fold((0, 0), |(d, p), s| (d + s.duration, p + s.pages_turned));
+        if pages > 0 {
+            Some(duration as f32 / pages as f32)
+        } else {
+            None
+        }
+    }
+
+    // The reading time of each of the *count* days ending with *today*, most recent first.
+    pub fn daily_totals(&self, today: NaiveDate, count: i64) -> Vec<(NaiveDate, u64)> {
+        let first = today - Duration::days(count - 1);
+        let mut totals: BTreeMap<NaiveDate, u64> = (0..count).map(|i| (first + Duration::days(i), 0))
+                                                             .collect();
+        for session in self.books.values().flat_map(|v| v.iter()) {
+            let date = session.start.naive_local().date();
+            if let Some(total) = totals.get_mut(&date) {
+                *total += session.duration;
+            }
+        }
+        totals.into_iter().rev().collect()
+    }
+
+    // The reading time of each of the *count* weeks ending with the current one, most recent first.
+    // Weeks are identified by their first day.
+    pub fn weekly_totals(&self, today: NaiveDate, count: i64) -> Vec<(NaiveDate, u64)> {
+        let monday = week_start(today);
+        let first = monday - Duration::weeks(count - 1);
+        let mut totals: BTreeMap<NaiveDate, u64> = (0..count).map(|i| (first + Duration::weeks(i), 0))
+                                                             .collect();
+        for session in self.books.values().flat_map(|v| v.iter()) {
+            let week = week_start(session.start.naive_local().date());
+            if let Some(total) = totals.get_mut(&week) {
+                *total += session.duration;
+            }
+        }
+        totals.into_iter().rev().collect()
+    }
+
+    pub fn report(&self, path: &Path, title: &str, chapter: Option<(String, usize)>, pages_left: usize) -> Report {
+        let sessions = self.sessions(path);
+        let today = Local::now().naive_local().date();
+        Report {
+            title: title.to_string(),
+            time_spent: sessions.iter().map(|s| s.duration).sum(),
+            sessions_count: sessions.len(),
+            seconds_per_page: self.seconds_per_page(path),
+            chapter,
+            pages_left,
+            days: self.daily_totals(today, DAYS_COUNT),
+            weeks: self.weekly_totals(today, WEEKS_COUNT),
+        }
+    }
+}
+
+fn week_start(date: NaiveDate) -> NaiveDate {
+    date - Duration::days(i64::from(date.weekday().num_days_from_monday()))
+}
+
+pub fn load_statistics(library_path: &Path) -> Statistics {
+    let path = library_path.join(STATISTICS_FILENAME);
+    if !path.exists() {
+        return Statistics::default();
+    }
+    load_json(&path).map_err(|e| eprintln!("Can't load statistics: {}", e))
+                    .unwrap_or_default()
+}
+
+pub fn record_session(library_path: &Path, book_path: &Path, session: Session) -> Result<(), Error> {
+    let mut statistics = load_statistics(library_path);
+    statistics.books.entry(book_path.to_path_buf())
+              .or_insert_with(Vec::new)
+              .push(session);
+    save_json(&statistics, library_path.join(STATISTICS_FILENAME))
+}
+
+// Updates the rolling average of the time spent per page after reading *pages* pages in *seconds*.
+pub fn update_reading_speed(speed: Option<f32>, seconds: u64, pages: usize) -> Option<f32> {
+    if pages == 0 || seconds < MIN_PAGE_DURATION * pages as u64 {
+        return speed;
+    }
+    let sample = seconds as f32 / pages as f32;
+    Some(speed.map_or(sample, |s| s + SPEED_SMOOTHING * (sample - s)))
+}
+
+pub fn format_duration(seconds: u64) -> String {
+    let minutes = (seconds + 30) / 60;
+    if minutes == 0 {
+        "< 1 min".to_string()
+    } else if minutes < 60 {
+        format!("{} min", minutes)
+    } else {
+        format!("{} h {:02} min", minutes / 60, minutes % 60)
+    }
+}
+
+pub fn report_as_html(report: &Report) -> String {
+    let mut buf = r#"<html>
+                         <head>
+                             <title>Statistics</title>
+                             <link rel="stylesheet" type="text/css" href="css/statistics.css"/>
+                         </head>
+                     <body>"#.to_string();
+    let duration = |seconds| escape_html(&format_duration(seconds));
+
+    buf.push_str(&format!("<h1>{}</h1><table>", escape_html(&report.title)));
+    buf.push_str(&format!("<tr><td>Time spent</td><td>{}</td></tr>", duration(report.time_spent)));
+    buf.push_str(&format!("<tr><td>Sessions</td><td>{}</td></tr>", report.sessions_count));
+
+    if let Some(spp) = report.seconds_per_page {
+        buf.push_str(&format!("<tr><td>Reading speed</td><td>{:.0} pages per hour</td></tr>",
+                              3600.0 / spp.max(1.0)));
+        if let Some((ref title, pages)) = report.chapter {
+            buf.push_str(&format!("<tr><td>Left in <em>{}</em></td><td>{}</td></tr>",
+                                  escape_html(title), duration((pages as f32 * spp) as u64)));
+        }
+        buf.push_str(&format!("<tr><td>Left in book</td><td>{}</td></tr>",
+                              duration((report.pages_left as f32 * spp) as u64)));
+    } else {
+        buf.push_str("<tr><td>Reading speed</td><td>Unknown</td></tr>");
+    }
+
+    buf.push_str("</table><h2>Days</h2><table>");
+    for &(date, total) in &report.days {
+        buf.push_str(&format!("<tr><td>{}</td><td>{}</td></tr>",
+                              date.format("%A, %B %-d"), duration(total)));
+    }
+
+    buf.push_str("</table><h2>Weeks</h2><table>");
+    for &(date, total) in &report.weeks {
+        buf.push_str(&format!("<tr><td>Week of {}</td><td>{}</td></tr>",
+                              date.format("%B %-d"), duration(total)));
+    }
+
+    buf.push_str("</table></body></html>");
+    buf
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn session(day: u32, duration: u64, pages_turned: usize) -> Session {
+        let start = Local.ymd(2019, 3, day).and_hms(20, 0, 0);
+        Session {
+            start,
+            end: start + Duration::seconds(duration as i64),
+            duration,
+            pages_turned,
+            pages: BTreeMap::new(),
+        }
+    }
+
+    #[test]
+    fn test_totals() {
+        let mut statistics = Statistics::default();
+        // March 11, 2019 is a Monday.
+        statistics.books.insert(PathBuf::from("a.pdf"), vec![session(4, 600, 10), session(11, 300, 0)]);
+        statistics.books.insert(PathBuf::from("b.epub"), vec![session(12, 120, 4), session(13, 60, 1)]);
+        let today = NaiveDate::from_ymd(2019, 3, 13);
+        assert_eq!(statistics.daily_totals(today, 3),
+                   vec![(today, 60),
+                        (NaiveDate::from_ymd(2019, 3, 12), 120),
+                        (NaiveDate::from_ymd(2019, 3, 11), 300)]);
+        assert_eq!(statistics.weekly_totals(today, 3),
+                   vec![(NaiveDate::from_ymd(2019, 3, 11), 480),
+                        (NaiveDate::from_ymd(2019, 3, 4), 600),
+                        (NaiveDate::from_ymd(2019, 2, 25), 0)]);
+        assert_eq!(statistics.seconds_per_page(Path::new("a.pdf")), Some(60.0));
+        assert_eq!(statistics.seconds_per_page(Path::new("c.djvu")), None);
+    }
+
+    #[test]
+    fn test_update_reading_speed() {
+        assert_eq!(update_reading_speed(None, 60, 1), Some(60.0));
+        assert_eq!(update_reading_speed(Some(60.0), 1, 1), Some(60.0));
+        assert_eq!(update_reading_speed(Some(60.0), 80, 2), Some(58.0));
+        assert_eq!(update_reading_speed(None, 30, 0), None);
+    }
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!(format_duration(20), "< 1 min");
+        assert_eq!(format_duration(150), "3 min");
+        assert_eq!(format_duration(3900), "1 h 05 min");
+    }
+}
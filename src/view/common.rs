@@ -46,6 +46,12 @@ pub fn toggle_main_menu(view: &mut View, rect: Rectangle, enable: Option<bool>,
         if let Some(false) = enable {
             return;
         }
+        let rotation = context.settings.rotation;
+        let rotations = ["Portrait", "Landscape", "Inverted Portrait", "Inverted Landscape"].iter().enumerate()
+                                 .map(|(n, name)| EntryKind::RadioButton(name.to_string(),
+                                                                         EntryId::Rotate(n as i8),
+                                                                         n as i8 == rotation))
+                                 .collect::<Vec<EntryKind>>();
         let mut entries = vec![EntryKind::CheckBox("Invert Colors".to_string(),
                                                    EntryId::ToggleInverted,
                                                    context.inverted),
@@ -55,6 +61,7 @@ pub fn toggle_main_menu(view: &mut View, rect: Rectangle, enable: Option<bool>,
                                EntryKind::CheckBox("Enable WiFi".to_string(),
                                                    EntryId::ToggleWifi,
                                                    context.settings.wifi),
+                               EntryKind::SubMenu("Rotation".to_string(), rotations),
                                EntryKind::Separator,
                                EntryKind::Command("Take Screenshot".to_string(),
                                                   EntryId::TakeScreenshot),
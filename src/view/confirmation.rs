@@ -27,7 +27,7 @@ impl Confirmation {
     pub fn new(id: ViewId, event: Event, text: String, fonts: &mut Fonts) -> Confirmation {
         let mut children = Vec::new();
         let dpi = CURRENT_DEVICE.dpi;
-        let (width, height) = CURRENT_DEVICE.dims;
+        let (width, height) = CURRENT_DEVICE.display_dims();
 
         let font = font_from_style(fonts, &NORMAL_STYLE, dpi);
         let x_height = font.x_heights.0 as i32;
@@ -0,0 +1,111 @@
+use device::{CURRENT_DEVICE, BAR_SIZES};
+use geom::Rectangle;
+use font::{Fonts, font_from_style, NORMAL_STYLE};
+use view::{View, Event, Hub, ViewId, Align};
+use view::button::Button;
+use view::popup::{Popup, TITLE_BAR_CHILDREN, popup_padding};
+use view::html_window::HtmlWindow;
+use document::escape_html;
+use dictionary::{Dictionary, DICTIONARIES_DIRNAME, load_dictionaries, lookup};
+use app::Context;
+
+const LABEL_ALL_DICTIONARIES: &str = "All Dictionaries";
+const DICTIONARY_CSS: &str = "css/dictionary.css";
+
+// The definitions of *word* are shown in a window covering the half of the screen
+// that doesn't contain *word_rect*.
+pub fn dictionary_window(word: &str, word_rect: &Rectangle, hub: &Hub, context: &mut Context) -> HtmlWindow {
+    let dpi = CURRENT_DEVICE.dpi;
+    let (_, height) = CURRENT_DEVICE.display_dims();
+    let &(small_height, _) = BAR_SIZES.get(&(height, dpi)).unwrap();
+    let small_height = small_height as i32;
+    let padding = popup_padding(&mut context.fonts);
+
+    if context.dictionaries.is_empty() {
+        context.dictionaries = load_dictionaries(context.settings.library_path.join(DICTIONARIES_DIRNAME));
+    }
+
+    let rect = Popup::half_screen(ViewId::Dictionary, word_rect, &mut context.fonts).rect;
+    let button_rect = dictionary_button_rect(&rect, small_height, padding, &mut context.fonts);
+    let mut window = HtmlWindow::new(ViewId::Dictionary, word, Align::Left(padding / 2),
+                                     word_rect, Some(button_rect.min.x - padding / 2), context);
+    window.set_button(Box::new(dictionary_button(button_rect, context)) as Box<View>);
+    define(&mut window, hub, context);
+    window
+}
+
+pub fn set_dictionary(window: &mut HtmlWindow, name: Option<String>, hub: &Hub, context: &mut Context) {
+    context.settings.reader.dictionary = name;
+    let button_rect = *window.child(TITLE_BAR_CHILDREN).rect();
+    window.set_button(Box::new(dictionary_button(button_rect, context)) as Box<View>);
+    define(window, hub, context);
+}
+
+fn define(window: &mut HtmlWindow, hub: &Hub, context: &mut Context) {
+    let html = definitions_as_html(window.title(), &context.dictionaries,
+                                   context.settings.reader.dictionary.as_ref().map(String::as_str),
+                                   &context.settings.library_path.join(DICTIONARIES_DIRNAME).to_string_lossy());
+    window.load(&html, DICTIONARY_CSS, hub);
+}
+
+fn dictionary_button_rect(rect: &Rectangle, small_height: i32, padding: i32, fonts: &mut Fonts) -> Rectangle {
+    let dpi = CURRENT_DEVICE.dpi;
+    let font = font_from_style(fonts, &NORMAL_STYLE, dpi);
+    let x_height = font.x_heights.0 as i32;
+    let button_width = (font.plan(LABEL_ALL_DICTIONARIES, None, None).width as i32 + 2 * padding)
+                           .min(rect.width() as i32 / 3);
+    let button_height = 4 * x_height;
+    let dy = (small_height - button_height) / 2;
+    rect![rect.max.x - small_height - button_width,
+          rect.min.y + dy,
+          rect.max.x - small_height,
+          rect.min.y + dy + button_height]
+}
+
+fn dictionary_button(rect: Rectangle, context: &Context) -> Button {
+    let text = context.settings.reader.dictionary.clone()
+                      .unwrap_or_else(|| LABEL_ALL_DICTIONARIES.to_string());
+    Button::new(rect, Event::ToggleNear(ViewId::DictionaryMenu, rect), text)
+        .disabled(context.dictionaries.len() < 2)
+}
+
+fn definitions_as_html(word: &str, dictionaries: &[Box<Dictionary>], selected: Option<&str>, dir: &str) -> String {
+    let mut buf = r#"<html>
+                         <head>
+                             <title>Dictionary</title>
+                             <link rel="stylesheet" type="text/css" href="css/dictionary.css"/>
+                         </head>
+                     <body>"#.to_string();
+
+    // Fall back to all the dictionaries when the selected one is gone.
+    let all = selected.map_or(true, |name| dictionaries.iter().all(|d| d.name() != name));
+    let mut found = false;
+
+    for dictionary in dictionaries.iter().filter(|d| all || selected == Some(d.name())) {
+        match lookup(&**dictionary, word) {
+            Ok(Some(definition)) => {
+                found = true;
+                if all && dictionaries.len() > 1 {
+                    buf.push_str(&format!("<h2>{}</h2>", escape_html(&definition.dictionary)));
+                }
+                if definition.headword.to_lowercase() != word.to_lowercase() {
+                    buf.push_str(&format!(r#"<p class="headword">{}</p>"#, escape_html(&definition.headword)));
+                }
+                for text in &definition.texts {
+                    buf.push_str(text);
+                }
+            },
+            Ok(None) => (),
+            Err(e) => eprintln!("Can't look up {} in {}: {}", word, dictionary.name(), e),
+        }
+    }
+
+    if dictionaries.is_empty() {
+        buf.push_str(&format!(r#"<p class="info">No dictionaries found in <em>{}</em>.</p>"#, escape_html(dir)));
+    } else if !found {
+        buf.push_str(&format!(r#"<p class="info">No definitions found for <em>{}</em>.</p>"#, escape_html(word)));
+    }
+
+    buf.push_str("</body></html>");
+    buf
+}
@@ -34,7 +34,7 @@ impl FrontlightWindow {
         let presets = &context.settings.frontlight_presets;
         let mut children = Vec::new();
         let dpi = CURRENT_DEVICE.dpi;
-        let (width, height) = CURRENT_DEVICE.dims;
+        let (width, height) = CURRENT_DEVICE.display_dims();
         let &(small_height, _) = BAR_SIZES.get(&(height, dpi)).unwrap();
         let thickness = scale_by_dpi(THICKNESS_LARGE, dpi) as i32;
         let border_radius = scale_by_dpi(BORDER_RADIUS_MEDIUM, dpi) as i32;
@@ -173,7 +173,7 @@ impl FrontlightWindow {
 
     fn toggle_presets(&mut self, enable: bool, hub: &Hub, context: &mut Context) {
         let dpi = CURRENT_DEVICE.dpi;
-        let (_, height) = CURRENT_DEVICE.dims;
+        let (_, height) = CURRENT_DEVICE.display_dims();
         let &(small_height, _) = BAR_SIZES.get(&(height, dpi)).unwrap();
 
         if enable {
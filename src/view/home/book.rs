@@ -4,7 +4,7 @@ use view::{View, Event, Hub, Bus};
 use font::{MD_TITLE, MD_AUTHOR, MD_YEAR, MD_KIND, MD_SIZE};
 use color::{TEXT_NORMAL, TEXT_INVERTED_HARD};
 use gesture::GestureEvent;
-use metadata::Info;
+use metadata::{Info, ReaderInfo, BookHits};
 use unit::scale_by_dpi;
 use document::HumanSize;
 use font::{Fonts, font_from_style};
@@ -16,6 +16,10 @@ pub struct Book {
     children: Vec<Box<View>>,
     info: Info,
     index: usize,
+    // The number of volumes, when the book stands for its series.
+    series_count: Option<usize>,
+    // The pages that contain the words searched inside the books.
+    hits: Option<BookHits>,
     active: bool,
 }
 
@@ -26,18 +30,54 @@ impl Book {
             children: vec![],
             info,
             index,
+            series_count: None,
+            hits: None,
             active: false,
         }
     }
+
+    pub fn series_count(mut self, series_count: Option<usize>) -> Book {
+        self.series_count = series_count;
+        self
+    }
+
+    pub fn hits(mut self, hits: Option<BookHits>) -> Book {
+        self.hits = hits;
+        self
+    }
+}
+
+// Opens the book at the first hit, the current page is kept in the history.
+fn go_to_first_hit(info: &mut Info, hits: &BookHits) {
+    let r = info.reader.get_or_insert_with(|| ReaderInfo {
+        current_page: 0,
+        pages_count: hits.pages_count,
+        .. Default::default()
+    });
+    // The pagination of reflowable documents can change between the indexing and the reading.
+    let page = (hits.pages[0] * r.pages_count / hits.pages_count.max(1))
+                   .min(r.pages_count.saturating_sub(1));
+    if page != r.current_page {
+        r.history.push(r.current_page);
+        r.current_page = page;
+    }
 }
 
 impl View for Book {
     fn handle_event(&mut self, evt: &Event, hub: &Hub, bus: &mut Bus, _context: &mut Context) -> bool {
         match *evt {
+            Event::Gesture(GestureEvent::Tap(ref center)) if self.rect.includes(center) && self.series_count.is_some() => {
+                bus.push_back(Event::ToggleSeries(self.info.series.clone()));
+                true
+            },
             Event::Gesture(GestureEvent::Tap(ref center)) if self.rect.includes(center) => {
                 self.active = true;
                 hub.send(Event::Render(self.rect, UpdateMode::Gui)).unwrap();
-                hub.send(Event::Open(Box::new(self.info.clone()))).unwrap();
+                let mut info = self.info.clone();
+                if let Some(ref hits) = self.hits {
+                    go_to_first_hit(&mut info, hits);
+                }
+                hub.send(Event::Open(Box::new(info))).unwrap();
                 true
             },
             Event::Gesture(GestureEvent::HoldFinger(ref center)) if self.rect.includes(center) => {
@@ -69,9 +109,26 @@ impl View for Book {
 
         fb.draw_rectangle(&self.rect, scheme[0]);
 
-        let title = self.info.title();
-        let author = self.info.author();
-        let year = &self.info.year;
+        let (title, author, year) = if let Some(count) = self.series_count {
+            let next = if self.info.is_finished() {
+                "Finished".to_string()
+            } else if self.info.number.is_empty() {
+                format!("Next: {}", self.info.title)
+            } else {
+                format!("Next: #{}", self.info.number)
+            };
+            (format!("{} ({})", self.info.series, count),
+             format!("{} · {}", self.info.author(), next),
+             "")
+        } else if let Some(ref hits) = self.hits {
+            let pages = hits.pages.iter().map(|p| (p + 1).to_string())
+                            .collect::<Vec<String>>().join(", ");
+            (self.info.title(),
+             format!("{} · p. {}", self.info.author(), pages),
+             self.info.year.as_str())
+        } else {
+            (self.info.title(), self.info.author().to_string(), self.info.year.as_str())
+        };
         let file_info = &self.info.file;
 
         let (x_height, padding, baseline) = {
@@ -88,7 +145,7 @@ impl View for Book {
         // Author
         let author_width = {
             let font = font_from_style(fonts, &MD_AUTHOR, dpi);
-            let plan = font.plan(author, Some(width as u32), None);
+            let plan = font.plan(&author, Some(width as u32), None);
             let pt = pt!(self.rect.min.x + padding, self.rect.max.y - baseline);
             font.render(fb, scheme[1], &plan, &pt);
             plan.width as i32
@@ -145,7 +202,7 @@ impl View for Book {
         }
 
         // File size
-        {
+        if self.series_count.is_none() {
             let size = file_info.size.human_size();
             let font = font_from_style(fonts, &MD_SIZE, dpi);
             let plan = font.plan(&size, None, None);
@@ -0,0 +1,257 @@
+use device::{CURRENT_DEVICE, BAR_SIZES};
+use framebuffer::{Framebuffer, UpdateMode};
+use geom::{Rectangle, halves};
+use font::{Fonts, font_from_style, NORMAL_STYLE};
+use view::{View, Event, Hub, Bus, ViewId, Align, THICKNESS_MEDIUM};
+use view::label::Label;
+use view::button::Button;
+use view::icon::Icon;
+use view::filler::Filler;
+use view::input_field::InputField;
+use view::keyboard::{Keyboard, DEFAULT_LAYOUT};
+use metadata::Info;
+use color::{BLACK, WHITE};
+use unit::scale_by_dpi;
+use app::Context;
+
+const LABEL_SAVE: &str = "Save";
+const LABEL_CANCEL: &str = "Cancel";
+
+const FIELDS: [&str; 11] = ["Title", "Subtitle", "Author", "Year", "Language",
+                            "Publisher", "Series", "Edition", "Volume", "Number", "ISBN"];
+
+// The index of the first field's label within the children.
+const FIRST_FIELD_INDEX: usize = 3;
+
+pub struct MetadataEditor {
+    rect: Rectangle,
+    children: Vec<Box<View>>,
+    info: Info,
+}
+
+impl MetadataEditor {
+    pub fn new(rect: Rectangle, info: Info, hub: &Hub, context: &mut Context) -> MetadataEditor {
+        let dpi = CURRENT_DEVICE.dpi;
+        let (_, height) = CURRENT_DEVICE.display_dims();
+        let &(small_height, big_height) = BAR_SIZES.get(&(height, dpi)).unwrap();
+        let (small_height, big_height) = (small_height as i32, big_height as i32);
+        let thickness = scale_by_dpi(THICKNESS_MEDIUM, dpi) as i32;
+        let (small_thickness, big_thickness) = halves(thickness);
+        let mut children = Vec::new();
+
+        let (x_height, padding, label_width, button_width) = {
+            let font = font_from_style(&mut context.fonts, &NORMAL_STYLE, dpi);
+            let label_width = FIELDS.iter().map(|f| font.plan(f, None, None).width)
+                                    .max().unwrap() as i32;
+            let button_width = [LABEL_SAVE, LABEL_CANCEL].iter()
+                                                         .map(|t| font.plan(t, None, None).width)
+                                                         .max().unwrap() as i32;
+            (font.x_heights.0 as i32, font.em() as i32, label_width, button_width)
+        };
+
+        let title = Label::new(rect![rect.min.x + small_height,
+                                     rect.min.y,
+                                     rect.max.x - small_height,
+                                     rect.min.y + small_height - small_thickness],
+                               "Edit Metadata".to_string(),
+                               Align::Center);
+        children.push(Box::new(title) as Box<View>);
+
+        let close_icon = Icon::new("close",
+                                   rect![rect.max.x - small_height,
+                                         rect.min.y,
+                                         rect.max.x,
+                                         rect.min.y + small_height - small_thickness],
+                                   Event::Close(ViewId::MetadataEditor));
+        children.push(Box::new(close_icon) as Box<View>);
+
+        let separator = Filler::new(rect![rect.min.x,
+                                          rect.min.y + small_height - small_thickness,
+                                          rect.max.x,
+                                          rect.min.y + small_height + big_thickness],
+                                    BLACK);
+        children.push(Box::new(separator) as Box<View>);
+
+        let mut kb_rect = rect![rect.min.x,
+                                rect.max.y - 3 * big_height + big_thickness,
+                                rect.max.x,
+                                rect.max.y];
+        let keyboard = Keyboard::new(&mut kb_rect, DEFAULT_LAYOUT.clone(), false);
+
+        let buttons_rect = rect![rect.min.x,
+                                 kb_rect.min.y - thickness - small_height,
+                                 rect.max.x,
+                                 kb_rect.min.y - thickness];
+
+        let fields_rect = rect![rect.min.x,
+                                rect.min.y + small_height + big_thickness,
+                                rect.max.x,
+                                buttons_rect.min.y - thickness];
+
+        let row_height = fields_rect.height() as i32 / FIELDS.len() as i32;
+        let input_height = (4 * x_height).min(row_height - 2 * small_thickness);
+
+        for (i, name) in FIELDS.iter().enumerate() {
+            let y_min = fields_rect.min.y + i as i32 * row_height;
+            let dy = (row_height - input_height) / 2;
+            let label = Label::new(rect![rect.min.x + padding,
+                                         y_min,
+                                         rect.min.x + 2 * padding + label_width,
+                                         y_min + row_height],
+                                   name.to_string(),
+                                   Align::Right(padding / 2));
+            children.push(Box::new(label) as Box<View>);
+
+            let input_field = InputField::new(rect![rect.min.x + 3 * padding + label_width,
+                                                    y_min + dy,
+                                                    rect.max.x - padding,
+                                                    y_min + dy + input_height],
+                                              ViewId::MetadataInput(i as u8))
+                                         .text(field(&info, i));
+            children.push(Box::new(input_field) as Box<View>);
+        }
+
+        let separator = Filler::new(rect![rect.min.x,
+                                          buttons_rect.min.y - thickness,
+                                          rect.max.x,
+                                          buttons_rect.min.y],
+                                    BLACK);
+        children.push(Box::new(separator) as Box<View>);
+
+        let button_width = button_width + 2 * padding;
+        let button_height = 4 * x_height;
+        let center_x = rect.min.x + rect.width() as i32 / 2;
+        let dy = (small_height - button_height) / 2;
+
+        let button_cancel = Button::new(rect![center_x - padding - button_width,
+                                              buttons_rect.min.y + dy,
+                                              center_x - padding,
+                                              buttons_rect.min.y + dy + button_height],
+                                        Event::Cancel,
+                                        LABEL_CANCEL.to_string());
+        children.push(Box::new(button_cancel) as Box<View>);
+
+        let button_save = Button::new(rect![center_x + padding,
+                                            buttons_rect.min.y + dy,
+                                            center_x + padding + button_width,
+                                            buttons_rect.min.y + dy + button_height],
+                                      Event::Save,
+                                      LABEL_SAVE.to_string());
+        children.push(Box::new(button_save) as Box<View>);
+
+        let separator = Filler::new(rect![rect.min.x,
+                                          kb_rect.min.y - thickness,
+                                          rect.max.x,
+                                          kb_rect.min.y],
+                                    BLACK);
+        children.push(Box::new(separator) as Box<View>);
+
+        children.push(Box::new(keyboard) as Box<View>);
+
+        hub.send(Event::Render(rect, UpdateMode::Gui)).unwrap();
+        hub.send(Event::Focus(Some(ViewId::MetadataInput(0)))).unwrap();
+
+        MetadataEditor {
+            rect,
+            children,
+            info,
+        }
+    }
+
+    fn save(&mut self, bus: &mut Bus) {
+        let mut info = self.info.clone();
+        for i in 0..FIELDS.len() {
+            if let Some(input_field) = self.child(FIRST_FIELD_INDEX + 2 * i + 1).downcast_ref::<InputField>() {
+                *field_mut(&mut info, i) = input_field.content().trim().to_string();
+            }
+        }
+        bus.push_back(Event::SaveMetadata(Box::new(info)));
+    }
+}
+
+fn field(info: &Info, index: usize) -> &str {
+    match index {
+        0 => &info.title,
+        1 => &info.subtitle,
+        2 => &info.author,
+        3 => &info.year,
+        4 => &info.language,
+        5 => &info.publisher,
+        6 => &info.series,
+        7 => &info.edition,
+        8 => &info.volume,
+        9 => &info.number,
+        _ => &info.isbn,
+    }
+}
+
+fn field_mut(info: &mut Info, index: usize) -> &mut String {
+    match index {
+        0 => &mut info.title,
+        1 => &mut info.subtitle,
+        2 => &mut info.author,
+        3 => &mut info.year,
+        4 => &mut info.language,
+        5 => &mut info.publisher,
+        6 => &mut info.series,
+        7 => &mut info.edition,
+        8 => &mut info.volume,
+        9 => &mut info.number,
+        _ => &mut info.isbn,
+    }
+}
+
+impl View for MetadataEditor {
+    fn handle_event(&mut self, evt: &Event, hub: &Hub, bus: &mut Bus, _context: &mut Context) -> bool {
+        match *evt {
+            Event::Submit(ViewId::MetadataInput(index), _) => {
+                if (index as usize) + 1 < FIELDS.len() {
+                    hub.send(Event::Focus(Some(ViewId::MetadataInput(index + 1)))).unwrap();
+                } else {
+                    self.save(bus);
+                    hub.send(Event::Close(ViewId::MetadataEditor)).unwrap();
+                }
+                true
+            },
+            Event::Save => {
+                self.save(bus);
+                hub.send(Event::Close(ViewId::MetadataEditor)).unwrap();
+                true
+            },
+            Event::Cancel => {
+                hub.send(Event::Close(ViewId::MetadataEditor)).unwrap();
+                true
+            },
+            Event::Gesture(..) => true,
+            _ => false,
+        }
+    }
+
+    fn render(&self, fb: &mut Framebuffer, _fonts: &mut Fonts) {
+        fb.draw_rectangle(&self.rect, WHITE);
+    }
+
+    fn is_background(&self) -> bool {
+        true
+    }
+
+    fn rect(&self) -> &Rectangle {
+        &self.rect
+    }
+
+    fn rect_mut(&mut self) -> &mut Rectangle {
+        &mut self.rect
+    }
+
+    fn children(&self) -> &Vec<Box<View>> {
+        &self.children
+    }
+
+    fn children_mut(&mut self) -> &mut Vec<Box<View>> {
+        &mut self.children
+    }
+
+    fn id(&self) -> Option<ViewId> {
+        Some(ViewId::MetadataEditor)
+    }
+}
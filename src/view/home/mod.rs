@@ -4,12 +4,14 @@ mod book;
 mod bottom_bar;
 mod category;
 mod matches_label;
+mod metadata_editor;
 mod shelf;
 mod sort_label;
 mod summary;
 mod top_bar;
 
 use self::bottom_bar::BottomBar;
+use self::metadata_editor::MetadataEditor;
 use self::shelf::Shelf;
 use self::summary::Summary;
 use self::top_bar::TopBar;
@@ -17,20 +19,20 @@ use app::Context;
 use color::BLACK;
 use device::{BAR_SIZES, CURRENT_DEVICE};
 use failure::Error;
-use fnv::FnvHashSet;
+use fnv::{FnvHashMap, FnvHashSet};
 use font::Fonts;
 use framebuffer::{Framebuffer, UpdateMode};
 use geom::{halves, small_half, CycleDir, Rectangle};
 use glob::glob;
 use helpers::{load_json, save_json};
 use input::{ButtonCode, ButtonStatus, DeviceEvent};
-use metadata::{make_query, sort, Metadata, SortMethod};
-use regex::Regex;
+use metadata::{sort, sort_volumes, BookHits, Info, Metadata, Query, SortMethod, TextIndex};
+use std::cmp::Ordering;
 use std::collections::{BTreeSet, VecDeque};
 use std::f32;
 use std::path::PathBuf;
 use std::sync::mpsc;
-use symbolic_path::SymbolicPath;
+use symbolic_path::{SymbolicPath, PATH_SEPARATOR};
 use trash::{trash, untrash};
 use unit::scale_by_dpi;
 use view::common::{locate, locate_by_id, shift, toggle_main_menu};
@@ -52,12 +54,22 @@ pub struct Home {
     current_page: usize,
     pages_count: usize,
     focus: Option<ViewId>,
-    query: Option<Regex>,
+    query: Option<Query>,
+    // The queries are looked up in the full-text index.
+    text_search: bool,
+    // The pages of the books that contain the words searched in the full-text index.
+    page_hits: Option<FnvHashMap<PathBuf, BookHits>>,
     target_path: Option<PathBuf>,
+    target_category: Option<String>,
     summary_size: u8,
     sort_method: SortMethod,
     reverse_order: bool,
     visible_books: Metadata,
+    // The indices of the displayed books within *visible_books*, with the number of volumes
+    // of the series they stand for, when the series are grouped.
+    shelf_entries: Vec<(usize, Option<usize>)>,
+    group_series: bool,
+    expanded_series: BTreeSet<String>,
     visible_categories: BTreeSet<String>,
     selected_categories: BTreeSet<String>,
     negated_categories: BTreeSet<String>,
@@ -68,148 +80,49 @@ pub struct Home {
 struct HistoryEntry {
     metadata: Metadata,
     restore_books: bool,
+    categories: Option<(BTreeSet<String>, BTreeSet<String>)>,
 }
 
 impl Home {
     pub fn new(rect: Rectangle, hub: &Hub, context: &mut Context) -> Result<Home, Error> {
-        let dpi = CURRENT_DEVICE.dpi;
-        let mut children = Vec::new();
-
-        let thickness = scale_by_dpi(THICKNESS_MEDIUM, dpi) as i32;
-        let (small_thickness, big_thickness) = halves(thickness);
-        let (_, height) = CURRENT_DEVICE.dims;
-        let &(small_height, big_height) = BAR_SIZES.get(&(height, dpi)).unwrap();
-
         let sort_method = SortMethod::Opened;
         let reverse_order = sort_method.reverse_order();
 
         sort(&mut context.metadata, sort_method, reverse_order);
 
-        let visible_books = context.metadata.clone();
-        let visible_categories = context
-            .metadata
-            .iter()
-            .flat_map(|info| info.categories.iter())
-            .map(|categ| categ.first_component().to_string())
-            .collect::<BTreeSet<String>>();
-
-        let selected_categories = BTreeSet::default();
-        let negated_categories = BTreeSet::default();
-
-        let max_lines = ((height - 3 * small_height) / big_height) as usize;
-        let summary_size = context.settings.summary_size.max(1).min(max_lines as u8);
-        let max_lines = max_lines - summary_size as usize + 1;
-        let count = visible_books.len();
-        let pages_count = (visible_books.len() as f32 / max_lines as f32).ceil() as usize;
-        let current_page = 0;
-
-        let top_bar = TopBar::new(
-            rect![
-                rect.min.x,
-                rect.min.y,
-                rect.max.x,
-                rect.min.y + small_height as i32 - small_thickness
-            ],
-            sort_method,
-            context,
-        );
-        children.push(Box::new(top_bar) as Box<View>);
-
-        let separator = Filler::new(
-            rect![
-                rect.min.x,
-                rect.min.y + small_height as i32 - small_thickness,
-                rect.max.x,
-                rect.min.y + small_height as i32 + big_thickness
-            ],
-            BLACK,
-        );
-        children.push(Box::new(separator) as Box<View>);
-
-        let summary_height =
-            small_height as i32 - thickness + (summary_size - 1) as i32 * big_height as i32;
-        let s_min_y = rect.min.y + small_height as i32 + big_thickness;
-        let s_max_y = s_min_y + summary_height;
-
-        let mut summary = Summary::new(rect![rect.min.x, s_min_y, rect.max.x, s_max_y]);
-
-        let (tx, _rx) = mpsc::channel();
-
-        summary.update(
-            &visible_categories,
-            &selected_categories,
-            &negated_categories,
-            false,
-            &tx,
-            &mut context.fonts,
-        );
-
-        children.push(Box::new(summary) as Box<View>);
-
-        let separator = Filler::new(
-            rect![rect.min.x, s_max_y, rect.max.x, s_max_y + thickness],
-            BLACK,
-        );
-        children.push(Box::new(separator) as Box<View>);
-
-        let mut shelf = Shelf::new(rect![
-            rect.min.x,
-            s_max_y + thickness,
-            rect.max.x,
-            rect.max.y - small_height as i32 - small_thickness
-        ]);
-
-        let index_lower = current_page * max_lines;
-        let index_upper = (index_lower + max_lines).min(visible_books.len());
-
-        shelf.update(&visible_books[index_lower..index_upper], &tx);
-
-        children.push(Box::new(shelf) as Box<View>);
-
-        let separator = Filler::new(
-            rect![
-                rect.min.x,
-                rect.max.y - small_height as i32 - small_thickness,
-                rect.max.x,
-                rect.max.y - small_height as i32 + big_thickness
-            ],
-            BLACK,
-        );
-        children.push(Box::new(separator) as Box<View>);
-
-        let bottom_bar = BottomBar::new(
-            rect![
-                rect.min.x,
-                rect.max.y - small_height as i32 + big_thickness,
-                rect.max.x,
-                rect.max.y
-            ],
-            current_page,
-            pages_count,
-            count,
-            false,
-        );
-        children.push(Box::new(bottom_bar) as Box<View>);
-
-        hub.send(Event::Render(rect, UpdateMode::Full)).unwrap();
+        let summary_size = fitting_summary_size(&rect, context.settings.summary_size);
+        let children = build_children(rect, summary_size, sort_method, context);
 
-        Ok(Home {
+        let mut home = Home {
             rect,
             children,
-            current_page,
-            pages_count,
+            current_page: 0,
+            pages_count: 0,
             focus: None,
             query: None,
+            text_search: false,
+            page_hits: None,
             target_path: None,
+            target_category: None,
             summary_size,
             sort_method,
             reverse_order,
-            visible_books,
-            visible_categories,
-            selected_categories,
-            negated_categories,
+            visible_books: Metadata::new(),
+            shelf_entries: Vec::new(),
+            group_series: false,
+            expanded_series: BTreeSet::default(),
+            visible_categories: BTreeSet::default(),
+            selected_categories: BTreeSet::default(),
+            negated_categories: BTreeSet::default(),
             history: VecDeque::new(),
-        })
+        };
+
+        let (tx, _rx) = mpsc::channel();
+        home.refresh_visibles(true, true, &tx, context);
+
+        hub.send(Event::Render(rect, UpdateMode::Full)).unwrap();
+
+        Ok(home)
     }
 
     fn refresh_visibles(
@@ -221,11 +134,16 @@ impl Home {
     ) {
         let fonts = &mut context.fonts;
         let metadata = &mut context.metadata;
+        let fuzzy = context.settings.fuzzy_search;
 
         self.visible_books = metadata
             .iter()
             .filter(|info| {
-                info.is_match(&self.query)
+                info.is_match(&self.query, fuzzy)
+                    && self
+                        .page_hits
+                        .as_ref()
+                        .map_or(true, |h| h.contains_key(&info.file.path))
                     && (self.selected_categories.is_subset(&info.categories)
                         || self.selected_categories.iter().all(|s| {
                             info.categories
@@ -241,6 +159,33 @@ impl Home {
             .cloned()
             .collect();
 
+        // The best matches come first, the sort method breaks the ties.
+        if let Some(ref query) = self.query {
+            let mut scored: Vec<(f32, Info)> = self
+                .visible_books
+                .drain(..)
+                .map(|info| (query.score(&info), info))
+                .collect();
+            scored.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(Ordering::Equal));
+            self.visible_books = scored.into_iter().map(|(_, info)| info).collect();
+        }
+
+        // The books with the most pages come first.
+        if let Some(ref page_hits) = self.page_hits {
+            self.visible_books.sort_by(|a, b| {
+                page_hits[&b.file.path]
+                    .pages
+                    .len()
+                    .cmp(&page_hits[&a.file.path].pages.len())
+            });
+        }
+
+        self.shelf_entries = if self.group_series {
+            series_entries(&self.visible_books, &self.expanded_series)
+        } else {
+            (0..self.visible_books.len()).map(|i| (i, None)).collect()
+        };
+
         self.visible_categories = self
             .visible_books
             .iter()
@@ -280,7 +225,7 @@ impl Home {
             let shelf = self.child(4).downcast_ref::<Shelf>().unwrap();
             shelf.max_lines
         };
-        self.pages_count = (self.visible_books.len() as f32 / max_lines as f32).ceil() as usize;
+        self.pages_count = (self.shelf_entries.len() as f32 / max_lines as f32).ceil() as usize;
 
         if reset_page {
             self.current_page = 0;
@@ -407,7 +352,7 @@ impl Home {
 
     fn update_shelf(&mut self, was_resized: bool, hub: &Hub) {
         let dpi = CURRENT_DEVICE.dpi;
-        let (_, height) = CURRENT_DEVICE.dims;
+        let (_, height) = CURRENT_DEVICE.display_dims();
         let &(_, big_height) = BAR_SIZES.get(&(height, dpi)).unwrap();
         let thickness = scale_by_dpi(THICKNESS_MEDIUM, dpi) as i32;
 
@@ -417,28 +362,38 @@ impl Home {
         // TODO: extract this into a function and call this when the shelf is resized to avoid the
         // temporal dependency between update_shelf and update_bottom_bar
         if was_resized {
-            let page_position = if self.visible_books.is_empty() {
+            let page_position = if self.shelf_entries.is_empty() {
                 0.0
             } else {
                 self.current_page as f32
-                    * (shelf.max_lines as f32 / self.visible_books.len() as f32)
+                    * (shelf.max_lines as f32 / self.shelf_entries.len() as f32)
             };
 
-            let mut page_guess = page_position * self.visible_books.len() as f32 / max_lines as f32;
+            let mut page_guess = page_position * self.shelf_entries.len() as f32 / max_lines as f32;
             let page_ceil = page_guess.ceil();
 
             if (page_ceil - page_guess) < f32::EPSILON {
                 page_guess = page_ceil;
             }
 
-            self.pages_count = (self.visible_books.len() as f32 / max_lines as f32).ceil() as usize;
+            self.pages_count = (self.shelf_entries.len() as f32 / max_lines as f32).ceil() as usize;
             self.current_page = (page_guess as usize).min(self.pages_count.saturating_sub(1));
         }
 
         let index_lower = self.current_page * max_lines;
-        let index_upper = (index_lower + max_lines).min(self.visible_books.len());
+        let index_upper = (index_lower + max_lines).min(self.shelf_entries.len());
+        let visible_books = &self.visible_books;
+        let page_hits = self.page_hits.as_ref();
+        let entries: Vec<(&Info, Option<usize>, Option<&BookHits>)> = self.shelf_entries
+            [index_lower..index_upper]
+            .iter()
+            .map(|&(index, series_count)| {
+                let info = &visible_books[index];
+                (info, series_count, page_hits.and_then(|h| h.get(&info.file.path)))
+            })
+            .collect();
 
-        shelf.update(&self.visible_books[index_lower..index_upper], hub);
+        shelf.update(&entries, hub);
     }
 
     fn update_top_bar(&mut self, search_visible: bool, hub: &Hub) {
@@ -459,6 +414,7 @@ impl Home {
                 .downcast_mut::<BottomBar>()
                 .unwrap();
             let filter = self.query.is_some()
+                || self.page_hits.is_some()
                 || !self.selected_categories.is_empty()
                 || !self.negated_categories.is_empty();
             bottom_bar.update_matches_label(self.visible_books.len(), filter, hub);
@@ -476,7 +432,7 @@ impl Home {
         fonts: &mut Fonts,
     ) {
         let dpi = CURRENT_DEVICE.dpi;
-        let (_, height) = CURRENT_DEVICE.dims;
+        let (_, height) = CURRENT_DEVICE.display_dims();
         let &(small_height, big_height) = BAR_SIZES.get(&(height, dpi)).unwrap();
         let thickness = scale_by_dpi(THICKNESS_MEDIUM, dpi) as i32;
         let (small_thickness, big_thickness) = halves(thickness);
@@ -602,7 +558,7 @@ impl Home {
         context: &mut Context,
     ) {
         let dpi = CURRENT_DEVICE.dpi;
-        let (_, height) = CURRENT_DEVICE.dims;
+        let (_, height) = CURRENT_DEVICE.display_dims();
         let &(small_height, big_height) = BAR_SIZES.get(&(height, dpi)).unwrap();
 
         let thickness = scale_by_dpi(THICKNESS_MEDIUM, dpi) as i32;
@@ -637,6 +593,7 @@ impl Home {
             self.resize_summary(-delta_y, false, hub, &mut context.fonts);
 
             self.query = None;
+            self.page_hits = None;
 
             search_visible = false;
         } else {
@@ -653,7 +610,11 @@ impl Home {
                     self.rect.max.x,
                     sp_rect.max.y + small_height as i32 - small_thickness
                 ],
-                "Title, author, category",
+                if self.text_search {
+                    "Words inside the books"
+                } else {
+                    "Title, author, category"
+                },
                 "",
             );
 
@@ -710,6 +671,39 @@ impl Home {
         }
     }
 
+    fn search_inside_books(&mut self, text: &str, hub: &Hub, context: &mut Context) {
+        let page_hits: Option<FnvHashMap<PathBuf, BookHits>> = {
+            let mut text_index = context.text_index.lock().unwrap();
+            let library_path = &context.settings.library_path;
+            let index = text_index.get_or_insert_with(|| TextIndex::load(library_path));
+            if index.is_empty() {
+                None
+            } else {
+                Some(index.search(text)
+                          .into_iter()
+                          .map(|hits| (hits.path.clone(), hits))
+                          .collect())
+            }
+        };
+
+        if page_hits.is_none() {
+            let notif = Notification::new(
+                ViewId::EmptyTextIndexNotif,
+                "The full-text index is empty.".to_string(),
+                &mut context.notification_index,
+                &mut context.fonts,
+                hub,
+            );
+            self.children.push(Box::new(notif) as Box<View>);
+            return;
+        }
+
+        self.query = None;
+        self.page_hits = page_hits;
+        self.toggle_keyboard(false, true, None, hub, &mut context.fonts);
+        self.refresh_visibles(true, true, hub, context);
+    }
+
     fn toggle_go_to_page(&mut self, enable: Option<bool>, hub: &Hub, fonts: &mut Fonts) {
         if let Some(index) = locate_by_id(self, ViewId::GoToPage) {
             if let Some(true) = enable {
@@ -794,6 +788,11 @@ impl Home {
                     EntryId::ReverseOrder,
                     self.reverse_order,
                 ),
+                EntryKind::CheckBox(
+                    "Group Series".to_string(),
+                    EntryId::GroupSeries,
+                    self.group_series,
+                ),
             ];
             let sort_menu = Menu::new(rect, ViewId::SortMenu, MenuKind::DropDown, entries, fonts);
             hub.send(Event::Render(*sort_menu.rect(), UpdateMode::Gui))
@@ -805,7 +804,7 @@ impl Home {
     fn book_index(&self, index: usize) -> usize {
         let max_lines = self.child(4).downcast_ref::<Shelf>().unwrap().max_lines;
         let index_lower = self.current_page * max_lines;
-        (index_lower + index).min(self.visible_books.len())
+        (index_lower + index).min(self.shelf_entries.len())
     }
 
     fn toggle_book_menu(
@@ -828,7 +827,7 @@ impl Home {
             }
 
             let book_index = self.book_index(index);
-            let info = &self.visible_books[book_index];
+            let info = &self.visible_books[self.shelf_entries[book_index].0];
             let path = &info.file.path;
 
             let categories = info
@@ -842,10 +841,16 @@ impl Home {
                 })
                 .collect::<Vec<EntryKind>>();
 
-            let mut entries = vec![EntryKind::Command(
-                "Add Categories".to_string(),
-                EntryId::AddBookCategories(path.clone()),
-            )];
+            let mut entries = vec![
+                EntryKind::Command(
+                    "Edit Metadata".to_string(),
+                    EntryId::EditMetadata(path.clone()),
+                ),
+                EntryKind::Command(
+                    "Add Categories".to_string(),
+                    EntryId::AddBookCategories(path.clone()),
+                ),
+            ];
 
             if !categories.is_empty() {
                 entries.push(EntryKind::SubMenu(
@@ -867,6 +872,74 @@ impl Home {
         }
     }
 
+    fn toggle_category_menu(
+        &mut self,
+        categ: &str,
+        rect: Rectangle,
+        enable: Option<bool>,
+        hub: &Hub,
+        fonts: &mut Fonts,
+    ) {
+        if let Some(index) = locate_by_id(self, ViewId::CategoryMenu) {
+            if let Some(true) = enable {
+                return;
+            }
+            hub.send(Event::Expose(*self.child(index).rect())).unwrap();
+            self.children.remove(index);
+        } else {
+            if let Some(false) = enable {
+                return;
+            }
+
+            let entries = vec![
+                EntryKind::Command(
+                    "Rename".to_string(),
+                    EntryId::RenameCategory(categ.to_string()),
+                ),
+                EntryKind::Command(
+                    "Move To".to_string(),
+                    EntryId::MoveCategory(categ.to_string()),
+                ),
+                EntryKind::Command(
+                    "Merge Into".to_string(),
+                    EntryId::MergeCategory(categ.to_string()),
+                ),
+            ];
+
+            let category_menu = Menu::new(
+                rect,
+                ViewId::CategoryMenu,
+                MenuKind::Contextual,
+                entries,
+                fonts,
+            );
+            hub.send(Event::Render(*category_menu.rect(), UpdateMode::Gui))
+                .unwrap();
+            self.children.push(Box::new(category_menu) as Box<View>);
+        }
+    }
+
+    fn show_category_input(&mut self, categ: &str, input_id: ViewId, hub: &Hub, fonts: &mut Fonts) {
+        let (label, id, text) = match input_id {
+            ViewId::RenameCategoryInput => {
+                ("Rename to", ViewId::RenameCategory, categ.last_component())
+            }
+            ViewId::MoveCategoryInput => (
+                "Move under",
+                ViewId::MoveCategory,
+                categ.parent().unwrap_or_default(),
+            ),
+            _ => ("Merge into", ViewId::MergeCategory, ""),
+        };
+        self.target_category = Some(categ.to_string());
+        let mut category_input = NamedInput::new(label.to_string(), id, input_id, 21, fonts);
+        category_input.set_text(text, hub);
+        hub.send(Event::Render(*category_input.rect(), UpdateMode::Gui))
+            .unwrap();
+        hub.send(Event::Focus(Some(input_id))).unwrap();
+        self.children.push(Box::new(category_input) as Box<View>);
+    }
+
     fn toggle_matches_menu(
         &mut self,
         rect: Rectangle,
@@ -901,10 +974,15 @@ impl Home {
                 })
                 .unwrap_or_default();
 
-            let mut entries = vec![EntryKind::Command(
-                "Export As".to_string(),
-                EntryId::ExportMatches,
-            )];
+            let mut entries = vec![
+                EntryKind::CheckBox(
+                    "Search Inside Books".to_string(),
+                    EntryId::SearchInsideBooks,
+                    self.text_search,
+                ),
+                EntryKind::Separator,
+                EntryKind::Command("Export As".to_string(), EntryId::ExportMatches),
+            ];
 
             if !loadables.is_empty() {
                 entries.push(EntryKind::SubMenu(
@@ -972,7 +1050,7 @@ impl Home {
     // and the separator between them.
     fn resize_summary(&mut self, delta_y: i32, update: bool, hub: &Hub, fonts: &mut Fonts) {
         let dpi = CURRENT_DEVICE.dpi;
-        let (_, height) = CURRENT_DEVICE.dims;
+        let (_, height) = CURRENT_DEVICE.display_dims();
         let &(small_height, big_height) = BAR_SIZES.get(&(height, dpi)).unwrap();
         let thickness = scale_by_dpi(THICKNESS_MEDIUM, dpi) as i32;
 
@@ -1032,6 +1110,7 @@ impl Home {
         self.history.push_back(HistoryEntry {
             metadata: context.metadata.clone(),
             restore_books,
+            categories: None,
         });
         if self.history.len() > HISTORY_SIZE {
             self.history.pop_front();
@@ -1046,6 +1125,10 @@ impl Home {
                     .map_err(|e| eprintln!("Couldn't restore books from trash: {}", e))
                     .ok();
             }
+            if let Some((selected, negated)) = entry.categories {
+                self.selected_categories = selected;
+                self.negated_categories = negated;
+            }
             sort(&mut context.metadata, self.sort_method, self.reverse_order);
             self.refresh_visibles(true, false, hub, context);
         }
@@ -1114,6 +1197,60 @@ impl Home {
         self.refresh_visibles(true, false, hub, context);
     }
 
+    // Renames *categ* and its descendants in every book.
+    fn rename_category(&mut self, categ: &str, new_categ: &str, hub: &Hub, context: &mut Context) {
+        if new_categ == categ {
+            return;
+        }
+
+        if new_categ.is_descendant_of(categ) {
+            let notif = Notification::new(
+                ViewId::InvalidCategoryNotif,
+                "A category can't be moved into itself.".to_string(),
+                &mut context.notification_index,
+                &mut context.fonts,
+                hub,
+            );
+            self.children.push(Box::new(notif) as Box<View>);
+            return;
+        }
+
+        self.history_push(false, context);
+        if let Some(entry) = self.history.back_mut() {
+            entry.categories = Some((
+                self.selected_categories.clone(),
+                self.negated_categories.clone(),
+            ));
+        }
+
+        for info in &mut context.metadata {
+            if info
+                .categories
+                .iter()
+                .any(|c| c == categ || c.is_descendant_of(categ))
+            {
+                info.categories = info
+                    .categories
+                    .iter()
+                    .map(|c| rename_path(c, categ, new_categ))
+                    .collect();
+            }
+        }
+
+        self.selected_categories = self
+            .selected_categories
+            .iter()
+            .map(|c| rename_path(c, categ, new_categ))
+            .collect();
+        self.negated_categories = self
+            .negated_categories
+            .iter()
+            .map(|c| rename_path(c, categ, new_categ))
+            .collect();
+
+        self.refresh_visibles(true, false, hub, context);
+    }
+
     fn remove(&mut self, path: &PathBuf, hub: &Hub, context: &mut Context) {
         let paths: FnvHashSet<PathBuf> = [path.clone()].iter().cloned().collect();
         if trash(&paths, context)
@@ -1149,6 +1286,21 @@ impl Home {
         self.refresh_visibles(true, false, hub, context);
     }
 
+    fn save_metadata(&mut self, info: &Info, hub: &Hub, context: &mut Context) {
+        self.history_push(false, context);
+
+        if let Some(entry) = context
+            .metadata
+            .iter_mut()
+            .find(|entry| entry.file.path == info.file.path)
+        {
+            *entry = info.clone();
+        }
+
+        sort(&mut context.metadata, self.sort_method, self.reverse_order);
+        self.refresh_visibles(true, false, hub, context);
+    }
+
     fn remove_book_category(
         &mut self,
         path: &PathBuf,
@@ -1256,6 +1408,178 @@ impl Home {
 // generic method for updating everything based on the bit field to avoid needlessly updating
 // things multiple times?
 
+// Replaces the books of each series by a single entry, placed where the first of them was.
+// This entry stands for the first unfinished volume and is followed, when the series is
+// expanded, by all the volumes in numeric order.
+fn series_entries(books: &[Info], expanded: &BTreeSet<String>) -> Vec<(usize, Option<usize>)> {
+    let mut volumes: FnvHashMap<&str, Vec<usize>> = FnvHashMap::default();
+
+    for (index, info) in books.iter().enumerate() {
+        if !info.series.is_empty() {
+            volumes
+                .entry(info.series.as_str())
+                .or_insert_with(Vec::new)
+                .push(index);
+        }
+    }
+
+    for indices in volumes.values_mut() {
+        indices.sort_by(|&a, &b| sort_volumes(&books[a], &books[b]));
+    }
+
+    let mut entries = Vec::new();
+    let mut seen = FnvHashSet::default();
+
+    for (index, info) in books.iter().enumerate() {
+        let indices = match volumes.get(info.series.as_str()) {
+            Some(indices) if indices.len() > 1 => indices,
+            _ => {
+                entries.push((index, None));
+                continue;
+            }
+        };
+
+        if !seen.insert(info.series.as_str()) {
+            continue;
+        }
+
+        let next = indices
+            .iter()
+            .find(|&&i| !books[i].is_finished())
+            .unwrap_or(&indices[0]);
+        entries.push((*next, Some(indices.len())));
+
+        if expanded.contains(&info.series) {
+            entries.extend(indices.iter().map(|&i| (i, None)));
+        }
+    }
+
+    entries
+}
+
+fn rename_path(path: &str, categ: &str, new_categ: &str) -> String {
+    if path == categ || path.is_descendant_of(categ) {
+        format!("{}{}", new_categ, &path[categ.len()..])
+    } else {
+        path.to_string()
+    }
+}
+
+// The new path of *categ* given the text submitted in the rename, move or merge input.
+fn new_category_path(id: ViewId, categ: &str, text: &str) -> Option<String> {
+    match id {
+        ViewId::RenameCategoryInput if !text.is_empty() => Some(categ.parent().map_or_else(
+            || text.to_string(),
+            |p| format!("{}{}{}", p, PATH_SEPARATOR, text),
+        )),
+        ViewId::MoveCategoryInput if text.is_empty() => Some(categ.last_component().to_string()),
+        ViewId::MoveCategoryInput => Some(format!(
+            "{}{}{}",
+            text,
+            PATH_SEPARATOR,
+            categ.last_component()
+        )),
+        ViewId::MergeCategoryInput if !text.is_empty() => Some(text.to_string()),
+        _ => None,
+    }
+}
+
+// The summary can't take more lines than the shelf.
+fn fitting_summary_size(rect: &Rectangle, summary_size: u8) -> u8 {
+    let dpi = CURRENT_DEVICE.dpi;
+    let (_, height) = CURRENT_DEVICE.display_dims();
+    let &(small_height, big_height) = BAR_SIZES.get(&(height, dpi)).unwrap();
+    let max_lines = ((rect.height() - 3 * small_height) / big_height) as u8;
+    summary_size.max(1).min(max_lines)
+}
+
+fn build_children(rect: Rectangle, summary_size: u8, sort_method: SortMethod, context: &mut Context) -> Vec<Box<View>> {
+    let dpi = CURRENT_DEVICE.dpi;
+    let mut children = Vec::new();
+
+    let thickness = scale_by_dpi(THICKNESS_MEDIUM, dpi) as i32;
+    let (small_thickness, big_thickness) = halves(thickness);
+    let (_, height) = CURRENT_DEVICE.display_dims();
+    let &(small_height, big_height) = BAR_SIZES.get(&(height, dpi)).unwrap();
+
+    let top_bar = TopBar::new(
+        rect![
+            rect.min.x,
+            rect.min.y,
+            rect.max.x,
+            rect.min.y + small_height as i32 - small_thickness
+        ],
+        sort_method,
+        context,
+    );
+    children.push(Box::new(top_bar) as Box<View>);
+
+    let separator = Filler::new(
+        rect![
+            rect.min.x,
+            rect.min.y + small_height as i32 - small_thickness,
+            rect.max.x,
+            rect.min.y + small_height as i32 + big_thickness
+        ],
+        BLACK,
+    );
+    children.push(Box::new(separator) as Box<View>);
+
+    let summary_height =
+        small_height as i32 - thickness + (summary_size - 1) as i32 * big_height as i32;
+    let s_min_y = rect.min.y + small_height as i32 + big_thickness;
+    let s_max_y = s_min_y + summary_height;
+
+    let summary = Summary::new(rect![rect.min.x, s_min_y, rect.max.x, s_max_y]);
+    children.push(Box::new(summary) as Box<View>);
+
+    let separator = Filler::new(
+        rect![rect.min.x, s_max_y, rect.max.x, s_max_y + thickness],
+        BLACK,
+    );
+    children.push(Box::new(separator) as Box<View>);
+
+    let mut shelf = Shelf::new(rect![
+        rect.min.x,
+        s_max_y + thickness,
+        rect.max.x,
+        rect.max.y - small_height as i32 - small_thickness
+    ]);
+
+    // Compute the shelf's maximum number of lines.
+    let (tx, _rx) = mpsc::channel();
+    shelf.update(&[], &tx);
+
+    children.push(Box::new(shelf) as Box<View>);
+
+    let separator = Filler::new(
+        rect![
+            rect.min.x,
+            rect.max.y - small_height as i32 - small_thickness,
+            rect.max.x,
+            rect.max.y - small_height as i32 + big_thickness
+        ],
+        BLACK,
+    );
+    children.push(Box::new(separator) as Box<View>);
+
+    let bottom_bar = BottomBar::new(
+        rect![
+            rect.min.x,
+            rect.max.y - small_height as i32 + big_thickness,
+            rect.max.x,
+            rect.max.y
+        ],
+        0,
+        0,
+        0,
+        false,
+    );
+    children.push(Box::new(bottom_bar) as Box<View>);
+
+    children
+}
+
 impl View for Home {
     fn handle_event(
         &mut self,
@@ -1280,6 +1604,8 @@ impl View for Home {
                 };
                 true
             }
+            // The metadata editor has its own keyboard.
+            Event::Focus(Some(ViewId::MetadataInput(_))) => false,
             Event::Focus(v) => {
                 self.focus = v;
                 self.toggle_keyboard(true, true, v, hub, &mut context.fonts);
@@ -1305,6 +1631,10 @@ impl View for Home {
                 self.toggle_book_menu(index, rect, None, hub, &mut context.fonts);
                 true
             }
+            Event::ToggleCategoryMenu(rect, ref categ) => {
+                self.toggle_category_menu(categ, rect, None, hub, &mut context.fonts);
+                true
+            }
             Event::ToggleNear(ViewId::MainMenu, rect) => {
                 toggle_main_menu(self, rect, None, hub, context);
                 true
@@ -1342,6 +1672,28 @@ impl View for Home {
                 self.set_reverse_order(next_value, hub, context);
                 true
             }
+            Event::Select(EntryId::SearchInsideBooks) => {
+                self.text_search = !self.text_search;
+                if self.query.is_some() || self.page_hits.is_some() {
+                    self.query = None;
+                    self.page_hits = None;
+                    self.refresh_visibles(true, true, hub, context);
+                }
+                true
+            }
+            Event::Select(EntryId::GroupSeries) => {
+                self.group_series = !self.group_series;
+                self.expanded_series.clear();
+                self.refresh_visibles(true, true, hub, context);
+                true
+            }
+            Event::ToggleSeries(ref series) => {
+                if !self.expanded_series.remove(series) {
+                    self.expanded_series.insert(series.clone());
+                }
+                self.refresh_visibles(true, false, hub, context);
+                true
+            }
             Event::Select(EntryId::ExportMatches) => {
                 let export_as = NamedInput::new(
                     "Export as".to_string(),
@@ -1368,6 +1720,22 @@ impl View for Home {
                 self.remove_book_category(path, categ, hub, context);
                 true
             }
+            Event::Select(EntryId::EditMetadata(ref path)) => {
+                let info = context
+                    .metadata
+                    .iter()
+                    .find(|info| info.file.path == *path)
+                    .cloned();
+                if let Some(info) = info {
+                    let metadata_editor = MetadataEditor::new(self.rect, info, hub, context);
+                    self.children.push(Box::new(metadata_editor) as Box<View>);
+                }
+                true
+            }
+            Event::SaveMetadata(ref info) => {
+                self.save_metadata(info, hub, context);
+                true
+            }
             Event::Select(ref id @ EntryId::AddBookCategories(..))
             | Event::Select(ref id @ EntryId::AddMatchesCategories) => {
                 if let EntryId::AddBookCategories(ref path) = *id {
@@ -1395,6 +1763,28 @@ impl View for Home {
                 self.remove_matches_category(categ, hub, context);
                 true
             }
+            Event::Select(EntryId::RenameCategory(ref categ)) => {
+                self.show_category_input(
+                    categ,
+                    ViewId::RenameCategoryInput,
+                    hub,
+                    &mut context.fonts,
+                );
+                true
+            }
+            Event::Select(EntryId::MoveCategory(ref categ)) => {
+                self.show_category_input(categ, ViewId::MoveCategoryInput, hub, &mut context.fonts);
+                true
+            }
+            Event::Select(EntryId::MergeCategory(ref categ)) => {
+                self.show_category_input(
+                    categ,
+                    ViewId::MergeCategoryInput,
+                    hub,
+                    &mut context.fonts,
+                );
+                true
+            }
             Event::Select(EntryId::Undo) => {
                 self.undo(hub, context);
                 true
@@ -1416,21 +1806,40 @@ impl View for Home {
                 self.toggle_keyboard(false, true, None, hub, &mut context.fonts);
                 true
             }
+            Event::Submit(id @ ViewId::RenameCategoryInput, ref text)
+            | Event::Submit(id @ ViewId::MoveCategoryInput, ref text)
+            | Event::Submit(id @ ViewId::MergeCategoryInput, ref text) => {
+                if let Some(categ) = self.target_category.take() {
+                    if let Some(new_categ) = new_category_path(id, &categ, text.trim()) {
+                        self.rename_category(&categ, &new_categ, hub, context);
+                    }
+                }
+                self.toggle_keyboard(false, true, None, hub, &mut context.fonts);
+                true
+            }
+            Event::Submit(ViewId::SearchInput, ref text) if self.text_search => {
+                self.search_inside_books(text, hub, context);
+                true
+            }
             Event::Submit(ViewId::SearchInput, ref text) => {
-                self.query = make_query(text);
-                if self.query.is_some() {
-                    // TODO: avoid updating things twice
-                    self.toggle_keyboard(false, true, None, hub, &mut context.fonts);
-                    self.refresh_visibles(true, true, hub, context);
-                } else {
-                    let notif = Notification::new(
-                        ViewId::InvalidSearchQueryNotif,
-                        "Invalid search query.".to_string(),
-                        &mut context.notification_index,
-                        &mut context.fonts,
-                        hub,
-                    );
-                    self.children.push(Box::new(notif) as Box<View>);
+                match Query::parse(text) {
+                    Ok(query) => {
+                        self.query = Some(query);
+                        self.page_hits = None;
+                        // TODO: avoid updating things twice
+                        self.toggle_keyboard(false, true, None, hub, &mut context.fonts);
+                        self.refresh_visibles(true, true, hub, context);
+                    }
+                    Err(e) => {
+                        let notif = Notification::new(
+                            ViewId::InvalidSearchQueryNotif,
+                            format!("Invalid search query: {}", e),
+                            &mut context.notification_index,
+                            &mut context.fonts,
+                            hub,
+                        );
+                        self.children.push(Box::new(notif) as Box<View>);
+                    }
                 }
                 true
             }
@@ -1485,6 +1894,18 @@ impl View for Home {
 
     fn render(&self, _fb: &mut Framebuffer, _fonts: &mut Fonts) {}
 
+    fn resize(&mut self, rect: Rectangle, hub: &Hub, context: &mut Context) {
+        self.summary_size = fitting_summary_size(&rect, context.settings.summary_size);
+        self.children = build_children(rect, self.summary_size, self.sort_method, context);
+        self.rect = rect;
+        self.focus = None;
+        self.query = None;
+        self.page_hits = None;
+        self.target_path = None;
+        self.target_category = None;
+        self.refresh_visibles(true, false, hub, context);
+    }
+
     fn rect(&self) -> &Rectangle {
         &self.rect
     }
@@ -1501,3 +1922,44 @@ impl View for Home {
         &mut self.children
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use metadata::volume;
+
+    #[test]
+    fn test_series_entries() {
+        let books = vec![volume("", "", "", "", false),
+                         volume("", "S", "2", "", false),
+                         volume("", "T", "1", "", false),
+                         volume("", "S", "1", "", true),
+                         volume("", "S", "3", "", false)];
+        let mut expanded = BTreeSet::new();
+        // The series stands where its first book was, for its first unfinished volume.
+        assert_eq!(series_entries(&books, &expanded),
+                   vec![(0, None), (1, Some(3)), (2, None)]);
+        expanded.insert("S".to_string());
+        assert_eq!(series_entries(&books, &expanded),
+                   vec![(0, None), (1, Some(3)), (3, None), (1, None), (4, None), (2, None)]);
+    }
+
+    #[test]
+    fn test_rename_path() {
+        assert_eq!(rename_path("a.b", "a.b", "c"), "c");
+        assert_eq!(rename_path("a.b.d", "a.b", "c"), "c.d");
+        assert_eq!(rename_path("a.bb", "a.b", "c"), "a.bb");
+        assert_eq!(rename_path("e", "a.b", "c"), "e");
+    }
+
+    #[test]
+    fn test_new_category_path() {
+        assert_eq!(new_category_path(ViewId::RenameCategoryInput, "a.b", "c"), Some("a.c".to_string()));
+        assert_eq!(new_category_path(ViewId::RenameCategoryInput, "a", "c"), Some("c".to_string()));
+        assert_eq!(new_category_path(ViewId::RenameCategoryInput, "a.b", ""), None);
+        assert_eq!(new_category_path(ViewId::MoveCategoryInput, "a.b", "d.e"), Some("d.e.b".to_string()));
+        assert_eq!(new_category_path(ViewId::MoveCategoryInput, "a.b", ""), Some("b".to_string()));
+        assert_eq!(new_category_path(ViewId::MergeCategoryInput, "a.b", "d"), Some("d".to_string()));
+        assert_eq!(new_category_path(ViewId::MergeCategoryInput, "a.b", ""), None);
+    }
+}
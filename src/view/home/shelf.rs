@@ -2,7 +2,7 @@ use device::{CURRENT_DEVICE, BAR_SIZES};
 use view::{View, Event, Hub, Bus, THICKNESS_MEDIUM};
 use framebuffer::{Framebuffer, UpdateMode};
 use font::Fonts;
-use metadata::Info;
+use metadata::{Info, BookHits};
 use geom::{Rectangle, Dir, CycleDir};
 use color::{WHITE, SEPARATOR_NORMAL};
 use gesture::GestureEvent;
@@ -26,21 +26,25 @@ impl Shelf {
         }
     }
 
-    pub fn update(&mut self, metadata: &[Info], hub: &Hub) {
+    // Each book comes with the number of volumes of the series it stands for, if any,
+    // and the pages that contain the words searched inside the books.
+    pub fn update(&mut self, metadata: &[(&Info, Option<usize>, Option<&BookHits>)], hub: &Hub) {
         self.children.clear();
         let dpi = CURRENT_DEVICE.dpi;
-        let (_, height) = CURRENT_DEVICE.dims;
+        let (_, height) = CURRENT_DEVICE.display_dims();
         let &(_, big_height) = BAR_SIZES.get(&(height, dpi)).unwrap();
         let thickness = scale_by_dpi(THICKNESS_MEDIUM, dpi) as i32;
         let max_lines = ((self.rect.height() + thickness as u32) / big_height) as usize;
 
-        for (index, info) in metadata.iter().enumerate() {
+        for (index, &(info, series_count, hits)) in metadata.iter().enumerate() {
             let y_min = self.rect.min.y + index as i32 * big_height as i32;
             let y_max = y_min + big_height as i32 - thickness;
             let book = Book::new(rect![self.rect.min.x, y_min,
                                        self.rect.max.x, y_max],
                                  info.clone(),
-                                 index);
+                                 index)
+                           .series_count(series_count)
+                           .hits(hits.cloned());
             self.children.push(Box::new(book) as Box<View>);
             if index < max_lines - 1 {
                 let separator = Filler::new(rect![self.rect.min.x, y_max,
@@ -0,0 +1,172 @@
+use device::CURRENT_DEVICE;
+use framebuffer::{Framebuffer, UpdateMode, Pixmap};
+use geom::{Rectangle, Dir};
+use font::{Fonts, DEFAULT_FONT_SIZE};
+use view::{View, Event, Hub, Bus, ViewId, Align};
+use view::BORDER_RADIUS_MEDIUM;
+use view::popup::{Popup, TITLE_BAR_CHILDREN};
+use document::Document;
+use document::pdf::{PdfOpener, PdfDocument};
+use gesture::GestureEvent;
+use unit::{scale_by_dpi, pt_to_px};
+use app::Context;
+
+// A popup window showing an HTML document, one page at a time.
+pub struct HtmlWindow {
+    popup: Popup,
+    children: Vec<Box<View>>,
+    title: String,
+    doc: Option<PdfDocument>,
+    pixmap: Option<Pixmap>,
+    current_page: usize,
+    pages_count: usize,
+}
+
+impl HtmlWindow {
+    // The window covers the half of the screen that doesn't contain *anchor*.
+    // The label of the title bar ends at *label_max_x*, when given, to leave room for a button.
+    pub fn new(id: ViewId, title: &str, align: Align, anchor: &Rectangle, label_max_x: Option<i32>, context: &mut Context) -> HtmlWindow {
+        let popup = Popup::half_screen(id, anchor, &mut context.fonts);
+        let children = popup.title_bar(title.to_string(), align, label_max_x);
+
+        HtmlWindow {
+            popup,
+            children,
+            title: title.to_string(),
+            doc: None,
+            pixmap: None,
+            current_page: 0,
+            pages_count: 0,
+        }
+    }
+
+    // Sets the button of the title bar.
+    pub fn set_button(&mut self, button: Box<View>) {
+        if self.children.len() > TITLE_BAR_CHILDREN {
+            self.children[TITLE_BAR_CHILDREN] = button;
+        } else {
+            self.children.push(button);
+        }
+    }
+
+    pub fn title(&self) -> &str {
+        &self.title
+    }
+
+    fn body_rect(&self) -> Rectangle {
+        let dpi = CURRENT_DEVICE.dpi;
+        let padding = scale_by_dpi(BORDER_RADIUS_MEDIUM, dpi) as i32;
+        let rect = self.popup.rect;
+        let top = self.popup.body_rect().min.y;
+        rect![rect.min.x + padding,
+              top + padding / 2,
+              rect.max.x - padding,
+              rect.max.y - padding]
+    }
+
+    // Lays out *html*, styled with the stylesheet at *css_path*, and shows its first page.
+    pub fn load(&mut self, html: &str, css_path: &str, hub: &Hub) {
+        let body_rect = self.body_rect();
+        let dpi = CURRENT_DEVICE.dpi;
+
+        self.doc = PdfOpener::new().and_then(|mut opener| {
+            if opener.set_user_css(css_path).is_err() {
+                eprintln!("Can't set the stylesheet {}.", css_path);
+            }
+            opener.open_memory("html", html.as_bytes())
+        });
+
+        if let Some(ref mut doc) = self.doc {
+            doc.layout(body_rect.width() as f32, body_rect.height() as f32,
+                       pt_to_px(DEFAULT_FONT_SIZE, dpi));
+        }
+
+        self.pages_count = self.doc.as_ref().map_or(0, |doc| doc.pages_count());
+        self.go_to_page(0);
+        self.update_label(hub);
+        hub.send(Event::Render(self.popup.rect, UpdateMode::Gui)).unwrap();
+    }
+
+    fn go_to_page(&mut self, index: usize) {
+        self.current_page = index;
+        self.pixmap = self.doc.as_ref().and_then(|doc| doc.pixmap(index, 1.0));
+    }
+
+    fn set_current_page(&mut self, dir: Dir, hub: &Hub) {
+        let index = match dir {
+            Dir::West if self.current_page + 1 < self.pages_count => self.current_page + 1,
+            Dir::East if self.current_page > 0 => self.current_page - 1,
+            _ => return,
+        };
+        self.go_to_page(index);
+        self.update_label(hub);
+        hub.send(Event::Render(self.body_rect(), UpdateMode::Gui)).unwrap();
+    }
+
+    fn update_label(&mut self, hub: &Hub) {
+        self.popup.update_title(&mut self.children, &self.title,
+                                self.current_page, self.pages_count, hub);
+    }
+}
+
+impl View for HtmlWindow {
+    fn handle_event(&mut self, evt: &Event, hub: &Hub, _bus: &mut Bus, _context: &mut Context) -> bool {
+        match *evt {
+            Event::Gesture(GestureEvent::Tap(ref center)) if !self.popup.rect.includes(center) => {
+                hub.send(Event::Close(self.popup.id)).unwrap();
+                true
+            },
+            Event::Gesture(GestureEvent::Tap(ref center)) if self.body_rect().includes(center) => {
+                let body_rect = self.body_rect();
+                if center.x < body_rect.min.x + body_rect.width() as i32 / 2 {
+                    self.set_current_page(Dir::East, hub);
+                } else {
+                    self.set_current_page(Dir::West, hub);
+                }
+                true
+            },
+            Event::Gesture(GestureEvent::Swipe { dir, ref start, .. }) if self.popup.rect.includes(start) => {
+                self.set_current_page(dir, hub);
+                true
+            },
+            Event::Gesture(..) => true,
+            _ => false,
+        }
+    }
+
+    fn render(&self, fb: &mut Framebuffer, _fonts: &mut Fonts) {
+        self.popup.render(fb);
+
+        if let Some(ref pixmap) = self.pixmap {
+            let body_rect = self.body_rect();
+            let source = rect![0, 0,
+                               pixmap.width.min(body_rect.width() as i32),
+                               pixmap.height.min(body_rect.height() as i32)];
+            fb.draw_framed_pixmap(pixmap, &source, &body_rect.min);
+        }
+    }
+
+    fn is_background(&self) -> bool {
+        true
+    }
+
+    fn rect(&self) -> &Rectangle {
+        &self.popup.rect
+    }
+
+    fn rect_mut(&mut self) -> &mut Rectangle {
+        &mut self.popup.rect
+    }
+
+    fn children(&self) -> &Vec<Box<View>> {
+        &self.children
+    }
+
+    fn children_mut(&mut self) -> &mut Vec<Box<View>> {
+        &mut self.children
+    }
+
+    fn id(&self) -> Option<ViewId> {
+        Some(self.popup.id)
+    }
+}
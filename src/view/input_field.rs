@@ -106,6 +106,16 @@ impl InputField {
         self
     }
 
+    pub fn content(&self) -> &str {
+        &self.text
+    }
+
+    pub fn set_text(&mut self, text: &str, hub: &Hub) {
+        self.text = text.to_string();
+        self.cursor = self.text.len();
+        hub.send(Event::Render(self.rect, UpdateMode::Gui)).unwrap();
+    }
+
     fn char_move(&mut self, dir: LinearDir) {
         if let Some(index) = closest_char_boundary(&self.text, self.cursor, dir) {
             self.cursor = index;
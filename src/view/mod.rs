@@ -24,6 +24,9 @@ pub mod confirmation;
 pub mod notification;
 pub mod intermission;
 pub mod frontlight;
+pub mod popup;
+pub mod html_window;
+pub mod dictionary;
 pub mod presets_list;
 pub mod preset;
 pub mod menu;
@@ -43,8 +46,9 @@ use std::fmt::{self, Debug};
 use fnv::FnvHashMap;
 use downcast_rs::Downcast;
 use font::Fonts;
-use document::TocEntry;
-use metadata::{Info, SortMethod, PageScheme, Margin};
+use document::{TocEntry, Snippet};
+use metadata::{Info, SortMethod, PageScheme, Margin, Annotation, TextAlign};
+use statistics::Report;
 use framebuffer::{Framebuffer, UpdateMode};
 use input::{DeviceEvent, FingerStatus};
 use gesture::GestureEvent;
@@ -73,6 +77,11 @@ pub trait View: Downcast {
     fn children(&self) -> &Vec<Box<View>>;
     fn children_mut(&mut self) -> &mut Vec<Box<View>>;
 
+    // Called when the frame is rotated.
+    fn resize(&mut self, rect: Rectangle, _hub: &Hub, _context: &mut Context) {
+        *self.rect_mut() = rect;
+    }
+
     fn child(&self, index: usize) -> &View {
         self.children()[index].as_ref()
     }
@@ -196,8 +205,11 @@ pub enum Event {
     Key(KeyKind),
     Open(Box<Info>),
     OpenToc(Vec<TocEntry>, usize),
+    OpenAnnotations(Vec<Annotation>, Vec<TocEntry>),
+    OpenStatistics(Box<Report>),
     Invalid(Box<Info>),
     Remove(Box<Info>),
+    SaveMetadata(Box<Info>),
     Page(CycleDir),
     ResultsPage(CycleDir),
     GoTo(usize),
@@ -216,13 +228,17 @@ pub enum Event {
     Slider(SliderId, f32, FingerStatus),
     ToggleNear(ViewId, Rectangle),
     ToggleBookMenu(Rectangle, usize),
+    ToggleBookmarkMenu(Rectangle, usize),
+    ToggleCategoryMenu(Rectangle, String),
+    ToggleSeries(String),
     TogglePresetMenu(Rectangle, usize),
     SubMenu(Rectangle, Vec<EntryKind>),
     Toggle(ViewId),
     Show(ViewId),
     Close(ViewId),
     CloseSub(ViewId),
-    SearchResult(usize, Rectangle),
+    // The rectangles of a search result, grouped by page, starting with the given page.
+    SearchResult(usize, Vec<Vec<Rectangle>>, Snippet),
     EndOfSearch,
     Finished,
     ClockTick,
@@ -256,13 +272,18 @@ pub enum ViewId {
     SortMenu,
     MainMenu,
     Frontlight,
-    FontSizeMenu,
+    TypographyMenu,
     MatchesMenu,
     PageMenu,
     BookMenu,
+    CategoryMenu,
     PresetMenu,
     MarginCropperMenu,
     SearchMenu,
+    ZoomMenu,
+    AnnotationMenu,
+    BookmarkMenu,
+    DictionaryMenu,
     GoToPage,
     GoToPageInput,
     GoToResultsPage,
@@ -271,17 +292,38 @@ pub enum ViewId {
     ExportAsInput,
     AddCategories,
     AddCategoriesInput,
+    RenameCategory,
+    RenameCategoryInput,
+    MoveCategory,
+    MoveCategoryInput,
+    MergeCategory,
+    MergeCategoryInput,
+    EditNote,
+    EditNoteInput,
+    RenameBookmark,
+    RenameBookmarkInput,
+    MetadataEditor,
+    MetadataInput(u8),
     SearchInput,
     SearchBar,
+    SearchResults,
     Keyboard,
     ConfirmMount,
     MarginCropper,
     TopBottomBars,
     TableOfContents,
+    Dictionary,
+    Footnote,
+    Thumbnails,
+    Bookmarks,
     FinishedNotif,
     TakeScreenshotNotif,
     NoSearchResultsNotif,
     InvalidSearchQueryNotif,
+    EmptyTextIndexNotif,
+    InvalidCategoryNotif,
+    AutoCropNotif,
+    LinkNotif,
     NetUpNotif,
     SubMenu(u8),
 }
@@ -352,21 +394,54 @@ pub enum EntryId {
     Sort(SortMethod),
     ApplyCroppings(usize, PageScheme),
     RemoveCroppings,
+    AutoCrop(PageScheme),
+    FitToPage,
+    FitToWidth,
+    Zoom(u16),
+    FontFamily(Option<String>),
+    LineHeight(Option<u16>),
+    MarginWidth(Option<u8>),
+    TextAlign(TextAlign),
+    ToggleHyphenation,
+    ResetTypography,
     Remove(PathBuf),
     SearchDirection(LinearDir),
+    ToggleCaseSensitive,
+    ToggleWholeWord,
+    ToggleRegex,
     AddBookCategories(PathBuf),
     RemoveBookCategory(PathBuf, String),
+    EditMetadata(PathBuf),
     RemoveMatches,
     RemovePreset(usize),
     AddMatchesCategories,
     RemoveMatchesCategory(String),
+    GroupSeries,
+    SearchInsideBooks,
+    RenameCategory(String),
+    MoveCategory(String),
+    MergeCategory(String),
     Load(PathBuf),
     ExportMatches,
     ToggleFirstPage,
+    PreviousLocation,
+    NextLocation,
+    HighlightSelection,
+    AnnotateSelection,
+    EditAnnotationNote(usize),
+    RemoveAnnotation(usize),
+    ShowAnnotations,
+    ShowStatistics,
+    ShowThumbnails,
+    ShowBookmarks,
+    RenameBookmark(usize),
+    RemoveBookmark(usize),
+    Dictionary(Option<String>),
     ReverseOrder,
     ToggleInverted,
     ToggleMonochrome,
     ToggleWifi,
+    Rotate(i8),
     TakeScreenshot,
     StartNickel,
     Reboot,
@@ -19,7 +19,7 @@ pub struct NamedInput {
 impl NamedInput {
     pub fn new(text: String, id: ViewId, input_id: ViewId, input_size: usize, fonts: &mut Fonts) -> NamedInput {
         let dpi = CURRENT_DEVICE.dpi;
-        let (width, height) = CURRENT_DEVICE.dims;
+        let (width, height) = CURRENT_DEVICE.display_dims();
 
         let input_size = input_size.max(3);
         let mut children = Vec::new();
@@ -74,6 +74,12 @@ impl NamedInput {
             id,
         }
     }
+
+    pub fn set_text(&mut self, text: &str, hub: &Hub) {
+        if let Some(input_field) = self.children[1].as_mut().downcast_mut::<InputField>() {
+            input_field.set_text(text, hub);
+        }
+    }
 }
 
 impl View for NamedInput {
@@ -31,7 +31,7 @@ impl Notification {
         });
 
         let dpi = CURRENT_DEVICE.dpi;
-        let (width, height) = CURRENT_DEVICE.dims;
+        let (width, height) = CURRENT_DEVICE.display_dims();
         let &(small_height, _) = BAR_SIZES.get(&(height, dpi)).unwrap();
 
         let font = font_from_style(fonts, &NORMAL_STYLE, dpi);
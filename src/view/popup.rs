@@ -0,0 +1,139 @@
+use device::{CURRENT_DEVICE, BAR_SIZES};
+use framebuffer::Framebuffer;
+use geom::{Rectangle, CornerSpec, BorderSpec};
+use font::{Fonts, font_from_style, NORMAL_STYLE};
+use view::{View, Event, Hub, ViewId, Align};
+use view::{THICKNESS_LARGE, THICKNESS_MEDIUM, BORDER_RADIUS_MEDIUM};
+use view::label::Label;
+use view::icon::Icon;
+use view::filler::Filler;
+use color::{BLACK, WHITE};
+use unit::scale_by_dpi;
+
+// The number of children of the title bar: the close icon, the label and the separator.
+pub const TITLE_BAR_CHILDREN: usize = 3;
+
+// The frame of the popup windows: a bordered rounded rectangle, with a title bar
+// made of a close icon, a label and a separator above the body of the window.
+pub struct Popup {
+    pub rect: Rectangle,
+    pub id: ViewId,
+}
+
+impl Popup {
+    // Covers the screen, but for a margin.
+    pub fn full_screen(id: ViewId, fonts: &mut Fonts) -> Popup {
+        let (width, height) = CURRENT_DEVICE.display_dims();
+        let padding = popup_padding(fonts);
+        Popup {
+            rect: rect![padding, padding, width as i32 - padding, height as i32 - padding],
+            id,
+        }
+    }
+
+    // Covers the half of the screen that doesn't contain *anchor*.
+    pub fn half_screen(id: ViewId, anchor: &Rectangle, fonts: &mut Fonts) -> Popup {
+        let (width, height) = CURRENT_DEVICE.display_dims();
+        let padding = popup_padding(fonts);
+        let window_width = width as i32 - 2 * padding;
+        let window_height = height as i32 / 2 - 2 * padding;
+        let dx = padding;
+        let dy = if anchor.center().y < height as i32 / 2 {
+            height as i32 - padding - window_height
+        } else {
+            padding
+        };
+        Popup {
+            rect: rect![dx, dy, dx + window_width, dy + window_height],
+            id,
+        }
+    }
+
+    fn title_height(&self) -> i32 {
+        let dpi = CURRENT_DEVICE.dpi;
+        let (_, height) = CURRENT_DEVICE.display_dims();
+        let &(small_height, _) = BAR_SIZES.get(&(height, dpi)).unwrap();
+        small_height as i32
+    }
+
+    // The close icon, the label and the separator. The label ends at *label_max_x*, when given,
+    // to leave room for other views in the title bar.
+    pub fn title_bar(&self, title: String, align: Align, label_max_x: Option<i32>) -> Vec<Box<View>> {
+        let dpi = CURRENT_DEVICE.dpi;
+        let small_height = self.title_height();
+        let thickness = scale_by_dpi(THICKNESS_LARGE, dpi) as i32;
+        let border_radius = scale_by_dpi(BORDER_RADIUS_MEDIUM, dpi) as i32;
+        let rect = self.rect;
+
+        let mut children = Vec::new();
+
+        let close_icon = Icon::new("close",
+                                   rect![rect.max.x - small_height,
+                                         rect.min.y + thickness,
+                                         rect.max.x - thickness,
+                                         rect.min.y + small_height],
+                                   Event::Close(self.id))
+                              .corners(Some(CornerSpec::Uniform(border_radius - thickness)));
+        children.push(Box::new(close_icon) as Box<View>);
+
+        let label = Label::new(rect![rect.min.x + small_height,
+                                     rect.min.y + thickness,
+                                     label_max_x.unwrap_or(rect.max.x - small_height),
+                                     rect.min.y + small_height],
+                               title,
+                               align);
+        children.push(Box::new(label) as Box<View>);
+
+        let separator = Filler::new(rect![rect.min.x + thickness,
+                                          rect.min.y + small_height,
+                                          rect.max.x - thickness,
+                                          rect.min.y + small_height + scale_by_dpi(THICKNESS_MEDIUM, dpi) as i32],
+                                    BLACK);
+        children.push(Box::new(separator) as Box<View>);
+
+        children
+    }
+
+    // The space below the title bar, within the border.
+    pub fn body_rect(&self) -> Rectangle {
+        let dpi = CURRENT_DEVICE.dpi;
+        let thickness = scale_by_dpi(THICKNESS_LARGE, dpi) as i32;
+        let border_radius = scale_by_dpi(BORDER_RADIUS_MEDIUM, dpi) as i32;
+        let top = self.rect.min.y + self.title_height() + scale_by_dpi(THICKNESS_MEDIUM, dpi) as i32;
+        rect![self.rect.min.x + thickness,
+              top,
+              self.rect.max.x - thickness,
+              self.rect.max.y - border_radius]
+    }
+
+    // Updates the label of the title bar built by `title_bar`, with the current page
+    // of the window when it has several.
+    pub fn update_title(&self, children: &mut Vec<Box<View>>, title: &str, current_page: usize, pages_count: usize, hub: &Hub) {
+        let text = if pages_count > 1 {
+            format!("{} ({}/{})", title, current_page + 1, pages_count)
+        } else {
+            title.to_string()
+        };
+        if let Some(label) = children[1].downcast_mut::<Label>() {
+            label.update(text, hub);
+        }
+    }
+
+    pub fn render(&self, fb: &mut Framebuffer) {
+        let dpi = CURRENT_DEVICE.dpi;
+        let border_radius = scale_by_dpi(BORDER_RADIUS_MEDIUM, dpi) as i32;
+        let border_thickness = scale_by_dpi(THICKNESS_LARGE, dpi) as u16;
+
+        fb.draw_rounded_rectangle_with_border(&self.rect,
+                                              &CornerSpec::Uniform(border_radius),
+                                              &BorderSpec { thickness: border_thickness,
+                                                            color: BLACK },
+                                              &WHITE);
+    }
+}
+
+// The margin between the popups and the edges of the screen.
+pub fn popup_padding(fonts: &mut Fonts) -> i32 {
+    let font = font_from_style(fonts, &NORMAL_STYLE, CURRENT_DEVICE.dpi);
+    font.em() as i32
+}
@@ -0,0 +1,236 @@
+use document::Document;
+use framebuffer::Pixmap;
+use geom::Rectangle;
+use metadata::Margin;
+
+// The width, in pixels, at which pages are rendered for the analysis.
+const ANALYSIS_WIDTH: f32 = 600.0;
+// Pixels darker than this are considered ink.
+const INK_THRESHOLD: u8 = 160;
+// Rows and columns with a lower proportion of ink are considered blank: it absorbs the scanning noise.
+const NOISE_RATIO: f32 = 0.004;
+// Rows and columns at the edges of the page with a higher proportion of ink are scanning shadows.
+const SHADOW_RATIO: f32 = 0.8;
+// Blank gaps thinner than this fraction of the page height don't separate blocks.
+const MIN_GAP_RATIO: f32 = 0.008;
+// Headers, footers and page numbers are thin blocks, close to the top or bottom edge,
+// and separated from the body by a wide enough gap.
+const EDGE_RATIO: f32 = 0.15;
+const MAX_EDGE_BLOCK_RATIO: f32 = 0.04;
+const MIN_EDGE_GAP_RATIO: f32 = 0.015;
+// The blank space kept around the content, relative to the page dimensions.
+const PADDING_RATIO: f32 = 0.01;
+
+// The margins that leave the body of the given page, in fractions of the page dimensions.
+pub fn content_margin(doc: &Document, index: usize) -> Option<Margin> {
+    let (width, _) = doc.dims(index)?;
+    let scale = ANALYSIS_WIDTH / width;
+    let pixmap = doc.pixmap(index, scale)?;
+    let mut rect = content_rect(&pixmap)?;
+
+    // The bounding box of the page's objects is exact, but it includes the headers and footers.
+    if let Some(bbox) = doc.boundary_box(index) {
+        let bbox = rect![(bbox.min.x as f32 * scale).floor() as i32,
+                         (bbox.min.y as f32 * scale).floor() as i32,
+                         (bbox.max.x as f32 * scale).ceil() as i32,
+                         (bbox.max.y as f32 * scale).ceil() as i32];
+        if let Some(inter) = rect.intersection(&bbox) {
+            rect = inter;
+        }
+    }
+
+    Some(margin_from_rect(&rect, pixmap.width, pixmap.height))
+}
+
+// Combines the margins of several pages: each side gets the lower quartile of its values,
+// so that the content of most pages is preserved despite a few full bleed illustrations.
+pub fn merge_margins(margins: &[Margin]) -> Option<Margin> {
+    if margins.is_empty() {
+        return None;
+    }
+    Some(Margin::new(lower_quartile(margins.iter().map(|m| m.top).collect()),
+                     lower_quartile(margins.iter().map(|m| m.right).collect()),
+                     lower_quartile(margins.iter().map(|m| m.bottom).collect()),
+                     lower_quartile(margins.iter().map(|m| m.left).collect())))
+}
+
+fn lower_quartile(mut values: Vec<f32>) -> f32 {
+    values.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    values[values.len() / 4]
+}
+
+fn margin_from_rect(rect: &Rectangle, width: i32, height: i32) -> Margin {
+    let (width, height) = (width as f32, height as f32);
+    let dx = PADDING_RATIO * width;
+    let dy = PADDING_RATIO * height;
+    Margin::new(((rect.min.y as f32 - dy) / height).max(0.0),
+                ((width - rect.max.x as f32 - dx) / width).max(0.0),
+                ((height - rect.max.y as f32 - dy) / height).max(0.0),
+                ((rect.min.x as f32 - dx) / width).max(0.0))
+}
+
+// The region of the pixmap that holds the body of the page.
+fn content_rect(pixmap: &Pixmap) -> Option<Rectangle> {
+    let (width, height) = (pixmap.width as usize, pixmap.height as usize);
+    if width == 0 || height == 0 {
+        return None;
+    }
+
+    let rows = profile(pixmap, true, (0, width));
+    let (y_min, y_max) = trim_shadows(&rows, width);
+    let columns = profile(pixmap, false, (y_min, y_max));
+    let (x_min, x_max) = trim_shadows(&columns, y_max - y_min);
+
+    if x_min >= x_max || y_min >= y_max {
+        return None;
+    }
+
+    // The rows are measured again, without the side shadows.
+    let rows = profile(pixmap, true, (x_min, x_max));
+    let threshold = (NOISE_RATIO * (x_max - x_min) as f32) as usize;
+    let min_gap = (MIN_GAP_RATIO * height as f32).ceil() as usize;
+    let mut blocks: Vec<(usize, usize)> = find_blocks(&rows[y_min..y_max], threshold, min_gap)
+                                              .into_iter().map(|(a, b)| (a + y_min, b + y_min))
+                                              .collect();
+    strip_edge_blocks(&mut blocks, height);
+
+    let top = blocks.first()?.0;
+    let bottom = blocks.last()?.1;
+
+    let columns = profile(pixmap, false, (top, bottom));
+    let threshold = (NOISE_RATIO * (bottom - top) as f32) as usize;
+    let blocks = find_blocks(&columns[x_min..x_max], threshold, 0);
+    let left = blocks.first()?.0 + x_min;
+    let right = blocks.last()?.1 + x_min;
+
+    Some(rect![left as i32, top as i32, right as i32, bottom as i32])
+}
+
+// Counts the ink pixels of each row, within the given columns, or of each column, within the given rows.
+fn profile(pixmap: &Pixmap, by_row: bool, (start, end): (usize, usize)) -> Vec<usize> {
+    let width = pixmap.width as usize;
+    let height = pixmap.height as usize;
+    let is_ink = |x: usize, y: usize| pixmap.buf[y * width + x] < INK_THRESHOLD;
+    if by_row {
+        (0..height).map(|y| (start..end).filter(|&x| is_ink(x, y)).count()).collect()
+    } else {
+        (0..width).map(|x| (start..end).filter(|&y| is_ink(x, y)).count()).collect()
+    }
+}
+
+// The range of the profile that remains once the shadows at both ends are removed.
+fn trim_shadows(profile: &[usize], length: usize) -> (usize, usize) {
+    let limit = (SHADOW_RATIO * length as f32) as usize;
+    let start = profile.iter().position(|&n| n <= limit).unwrap_or(profile.len());
+    let end = profile.iter().rposition(|&n| n <= limit).map_or(start, |i| i + 1);
+    (start, end.max(start))
+}
+
+// The ranges of consecutive entries above *threshold*, separated by gaps of at least *min_gap* entries.
+fn find_blocks(profile: &[usize], threshold: usize, min_gap: usize) -> Vec<(usize, usize)> {
+    let mut blocks: Vec<(usize, usize)> = Vec::new();
+    let mut start = None;
+
+    for (i, &n) in profile.iter().enumerate() {
+        if n > threshold {
+            if start.is_none() {
+                start = Some(i);
+            }
+        } else if let Some(s) = start.take() {
+            blocks.push((s, i));
+        }
+    }
+
+    if let Some(s) = start {
+        blocks.push((s, profile.len()));
+    }
+
+    let mut merged: Vec<(usize, usize)> = Vec::new();
+    for block in blocks {
+        if let Some(last) = merged.last_mut() {
+            if block.0 - last.1 < min_gap {
+                last.1 = block.1;
+                continue;
+            }
+        }
+        merged.push(block);
+    }
+
+    merged
+}
+
+// Removes the headers, footers and page numbers.
+fn strip_edge_blocks(blocks: &mut Vec<(usize, usize)>, height: usize) {
+    let edge = (EDGE_RATIO * height as f32) as usize;
+    let max_size = (MAX_EDGE_BLOCK_RATIO * height as f32) as usize;
+    let min_gap = (MIN_EDGE_GAP_RATIO * height as f32) as usize;
+
+    while blocks.len() > 1 {
+        let (start, end) = blocks[0];
+        if end <= edge && end - start <= max_size && blocks[1].0 - end >= min_gap {
+            blocks.remove(0);
+        } else {
+            break;
+        }
+    }
+
+    while blocks.len() > 1 {
+        let n = blocks.len();
+        let (start, end) = blocks[n - 1];
+        if start >= height - edge && end - start <= max_size && start - blocks[n - 2].1 >= min_gap {
+            blocks.pop();
+        } else {
+            break;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn page() -> Pixmap {
+        let (width, height) = (400, 600);
+        let mut buf = vec![255u8; width * height];
+        {
+            let mut fill = |x0: usize, y0: usize, x1: usize, y1: usize| {
+                for y in y0..y1 {
+                    for x in x0..x1 {
+                        buf[y * width + x] = 0;
+                    }
+                }
+            };
+            // A scanning shadow on the left edge.
+            fill(0, 0, 6, height);
+            // A running header.
+            fill(120, 20, 280, 30);
+            // The body, made of lines.
+            for i in 0..18 {
+                fill(50, 80 + 22 * i, 360, 92 + 22 * i);
+            }
+            // A wide figure.
+            fill(40, 480, 370, 540);
+            // A page number.
+            fill(190, 575, 210, 585);
+        }
+        Pixmap { width: width as i32, height: height as i32, buf }
+    }
+
+    #[test]
+    fn test_content_rect() {
+        assert_eq!(content_rect(&page()), Some(rect![40, 80, 370, 540]));
+        let blank = Pixmap { width: 10, height: 10, buf: vec![255; 100] };
+        assert_eq!(content_rect(&blank), None);
+    }
+
+    #[test]
+    fn test_merge_margins() {
+        let margins: Vec<Margin> = [0.1, 0.2, 0.15, 0.0].iter()
+                                                        .map(|&v| Margin::new(v, v, 0.1, 0.3))
+                                                        .collect();
+        let margin = merge_margins(&margins).unwrap();
+        assert_eq!(margin.top, 0.1);
+        assert_eq!(margin.left, 0.3);
+        assert!(merge_margins(&[]).is_none());
+    }
+}
@@ -0,0 +1,136 @@
+use device::CURRENT_DEVICE;
+use framebuffer::{Framebuffer, UpdateMode};
+use view::{View, Event, Hub, Bus};
+use view::reader::list_window::ListItem;
+use font::{MD_TITLE, MD_AUTHOR, MD_SIZE};
+use color::{TEXT_NORMAL, TEXT_INVERTED_HARD};
+use gesture::GestureEvent;
+use metadata::Bookmark;
+use font::{Fonts, font_from_style};
+use geom::Rectangle;
+use app::Context;
+
+// A bookmark: its location, its creation date when known, and its label or excerpt.
+pub struct BookmarkEntry {
+    rect: Rectangle,
+    children: Vec<Box<View>>,
+    bookmark: Bookmark,
+    chapter: Option<String>,
+    active: bool,
+}
+
+impl BookmarkEntry {
+    pub fn new(rect: Rectangle, bookmark: Bookmark, chapter: Option<String>) -> BookmarkEntry {
+        BookmarkEntry {
+            rect,
+            children: vec![],
+            bookmark,
+            chapter,
+            active: false,
+        }
+    }
+}
+
+impl ListItem for Bookmark {
+    fn page(&self) -> usize {
+        self.page
+    }
+
+    fn entry(&self, rect: Rectangle, chapter: Option<String>) -> Box<View> {
+        Box::new(BookmarkEntry::new(rect, self.clone(), chapter)) as Box<View>
+    }
+}
+
+impl View for BookmarkEntry {
+    fn handle_event(&mut self, evt: &Event, hub: &Hub, bus: &mut Bus, _context: &mut Context) -> bool {
+        match *evt {
+            Event::Gesture(GestureEvent::Tap(ref center)) if self.rect.includes(center) => {
+                self.active = true;
+                hub.send(Event::Render(self.rect, UpdateMode::Gui)).unwrap();
+                bus.push_back(Event::GoTo(self.bookmark.page));
+                true
+            },
+            Event::Gesture(GestureEvent::HoldFinger(ref center)) if self.rect.includes(center) => {
+                let pt = pt!(center.x, self.rect.center().y);
+                bus.push_back(Event::ToggleBookmarkMenu(Rectangle::from_point(&pt), self.bookmark.page));
+                true
+            },
+            _ => false,
+        }
+    }
+
+    fn render(&self, fb: &mut Framebuffer, fonts: &mut Fonts) {
+        let dpi = CURRENT_DEVICE.dpi;
+
+        let scheme = if self.active {
+            TEXT_INVERTED_HARD
+        } else {
+            TEXT_NORMAL
+        };
+
+        fb.draw_rectangle(&self.rect, scheme[0]);
+
+        let (x_height, padding, baseline) = {
+            let font = font_from_style(fonts, &MD_AUTHOR, dpi);
+            let x_height = font.x_heights.0 as i32;
+            (x_height, font.em() as i32, (self.rect.height() as i32 - 2 * x_height) / 3)
+        };
+
+        let width = self.rect.width() as i32 - 2 * padding;
+
+        // Date
+        let date_width = if let Some(created) = self.bookmark.created {
+            let date = created.format("%Y-%m-%d").to_string();
+            let font = font_from_style(fonts, &MD_SIZE, dpi);
+            let plan = font.plan(&date, None, None);
+            let pt = pt!(self.rect.max.x - padding - plan.width as i32,
+                         self.rect.min.y + baseline + x_height);
+            font.render(fb, scheme[2], &plan, &pt);
+            plan.width as i32 + padding
+        } else {
+            0
+        };
+
+        // Location
+        {
+            let location = match self.chapter {
+                Some(ref title) => format!("{} · {}", self.bookmark.page + 1, title),
+                None => (self.bookmark.page + 1).to_string(),
+            };
+            let font = font_from_style(fonts, &MD_SIZE, dpi);
+            let max_width = (width - date_width).max(0) as u32;
+            let plan = font.plan(&location, Some(max_width), None);
+            let pt = self.rect.min + pt!(padding, baseline + x_height);
+            font.render(fb, scheme[2], &plan, &pt);
+        }
+
+        // Label or excerpt
+        {
+            let (style, text) = if self.bookmark.label.is_empty() {
+                (&MD_AUTHOR, &self.bookmark.excerpt)
+            } else {
+                (&MD_TITLE, &self.bookmark.label)
+            };
+            let font = font_from_style(fonts, style, dpi);
+            let plan = font.plan(text, Some(width as u32), None);
+            let pt = pt!(self.rect.min.x + padding, self.rect.max.y - baseline);
+            font.render(fb, scheme[1], &plan, &pt);
+        }
+    }
+
+    fn rect(&self) -> &Rectangle {
+        &self.rect
+    }
+
+    fn rect_mut(&mut self) -> &mut Rectangle {
+        &mut self.rect
+    }
+
+    fn children(&self) -> &Vec<Box<View>> {
+        &self.children
+    }
+
+    fn children_mut(&mut self) -> &mut Vec<Box<View>> {
+        &mut self.children
+    }
+}
@@ -21,7 +21,7 @@ pub struct BottomBar {
 }
 
 impl BottomBar {
-    pub fn new(rect: Rectangle, doc: &Document, current_page: usize, pages_count: usize) -> BottomBar {
+    pub fn new(rect: Rectangle, doc: &Document, current_page: usize, pages_count: usize, time_left: Option<String>) -> BottomBar {
         let mut children = Vec::new();
         let side = rect.height() as i32;
         let is_prev_disabled = pages_count < 2 || current_page == 0;
@@ -53,12 +53,23 @@ impl BottomBar {
                                        Align::Center);
         children.push(Box::new(chapter_label) as Box<View>);
 
-        let page_label = PageLabel::new(rect![pt!(rect.max.x - side - big_half_width, rect.min.y),
+        // The time left label, when shown, takes the first half of the page label's space.
+        let page_width = if time_left.is_some() { halves(big_half_width).1 } else { big_half_width };
+
+        let page_label = PageLabel::new(rect![pt!(rect.max.x - side - page_width, rect.min.y),
                                               pt!(rect.max.x - side, rect.max.y)],
                                         current_page,
                                         pages_count);
         children.push(Box::new(page_label) as Box<View>);
 
+        if let Some(text) = time_left {
+            let time_left_label = Label::new(rect![pt!(rect.max.x - side - big_half_width, rect.min.y),
+                                                   pt!(rect.max.x - side - page_width, rect.max.y)],
+                                             text,
+                                             Align::Center);
+            children.push(Box::new(time_left_label) as Box<View>);
+        }
+
         let next_rect = rect![rect.max - side, rect.max];
 
         if is_next_disabled {
@@ -89,6 +100,12 @@ impl BottomBar {
         chapter_label.update(text, hub);
     }
 
+    pub fn update_time_left(&mut self, text: String, hub: &Hub) {
+        if let Some(time_left_label) = self.child_mut(3).downcast_mut::<Label>() {
+            time_left_label.update(text, hub);
+        }
+    }
+
     pub fn update_icons(&mut self, current_page: usize, pages_count: usize, hub: &Hub) {
         let is_prev_disabled = pages_count < 2 || current_page == 0;
 
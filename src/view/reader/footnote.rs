@@ -0,0 +1,23 @@
+use geom::Rectangle;
+use view::{Hub, ViewId, Align};
+use view::html_window::HtmlWindow;
+use document::escape_html;
+use app::Context;
+
+const FOOTNOTE_CSS: &str = "css/footnote.css";
+const LABEL_FOOTNOTE: &str = "Note";
+
+// Shows the text of a footnote without leaving the current page, in a window
+// covering the half of the screen that doesn't contain *link_rect*.
+pub fn footnote_window(text: &str, link_rect: &Rectangle, hub: &Hub, context: &mut Context) -> HtmlWindow {
+    let html = format!(r#"<html>
+                              <head>
+                                  <title>Note</title>
+                                  <link rel="stylesheet" type="text/css" href="css/footnote.css"/>
+                              </head>
+                          <body><p>{}</p></body></html>"#, escape_html(text));
+    let mut window = HtmlWindow::new(ViewId::Footnote, LABEL_FOOTNOTE, Align::Center,
+                                     link_rect, None, context);
+    window.load(&html, FOOTNOTE_CSS, hub);
+    window
+}
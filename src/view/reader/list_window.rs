@@ -0,0 +1,193 @@
+use device::{CURRENT_DEVICE, BAR_SIZES};
+use framebuffer::{Framebuffer, UpdateMode};
+use geom::{Rectangle, Dir};
+use font::Fonts;
+use view::{View, Event, Hub, Bus, ViewId, Align};
+use view::THICKNESS_MEDIUM;
+use view::popup::{Popup, TITLE_BAR_CHILDREN};
+use view::filler::Filler;
+use document::{TocEntry, chapter_at};
+use gesture::GestureEvent;
+use color::{WHITE, SEPARATOR_NORMAL};
+use unit::scale_by_dpi;
+use app::Context;
+
+// The items of a list window: locations in the document.
+pub trait ListItem: 'static {
+    fn page(&self) -> usize;
+    // The view of the item, *chapter* is the title of the chapter containing its page.
+    fn entry(&self, rect: Rectangle, chapter: Option<String>) -> Box<View>;
+}
+
+// A popup listing locations of the document, in page order, with their chapters.
+// The entries send `GoTo` events: the window is closed and the reader goes to the page.
+pub struct ListWindow<T: ListItem> {
+    popup: Popup,
+    children: Vec<Box<View>>,
+    title: String,
+    toc: Option<Vec<TocEntry>>,
+    items: Vec<T>,
+    current_page: usize,
+    max_lines: usize,
+}
+
+impl<T: ListItem> ListWindow<T> {
+    pub fn new(id: ViewId, title: &str, items: Vec<T>, toc: Option<Vec<TocEntry>>, hub: &Hub, context: &mut Context) -> ListWindow<T> {
+        let dpi = CURRENT_DEVICE.dpi;
+        let (_, height) = CURRENT_DEVICE.display_dims();
+        let &(_, big_height) = BAR_SIZES.get(&(height, dpi)).unwrap();
+        let popup = Popup::full_screen(id, &mut context.fonts);
+        let children = popup.title_bar(title.to_string(), Align::Center, None);
+
+        let body_rect = popup.body_rect();
+        let separator_thickness = scale_by_dpi(THICKNESS_MEDIUM, dpi) as u32;
+        let max_lines = ((body_rect.height() + separator_thickness) / big_height).max(1) as usize;
+
+        let mut list_window = ListWindow {
+            popup,
+            children,
+            title: title.to_string(),
+            toc,
+            items,
+            current_page: 0,
+            max_lines,
+        };
+
+        list_window.update_entries();
+        list_window.update_label(hub);
+        hub.send(Event::Render(list_window.popup.rect, UpdateMode::Gui)).unwrap();
+
+        list_window
+    }
+
+    fn pages_count(&self) -> usize {
+        ((self.items.len() + self.max_lines - 1) / self.max_lines).max(1)
+    }
+
+    // Inserts an item at the given position, e.g. while a search is running.
+    pub fn insert(&mut self, position: usize, item: T, hub: &Hub) {
+        let pages_count = self.pages_count();
+        self.items.insert(position, item);
+        if position < (self.current_page + 1) * self.max_lines {
+            self.update_entries();
+            hub.send(Event::Render(self.popup.body_rect(), UpdateMode::Gui)).unwrap();
+        }
+        if self.pages_count() != pages_count {
+            self.update_label(hub);
+        }
+    }
+
+    // Replaces the items, e.g. when one of them was modified or removed.
+    pub fn update(&mut self, items: Vec<T>, hub: &Hub) {
+        self.items = items;
+        self.current_page = self.current_page.min(self.pages_count() - 1);
+        self.update_entries();
+        self.update_label(hub);
+        hub.send(Event::Render(self.popup.body_rect(), UpdateMode::Gui)).unwrap();
+    }
+
+    fn update_entries(&mut self) {
+        let dpi = CURRENT_DEVICE.dpi;
+        let (_, height) = CURRENT_DEVICE.display_dims();
+        let &(_, big_height) = BAR_SIZES.get(&(height, dpi)).unwrap();
+        let thickness = scale_by_dpi(THICKNESS_MEDIUM, dpi) as i32;
+        let body_rect = self.popup.body_rect();
+
+        self.children.truncate(TITLE_BAR_CHILDREN);
+
+        let start = self.current_page * self.max_lines;
+        let end = (start + self.max_lines).min(self.items.len());
+
+        for (i, item) in self.items[start..end].iter().enumerate() {
+            let y_min = body_rect.min.y + i as i32 * big_height as i32;
+            let y_max = y_min + big_height as i32 - thickness;
+            let chapter = self.toc.as_ref()
+                              .and_then(|toc| chapter_at(toc, item.page()))
+                              .map(|c| c.title.clone());
+            let entry = item.entry(rect![body_rect.min.x, y_min,
+                                         body_rect.max.x, y_max],
+                                   chapter);
+            self.children.push(entry);
+            let separator = Filler::new(rect![body_rect.min.x, y_max,
+                                              body_rect.max.x, y_max + thickness],
+                                        SEPARATOR_NORMAL);
+            self.children.push(Box::new(separator) as Box<View>);
+        }
+
+        let y_min = body_rect.min.y + (end - start) as i32 * big_height as i32;
+        if y_min < body_rect.max.y {
+            let filler = Filler::new(rect![body_rect.min.x, y_min,
+                                           body_rect.max.x, body_rect.max.y],
+                                     WHITE);
+            self.children.push(Box::new(filler) as Box<View>);
+        }
+    }
+
+    fn update_label(&mut self, hub: &Hub) {
+        let pages_count = self.pages_count();
+        self.popup.update_title(&mut self.children, &self.title,
+                                self.current_page, pages_count, hub);
+    }
+
+    fn set_current_page(&mut self, dir: Dir, hub: &Hub) {
+        let index = match dir {
+            Dir::West if self.current_page + 1 < self.pages_count() => self.current_page + 1,
+            Dir::East if self.current_page > 0 => self.current_page - 1,
+            _ => return,
+        };
+        self.current_page = index;
+        self.update_entries();
+        self.update_label(hub);
+        hub.send(Event::Render(self.popup.body_rect(), UpdateMode::Gui)).unwrap();
+    }
+}
+
+impl<T: ListItem> View for ListWindow<T> {
+    fn handle_event(&mut self, evt: &Event, hub: &Hub, _bus: &mut Bus, _context: &mut Context) -> bool {
+        match *evt {
+            Event::Gesture(GestureEvent::Tap(ref center)) if !self.popup.rect.includes(center) => {
+                hub.send(Event::Close(self.popup.id)).unwrap();
+                true
+            },
+            Event::Gesture(GestureEvent::Swipe { dir, ref start, .. }) if self.popup.rect.includes(start) => {
+                self.set_current_page(dir, hub);
+                true
+            },
+            // Sent by the entries: the reader goes to the page once the list is closed.
+            Event::GoTo(..) => {
+                hub.send(Event::Close(self.popup.id)).unwrap();
+                false
+            },
+            Event::Gesture(..) => true,
+            _ => false,
+        }
+    }
+
+    fn render(&self, fb: &mut Framebuffer, _fonts: &mut Fonts) {
+        self.popup.render(fb);
+    }
+
+    fn is_background(&self) -> bool {
+        true
+    }
+
+    fn rect(&self) -> &Rectangle {
+        &self.popup.rect
+    }
+
+    fn rect_mut(&mut self) -> &mut Rectangle {
+        &mut self.popup.rect
+    }
+
+    fn children(&self) -> &Vec<Box<View>> {
+        &self.children
+    }
+
+    fn children_mut(&mut self) -> &mut Vec<Box<View>> {
+        &mut self.children
+    }
+
+    fn id(&self) -> Option<ViewId> {
+        Some(self.popup.id)
+    }
+}
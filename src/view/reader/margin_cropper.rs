@@ -35,7 +35,7 @@ impl MarginCropper {
         let frame = rect![x_min, y_min, x_max, y_max];
 
         let dpi = CURRENT_DEVICE.dpi;
-        let (_, height) = CURRENT_DEVICE.dims;
+        let (_, height) = CURRENT_DEVICE.display_dims();
         let &(small_height, _) = BAR_SIZES.get(&(height, dpi)).unwrap();
         let big_button_diameter = small_height as i32;
         let padding = big_button_diameter / 2;
@@ -3,15 +3,21 @@ mod tool_bar;
 mod bottom_bar;
 mod results_bar;
 mod margin_cropper;
+mod auto_crop;
+mod footnote;
 mod results_label;
+mod list_window;
+mod result_entry;
+mod bookmark_entry;
+mod thumbnails;
+mod prerender;
 
 use std::thread;
 use std::sync::{Arc, Mutex, mpsc};
 use std::sync::atomic::AtomicBool;
 use std::sync::atomic::Ordering;
-use std::rc::Rc;
 use std::path::PathBuf;
-use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::collections::{BTreeMap, VecDeque};
 use chrono::Local;
 use regex::Regex;
 use input::FingerStatus;
@@ -19,12 +25,17 @@ use framebuffer::{Framebuffer, UpdateMode, Pixmap};
 use view::{View, Event, Hub, ViewId, EntryKind, EntryId, SliderId, Bus, THICKNESS_MEDIUM};
 use unit::{scale_by_dpi, pt_to_px, mm_to_in};
 use device::{CURRENT_DEVICE, BAR_SIZES};
-use font::{Fonts, DEFAULT_FONT_SIZE};
+use font::{Fonts, DEFAULT_FONT_SIZE, FONTS_DIR, family_names};
 use self::margin_cropper::{MarginCropper, BUTTON_DIAMETER};
+use self::auto_crop::{content_margin, merge_margins};
+use self::footnote::footnote_window;
 use self::top_bar::TopBar;
 use self::tool_bar::ToolBar;
 use self::bottom_bar::BottomBar;
 use self::results_bar::ResultsBar;
+use self::list_window::ListWindow;
+use self::thumbnails::ThumbnailGrid;
+use self::prerender::{Prerenderer, Job};
 use view::common::{locate, locate_by_id, toggle_main_menu, shift};
 use view::filler::Filler;
 use view::named_input::NamedInput;
@@ -32,26 +43,48 @@ use view::search_bar::SearchBar;
 use view::keyboard::{Keyboard, DEFAULT_LAYOUT};
 use view::menu::{Menu, MenuKind};
 use view::notification::Notification;
-use settings::{guess_frontlight, FinishedAction};
+use view::dictionary::{dictionary_window, set_dictionary};
+use view::html_window::HtmlWindow;
+use settings::{guess_frontlight, FinishedAction, LandscapeMode, TimeLeft, GestureAction, Region, Settings};
 use frontlight::LightLevels;
 use gesture::GestureEvent;
-use document::{Document, TocEntry, open, toc_as_html, chapter_at, chapter_relative};
+use document::{Document, BoundedText, Link, TocEntry, Snippet, book_typography, is_external, find_phrases, snippet, open, toc_as_html, annotations_as_html, chapter_at, chapter_relative};
 use document::pdf::PdfOpener;
-use metadata::{Info, FileInfo, ReaderInfo, PageScheme, Margin, CroppingMargins, make_query};
-use geom::{Rectangle, CornerSpec, BorderSpec, Dir, CycleDir, LinearDir, halves};
-use color::{BLACK, WHITE};
+use metadata::{Info, FileInfo, ReaderInfo, Annotation, Bookmark, PageScheme, Margin, CroppingMargins, ZoomMode, TextAlign, SearchOptions, make_query, next_volume};
+use statistics::{SessionTracker, Report, load_statistics, record_session, report_as_html};
+use statistics::{update_reading_speed, format_duration};
+use geom::{Point, Rectangle, CornerSpec, BorderSpec, Dir, Axis, CycleDir, LinearDir, halves};
+use color::{BLACK, WHITE, GRAY08};
 use app::Context;
 use input::{DeviceEvent, ButtonCode, ButtonStatus};
 
 
 const HISTORY_SIZE: usize = 32;
+// The number of words at the top of the next page searched for the results that cross a page break.
+const PAGE_BREAK_WORDS: usize = 32;
+// The number of characters of the bookmark excerpts.
+const EXCERPT_LENGTH: usize = 80;
+// The custom zoom levels, relative to the width of the screen.
+const ZOOM_LEVELS: [u16; 4] = [125, 150, 200, 300];
+// The fraction of the screen height scrolled by a tap, in the scrolling zoom modes.
+const SCROLL_RATIO: f32 = 0.9;
+// The highest custom zoom reachable by spreading, relative to the width of the screen.
+const MAX_ZOOM: f32 = 4.0;
+// The zoom factor applied by the zoom actions that aren't triggered by a pinch or a spread.
+const ZOOM_STEP: f32 = 1.25;
+// The number of pages analyzed when cropping the margins automatically.
+const AUTO_CROP_PAGES: usize = 8;
+// The line heights of reflowable documents, in percents of the font size.
+const LINE_HEIGHTS: [u16; 6] = [100, 120, 140, 160, 180, 200];
+// The margin widths of reflowable documents, in millimeters.
+const MARGIN_WIDTHS: [u8; 6] = [0, 2, 4, 6, 8, 10];
 
 pub struct Reader {
     rect: Rectangle,
     children: Vec<Box<View>>,
     info: Info,
     doc: Arc<Mutex<Box<Document>>>,
-    pixmap: Rc<Pixmap>,
+    pixmap: Arc<Pixmap>,
     current_page: usize,
     pages_count: usize,
     page_turns: usize,
@@ -59,16 +92,47 @@ pub struct Reader {
     ephemeral: bool,
     refresh_every: u8,
     search_direction: LinearDir,
+    search_options: SearchOptions,
     frame: Rectangle,
     scale: f32,
+    zoom_mode: ZoomMode,
+    // Whether two pages are displayed side by side when fitting pages.
+    spread: bool,
+    // The position of the top left corner of the screen within the frame.
+    offset: Point,
+    // The pages displayed below, or beside, the current one.
+    next_pages: Vec<(Arc<Pixmap>, Rectangle)>,
+    prerenderer: Prerenderer,
     focus: Option<ViewId>,
     search: Option<Search>,
+    // The pages left by jumping elsewhere, and by going back, most recent last.
     history: VecDeque<usize>,
+    forward_history: Vec<usize>,
+    words: Vec<BoundedText>,
+    selection: Option<Selection>,
+    edited_annotation: Option<usize>,
+    // The page of the bookmark being renamed.
+    renamed_bookmark: Option<usize>,
+    // The current reading session, ephemeral documents aren't tracked.
+    session: Option<SessionTracker>,
+    time_left: Option<TimeLeft>,
+}
+
+struct Selection {
+    anchor: usize,
+    start: usize,
+    end: usize,
+    active: bool,
+    // Whether the selection was extended beyond the initial word.
+    extended: bool,
 }
 
 struct Search {
     query: String,
-    highlights: BTreeMap<usize, Vec<Rectangle>>,
+    // The rectangles of each result, by page.
+    highlights: BTreeMap<usize, Vec<Vec<Rectangle>>>,
+    // The page and the snippet of each result, in page order.
+    results: Vec<(usize, Snippet)>,
     running: Arc<AtomicBool>,
     current_page: usize,
     results_count: usize,
@@ -79,6 +143,7 @@ impl Default for Search {
         Search {
             query: String::new(),
             highlights: BTreeMap::new(),
+            results: Vec::new(),
             running: Arc::new(AtomicBool::new(true)),
             current_page: 0,
             results_count: 0,
@@ -92,7 +157,10 @@ impl Reader {
         let path = settings.library_path.join(&info.file.path);
 
         open(&path).map(|mut doc| {
-            let (width, height) = CURRENT_DEVICE.dims;
+            let (width, height) = (rect.width(), rect.height());
+            if doc.is_reflowable() {
+                doc.set_typography(&book_typography(info.reader.as_ref(), &settings.reader.font_path));
+            }
             let font_size = info.reader.as_ref().and_then(|r| r.font_size);
             doc.layout(width as f32, height as f32,
                        pt_to_px(font_size.unwrap_or(DEFAULT_FONT_SIZE),
@@ -108,6 +176,10 @@ impl Reader {
                     r.finished = false;
                     r.current_page = 0;
                 }
+                // The pagination of reflowable documents can change between two openings.
+                if r.pages_count != doc.pages_count() {
+                    r.rescale_pages(doc.pages_count());
+                }
                 current_page = r.current_page;
                 pages_count = r.pages_count;
             } else {
@@ -126,20 +198,32 @@ impl Reader {
                              .and_then(|r| r.cropping_margins.as_ref()
                                             .map(|c| c.margin(current_page)))
                              .cloned().unwrap_or_default();
-            let (pixmap, scale) = build_pixmap(&rect, doc.as_ref(), current_page, &margin);
-            let frame = rect![(margin.left * pixmap.width as f32).ceil() as i32,
-                              (margin.top * pixmap.height as f32).ceil() as i32,
-                              ((1.0 - margin.right) * pixmap.width as f32).floor() as i32,
-                              ((1.0 - margin.bottom) * pixmap.height as f32).floor() as i32];
-            let pixmap = Rc::new(pixmap);
+            let zoom_mode = info.reader.as_ref()
+                                .and_then(|r| r.zoom_mode)
+                                .unwrap_or_else(|| default_zoom_mode(&rect, settings));
+            let spread = is_spread(&rect, doc.as_ref(), settings);
+            let page_rect = if spread && zoom_mode == ZoomMode::FitToPage {
+                left_half(&rect)
+            } else {
+                rect
+            };
+            let (history, forward_history) = info.reader.as_ref()
+                                                 .map(|r| (r.history.iter().cloned().collect(),
+                                                           r.forward_history.clone()))
+                                                 .unwrap_or_default();
+            let (pixmap, scale) = build_pixmap(&page_rect, doc.as_ref(), current_page, &margin, zoom_mode);
+            let frame = frame_rect(&pixmap, &margin);
+            let pixmap = Arc::new(pixmap);
+            let doc = Arc::new(Mutex::new(doc));
+            let prerenderer = Prerenderer::new(&doc);
 
             hub.send(Event::Render(rect, UpdateMode::Partial)).unwrap();
 
-            Reader {
+            let mut reader = Reader {
                 rect,
                 children: vec![],
                 info,
-                doc: Arc::new(Mutex::new(doc)),
+                doc,
                 pixmap,
                 current_page,
                 pages_count,
@@ -148,20 +232,55 @@ impl Reader {
                 ephemeral: false,
                 refresh_every: settings.reader.refresh_every,
                 search_direction: LinearDir::Forward,
+                search_options: SearchOptions::default(),
                 frame,
                 scale,
+                zoom_mode,
+                spread,
+                offset: pt!(0, 0),
+                next_pages: Vec::new(),
+                prerenderer,
                 focus: None,
                 search: None,
-                history: VecDeque::new(),
+                history,
+                forward_history,
+                words: Vec::new(),
+                selection: None,
+                edited_annotation: None,
+                renamed_bookmark: None,
+                session: Some(SessionTracker::new(current_page)),
+                time_left: settings.reader.time_left,
+            };
+
+            if zoom_mode != ZoomMode::FitToPage || spread {
+                reader.update_next_pages();
             }
+
+            reader.prerender();
+
+            reader
         })
     }
 
-    pub fn from_toc(rect: Rectangle, toc: &[TocEntry], mut current_page: usize, hub: &Hub, context: &mut Context) -> Reader {
+    pub fn from_toc(rect: Rectangle, toc: &[TocEntry], current_page: usize, hub: &Hub, context: &mut Context) -> Reader {
         let html = toc_as_html(toc, current_page);
+        let link_uri = chapter_at(toc, current_page).map(|chap| format!("@{}", chap.page));
+        Reader::from_html(rect, "Table of Contents", &html, "css/toc.css", link_uri, hub, context)
+    }
+
+    pub fn from_annotations(rect: Rectangle, annotations: &[Annotation], toc: &[TocEntry], hub: &Hub, context: &mut Context) -> Reader {
+        let html = annotations_as_html(annotations, Some(toc));
+        Reader::from_html(rect, "Annotations", &html, "css/annotations.css", None, hub, context)
+    }
+
+    pub fn from_statistics(rect: Rectangle, report: &Report, hub: &Hub, context: &mut Context) -> Reader {
+        let html = report_as_html(report);
+        Reader::from_html(rect, "Statistics", &html, "css/statistics.css", None, hub, context)
+    }
 
+    fn from_html(rect: Rectangle, title: &str, html: &str, css: &str, link_uri: Option<String>, hub: &Hub, context: &mut Context) -> Reader {
         let info = Info {
-            title: "Table of Contents".to_string(),
+            title: title.to_string(),
             file: FileInfo {
                 path: PathBuf::from("toc:"),
                 kind: "html".to_string(),
@@ -171,21 +290,22 @@ impl Reader {
         };
 
         let mut opener = PdfOpener::new().unwrap();
-        opener.set_user_css("css/toc.css").unwrap();
+        opener.set_user_css(css).unwrap();
         let doc = opener.open_memory("html", html.as_bytes()).unwrap();
         let pages_count = doc.pages_count();
 
-        current_page = chapter_at(toc, current_page).and_then(|chap| {
-            let link_uri = format!("@{}", chap.page);
+        let current_page = link_uri.and_then(|link_uri| {
             (0..pages_count).find(|index| doc.links(*index).as_ref()
                                              .and_then(|links| links.iter()
                                                                     .find(|link| link.uri == link_uri))
                                              .is_some())}).unwrap_or(0);
 
-        let (pixmap, scale) = build_pixmap(&rect, &doc, current_page, &Margin::default());
-        let pixmap = Rc::new(pixmap);
+        let (pixmap, scale) = build_pixmap(&rect, &doc, current_page, &Margin::default(), ZoomMode::FitToPage);
+        let pixmap = Arc::new(pixmap);
         let frame = rect![0, 0, pixmap.width, pixmap.height];
         println!("pixmap");
+        let doc: Arc<Mutex<Box<Document>>> = Arc::new(Mutex::new(Box::new(doc)));
+        let prerenderer = Prerenderer::new(&doc);
 
         hub.send(Event::Render(rect, UpdateMode::Partial)).unwrap();
 
@@ -193,7 +313,7 @@ impl Reader {
             rect,
             children: vec![],
             info,
-            doc: Arc::new(Mutex::new(Box::new(doc))),
+            doc,
             pixmap,
             current_page,
             pages_count,
@@ -202,24 +322,40 @@ impl Reader {
             ephemeral: true,
             refresh_every: context.settings.reader.refresh_every,
             search_direction: LinearDir::Forward,
+            search_options: SearchOptions::default(),
             frame,
             scale,
+            zoom_mode: ZoomMode::FitToPage,
+            spread: false,
+            offset: pt!(0, 0),
+            next_pages: Vec::new(),
+            prerenderer,
             focus: None,
             search: None,
             history: VecDeque::new(),
+            forward_history: Vec::new(),
+            words: Vec::new(),
+            selection: None,
+            edited_annotation: None,
+            renamed_bookmark: None,
+            session: None,
+            time_left: None,
         }
     }
 
     fn go_to_page(&mut self, index: usize, record: bool, hub: &Hub) {
+        self.go_to_location(index, 0, record, hub);
+    }
+
+    // Goes to the given vertical offset within the frame of the given page.
+    fn go_to_location(&mut self, index: usize, offset: i32, record: bool, hub: &Hub) {
         if index >= self.pages_count {
             return;
         }
 
-        if record {
-            self.history.push_back(self.current_page);
-            if self.history.len() > HISTORY_SIZE {
-                self.history.pop_front();
-            }
+        if record && index != self.current_page {
+            self.push_history();
+            self.forward_history.clear();
         }
 
         if let Some(ref mut s) = self.search {
@@ -227,6 +363,7 @@ impl Reader {
         }
 
         self.current_page = index;
+        self.offset.y = offset;
         self.update(hub);
         self.update_bottom_bar(hub);
 
@@ -251,12 +388,12 @@ impl Reader {
         if let Some(ref r) = self.info.reader {
             match dir {
                 CycleDir::Next => {
-                    index = r.bookmarks.range(self.current_page+1 ..)
-                                       .next().cloned();
+                    index = r.bookmarks.iter().map(|b| b.page)
+                                       .find(|page| *page > self.current_page);
                 },
                 CycleDir::Previous => {
-                    index = r.bookmarks.range(.. self.current_page)
-                                       .next_back().cloned();
+                    index = r.bookmarks.iter().rev().map(|b| b.page)
+                                       .find(|page| *page < self.current_page);
                 },
             }
         }
@@ -265,54 +402,231 @@ impl Reader {
         }
     }
 
+    fn push_history(&mut self) {
+        self.history.push_back(self.current_page);
+        if self.history.len() > HISTORY_SIZE {
+            self.history.pop_front();
+        }
+    }
+
     fn go_to_last_page(&mut self, hub: &Hub) {
         if let Some(index) = self.history.pop_back() {
+            self.forward_history.push(self.current_page);
+            self.go_to_page(index, false, hub);
+        }
+    }
+
+    fn go_to_next_location(&mut self, hub: &Hub) {
+        if let Some(index) = self.forward_history.pop() {
+            self.push_history();
             self.go_to_page(index, false, hub);
         }
     }
 
     fn set_current_page(&mut self, dir: CycleDir, hub: &Hub, context: &mut Context) {
+        if self.zoom_mode != ZoomMode::FitToPage {
+            let delta = (SCROLL_RATIO * self.rect.height() as f32) as i32;
+            match dir {
+                CycleDir::Next => self.vertical_scroll(delta, hub, context),
+                CycleDir::Previous => self.vertical_scroll(-delta, hub, context),
+            }
+            return;
+        }
+
         let current_page = self.current_page;
+        let step = if self.two_pages() { 2 } else { 1 };
         match dir {
-            CycleDir::Next if current_page < self.pages_count - 1 => {
-                self.go_to_page(current_page + 1, false, hub);
+            CycleDir::Next if current_page + step < self.pages_count => {
+                self.go_to_page(current_page + step, false, hub);
             },
             CycleDir::Previous if current_page > 0 => {
-                self.go_to_page(current_page - 1, false, hub);
-            },
-            CycleDir::Next if current_page == self.pages_count - 1 => {
-                self.finished = true;
-                match context.settings.reader.finished {
-                    FinishedAction::Notify => {
-                        let notif = Notification::new(ViewId::FinishedNotif,
-                                                      "No next page.".to_string(),
-                                                      &mut context.notification_index,
-                                                      &mut context.fonts,
-                                                      hub);
-                        self.children.push(Box::new(notif) as Box<View>);
-                    },
-                    FinishedAction::Close => {
-                        hub.send(Event::Back).unwrap();
-                    },
+                self.go_to_page(current_page.saturating_sub(step), false, hub);
+            },
+            CycleDir::Next => {
+                self.finish(hub, context);
+            },
+            _ => (),
+        }
+    }
+
+    fn finish(&mut self, hub: &Hub, context: &mut Context) {
+        self.finished = true;
+        match context.settings.reader.finished {
+            FinishedAction::NextVolume if !self.ephemeral => {
+                if let Some(info) = next_volume(&context.metadata, &self.info).cloned() {
+                    self.quit(context);
+                    hub.send(Event::Back).unwrap();
+                    hub.send(Event::Open(Box::new(info))).unwrap();
+                } else {
+                    let notif = Notification::new(ViewId::FinishedNotif,
+                                                  "No next volume.".to_string(),
+                                                  &mut context.notification_index,
+                                                  &mut context.fonts,
+                                                  hub);
+                    self.children.push(Box::new(notif) as Box<View>);
+                }
+            },
+            FinishedAction::Notify | FinishedAction::NextVolume => {
+                let notif = Notification::new(ViewId::FinishedNotif,
+                                              "No next page.".to_string(),
+                                              &mut context.notification_index,
+                                              &mut context.fonts,
+                                              hub);
+                self.children.push(Box::new(notif) as Box<View>);
+            },
+            FinishedAction::Close => {
+                hub.send(Event::Back).unwrap();
+            },
+        }
+    }
+
+    // Scrolls through the pages by *delta* pixels, in the scrolling zoom modes.
+    fn vertical_scroll(&mut self, delta: i32, hub: &Hub, context: &mut Context) {
+        let screen_height = self.rect.height() as i32;
+        let last_page = self.pages_count - 1;
+        let location = {
+            let doc = self.doc.lock().unwrap();
+            let height_of = |index: usize| {
+                let margin = self.info.reader.as_ref()
+                                 .and_then(|r| r.cropping_margins.as_ref()
+                                                .map(|c| c.margin(index)))
+                                 .cloned().unwrap_or_default();
+                frame_height(&self.rect, doc.as_ref(), index, &margin, self.zoom_mode)
+            };
+            let max_offset = (height_of(last_page) - screen_height).max(0);
+            let mut index = self.current_page;
+            let mut offset = self.offset.y + delta;
+
+            if delta > 0 {
+                if index == last_page && self.offset.y >= max_offset {
+                    None
+                } else {
+                    while index < last_page && offset >= height_of(index) {
+                        offset -= height_of(index);
+                        index += 1;
+                    }
+                    if index == last_page {
+                        offset = offset.min(max_offset);
+                    }
+                    Some((index, offset))
                 }
+            } else {
+                while offset < 0 && index > 0 {
+                    index -= 1;
+                    offset += height_of(index);
+                }
+                Some((index, offset.max(0)))
+            }
+        };
+
+        match location {
+            None => self.finish(hub, context),
+            Some((index, offset)) if index != self.current_page => {
+                self.go_to_location(index, offset, false, hub);
+            },
+            Some((_, offset)) if offset != self.offset.y => {
+                self.offset.y = offset;
+                self.update(hub);
             },
             _ => (),
         }
     }
 
-    fn go_to_results_page(&mut self, index: usize, hub: &Hub) {
-        let mut doc_index = None;
-        if let Some(ref mut s) = self.search {
-            if index < s.highlights.len() {
-                s.current_page = index;
-                doc_index = Some(*s.highlights.keys().nth(index).unwrap());
+    // Pans horizontally, when the frame is wider than the screen.
+    fn horizontal_scroll(&mut self, delta: i32, hub: &Hub) {
+        let max_offset = (self.frame.width() as i32 - self.rect.width() as i32).max(0);
+        let offset = (self.offset.x + delta).max(0).min(max_offset);
+        if offset != self.offset.x {
+            self.offset.x = offset;
+            hub.send(Event::Render(self.rect, UpdateMode::Partial)).unwrap();
+        }
+    }
+
+    fn set_zoom_mode(&mut self, zoom_mode: ZoomMode, hub: &Hub) {
+        if self.zoom_mode == zoom_mode {
+            return;
+        }
+
+        self.zoom_mode = zoom_mode;
+        self.offset = pt!(0, 0);
+
+        if let Some(ref mut r) = self.info.reader {
+            r.zoom_mode = Some(zoom_mode);
+        }
+
+        self.update(hub);
+    }
+
+    // Multiplies the zoom by *factor*, keeping the point at *center* in place.
+    fn zoom(&mut self, factor: f32, center: Point, hub: &Hub, context: &Context) {
+        if self.ephemeral {
+            return;
+        }
+
+        let current_page = self.current_page;
+        let margin = self.page_margin(current_page);
+        let (width_scale, page_scale) = {
+            let doc = self.doc.lock().unwrap();
+            if doc.is_reflowable() {
+                return;
             }
+            (scaling_factor(&self.rect, doc.as_ref(), current_page, &margin, ZoomMode::FitToWidth),
+             scaling_factor(&self.rect, doc.as_ref(), current_page, &margin, ZoomMode::FitToPage))
+        };
+
+        let zoom = (factor * self.scale / width_scale).min(MAX_ZOOM);
+
+        // Zooming out beyond the page's fitting zoom resets the zoom.
+        if zoom <= (page_scale / width_scale).min(1.0) {
+            self.reset_zoom(hub, context);
+            return;
+        }
+
+        let ratio = zoom * width_scale / self.scale;
+        let position = center - self.origin();
+        let x = (ratio * position.x as f32) as i32 - (center.x - self.rect.min.x);
+        let y = (ratio * position.y as f32) as i32 - (center.y - self.rect.min.y);
+
+        self.zoom_mode = ZoomMode::Custom(zoom);
+
+        if let Some(ref mut r) = self.info.reader {
+            r.zoom_mode = Some(self.zoom_mode);
+        }
+
+        let max_y = {
+            let doc = self.doc.lock().unwrap();
+            frame_height(&self.rect, doc.as_ref(), current_page, &margin, self.zoom_mode) - 1
+        };
+        self.offset = pt!(x.max(0), y.min(max_y).max(0));
+        self.update(hub);
+        let max_x = self.frame.width() as i32 - self.rect.width() as i32;
+        self.offset.x = self.offset.x.min(max_x.max(0));
+    }
+
+    fn reset_zoom(&mut self, hub: &Hub, context: &Context) {
+        let zoom_mode = default_zoom_mode(&self.rect, &context.settings);
+        self.set_zoom_mode(zoom_mode, hub);
+    }
+
+    // Zooms in or out, according to *action*, by the ratio of the distances between the fingers.
+    fn pinch_zoom(&mut self, action: GestureAction, starts: &[Point; 2], ends: &[Point; 2], hub: &Hub, context: &mut Context) -> bool {
+        let d_start = (starts[0] - starts[1]).length().max(1.0);
+        let d_end = (ends[0] - ends[1]).length().max(1.0);
+        let factor = (d_end / d_start).max(d_start / d_end);
+        let center = (ends[0] + ends[1]) / 2;
+        match action {
+            GestureAction::ZoomIn => self.zoom(factor, center, hub, context),
+            GestureAction::ZoomOut => self.zoom(1.0 / factor, center, hub, context),
+            _ => return self.perform_action(action, hub, context),
         }
+        true
+    }
+
+    fn go_to_results_page(&mut self, index: usize, hub: &Hub) {
+        let doc_index = self.search.as_ref()
+                            .and_then(|s| s.highlights.keys().nth(index).cloned());
         if let Some(doc_index) = doc_index {
-            self.current_page = doc_index;
-            self.update_results_bar(hub);
-            self.update_bottom_bar(hub);
-            self.update(hub);
+            self.go_to_page(doc_index, true, hub);
         }
     }
 
@@ -330,6 +644,7 @@ impl Reader {
                 s.current_page = s.highlights.range(..index+1).count().saturating_sub(1);
             }
             self.current_page = index;
+            self.offset.y = 0;
             self.update_results_bar(hub);
             self.update_bottom_bar(hub);
             self.update(hub);
@@ -339,17 +654,41 @@ impl Reader {
     fn update_bottom_bar(&mut self, hub: &Hub) {
         if let Some(index) = locate::<BottomBar>(self) {
             let current_page = self.current_page;
+            let doc = self.doc.lock().unwrap();
+            let time_left = self.time_left_text(doc.as_ref());
             let bottom_bar = self.children[index].as_mut().downcast_mut::<BottomBar>().unwrap();
             bottom_bar.update_page_label(self.current_page, self.pages_count, hub);
             bottom_bar.update_icons(self.current_page, self.pages_count, hub);
-            let doc = self.doc.lock().unwrap();
             let chapter = doc.toc().as_ref().and_then(|t| chapter_at(t, current_page))
                                    .map(|c| c.title.clone())
                                    .unwrap_or_default();
             bottom_bar.update_chapter(chapter, hub);
+            if let Some(text) = time_left {
+                bottom_bar.update_time_left(text, hub);
+            }
         }
     }
 
+    // Estimates, from the reading speed, the time left in the current chapter or book.
+    fn time_left_text(&self, doc: &Document) -> Option<String> {
+        let kind = self.time_left?;
+        let seconds_per_page = self.info.reader.as_ref().and_then(|r| r.seconds_per_page);
+        let chapter_end = if kind == TimeLeft::Chapter {
+            doc.toc().and_then(|toc| chapter_relative(&toc, self.current_page, CycleDir::Next))
+        } else {
+            None
+        };
+        let (end, name) = match chapter_end {
+            Some(page) => (page, "chapter"),
+            None => (self.pages_count, "book"),
+        };
+        Some(seconds_per_page.map(|spp| {
+            let seconds = (end.saturating_sub(self.current_page) as f32 * spp) as u64;
+            let prefix = if seconds < 30 { "" } else { "~" };
+            format!("{}{} left in {}", prefix, format_duration(seconds), name)
+        }).unwrap_or_default())
+    }
+
     fn update_results_bar(&mut self, hub: &Hub) {
         if self.search.is_none() {
             return;
@@ -368,6 +707,16 @@ impl Reader {
 
     fn update(&mut self, hub: &Hub) {
         self.page_turns += 1;
+        let current_page = self.current_page;
+        if let Some((previous, seconds)) = self.session.as_mut().and_then(|s| s.turn_page(current_page)) {
+            // Only the pages read in sequence tell how fast the book is read.
+            if current_page > previous && current_page - previous <= 2 {
+                if let Some(ref mut r) = self.info.reader {
+                    r.seconds_per_page = update_reading_speed(r.seconds_per_page, seconds,
+                                                              current_page - previous);
+                }
+            }
+        }
         let update_mode = if self.refresh_every > 0 {
             if self.page_turns % (self.refresh_every as usize) == 0 {
                 UpdateMode::Full
@@ -378,25 +727,101 @@ impl Reader {
             UpdateMode::Partial
         };
         let start = std::time::Instant::now();
-        let margin = self.info.reader.as_ref()
-                         .and_then(|r| r.cropping_margins.as_ref()
-                                        .map(|c| c.margin(self.current_page)))
-                         .cloned().unwrap_or_default();
+        let margin = self.page_margin(self.current_page);
+        let page_rect = self.page_rect();
         let doc = self.doc.lock().unwrap();
-        let (pixmap, scale) = build_pixmap(&self.rect, doc.as_ref(), self.current_page, &margin);
+        let (pixmap, scale) = self.prerenderer.pixmap(&page_rect, doc.as_ref(), self.current_page, &margin, self.zoom_mode);
+
+        let current_page = self.current_page;
+        self.selection = None;
+        self.words.clear();
+        if let Some(ref mut r) = self.info.reader {
+            if r.annotations.iter().any(|a| a.page == current_page) {
+                self.words = doc.words(current_page).unwrap_or_default();
+                locate_annotations(&mut r.annotations, current_page, &self.words);
+            }
+        }
        
         let duration = start.elapsed();
         println!("rendered in {}", duration.as_millis());
-        self.pixmap = Rc::new(pixmap);
-        let frame = rect![(margin.left * self.pixmap.width as f32).ceil() as i32,
-                          (margin.top * self.pixmap.height as f32).ceil() as i32,
-                          ((1.0 - margin.right) * self.pixmap.width as f32).floor() as i32,
-                          ((1.0 - margin.bottom) * self.pixmap.height as f32).floor() as i32];
-        self.frame = frame;
+        self.frame = frame_rect(&pixmap, &margin);
+        self.pixmap = pixmap;
         self.scale = scale;
+        drop(doc);
+        self.update_next_pages();
+        self.prerender();
         hub.send(Event::Render(self.rect, update_mode)).unwrap();
     }
 
+    fn page_margin(&self, index: usize) -> Margin {
+        self.info.reader.as_ref()
+            .and_then(|r| r.cropping_margins.as_ref()
+                           .map(|c| c.margin(index)))
+            .cloned().unwrap_or_default()
+    }
+
+    // Renders the pages that are visible below, or beside, the current page.
+    fn update_next_pages(&mut self) {
+        self.next_pages.clear();
+
+        if self.two_pages() {
+            let index = self.current_page + 1;
+            if index < self.pages_count {
+                let margin = self.page_margin(index);
+                let page_rect = self.page_rect();
+                let doc = self.doc.lock().unwrap();
+                let (pixmap, _) = self.prerenderer.pixmap(&page_rect, doc.as_ref(), index, &margin, self.zoom_mode);
+                let frame = frame_rect(&pixmap, &margin);
+                self.next_pages.push((pixmap, frame));
+            }
+            return;
+        }
+
+        if self.zoom_mode == ZoomMode::FitToPage {
+            return;
+        }
+
+        let doc = self.doc.lock().unwrap();
+        let mut y = self.frame.height() as i32 - self.offset.y;
+        let mut index = self.current_page + 1;
+
+        while y < self.rect.height() as i32 && index < self.pages_count {
+            let margin = self.page_margin(index);
+            let (pixmap, _) = self.prerenderer.pixmap(&self.rect, doc.as_ref(), index, &margin, self.zoom_mode);
+            let frame = frame_rect(&pixmap, &margin);
+            y += frame.height() as i32;
+            self.next_pages.push((pixmap, frame));
+            index += 1;
+        }
+    }
+
+    // Asks for the pages surrounding the displayed ones to be rendered in the background.
+    fn prerender(&mut self) {
+        if self.ephemeral {
+            return;
+        }
+
+        let step = if self.two_pages() { 2 } else { 1 };
+        let first = self.current_page;
+        let last = first + self.next_pages.len();
+        let page_rect = self.page_rect();
+        let job = |index: usize| {
+            Job {
+                index,
+                rect: page_rect,
+                margin: self.page_margin(index),
+                zoom_mode: self.zoom_mode,
+            }
+        };
+
+        let next = (last + 1 .. last + 1 + step).filter(|&index| index < self.pages_count)
+                                                .map(&job).collect();
+        let previous = (first.saturating_sub(step) .. first).rev()
+                                                            .map(&job).collect();
+
+        self.prerenderer.request(self.current_page, next, previous);
+    }
+
     fn search(&mut self, text: &str, query: Regex, hub: &Hub) {
         let s = Search {
             query: text.to_string(),
@@ -412,6 +837,8 @@ impl Reader {
 
         thread::spawn(move || {
             let mut index = current_page;
+            // The words of a page are needed again when searching the page before it.
+            let mut cache: Option<(usize, Vec<BoundedText>)> = None;
             loop {
                 if !running.load(Ordering::Relaxed) {
                     break;
@@ -419,15 +846,39 @@ impl Reader {
 
                 let doc = doc2.lock().unwrap();
 
-                if let Some(ref words) = doc.words(index) {
-                    for word in words {
-                        if query.is_match(&word.text) {
-                            if !running.load(Ordering::Relaxed) {
-                                break;
-                            }
-                            hub2.send(Event::SearchResult(index, word.rect)).unwrap();
+                let (words, next_words) = match search_direction {
+                    LinearDir::Forward => {
+                        let next = if index + 1 < pages_count { doc.words(index + 1) } else { None };
+                        (page_words(&**doc, &mut cache, index), next)
+                    },
+                    LinearDir::Backward => {
+                        let next = if index + 1 < pages_count { page_words(&**doc, &mut cache, index + 1) } else { None };
+                        (doc.words(index), next)
+                    },
+                };
+
+                if let Some(mut words) = words {
+                    let count = words.len();
+                    if let Some(ref next) = next_words {
+                        words.extend(next.iter().take(PAGE_BREAK_WORDS).cloned());
+                    }
+                    for (start, end) in find_phrases(&query, &words) {
+                        if start >= count || !running.load(Ordering::Relaxed) {
+                            break;
                         }
+                        let mut rects = vec![line_rects(&words[start..end.min(count)])];
+                        if end > count {
+                            rects.push(line_rects(&words[count..end]));
+                        }
+                        hub2.send(Event::SearchResult(index, rects, snippet(&words, start, end))).unwrap();
                     }
+                    cache = match search_direction {
+                        LinearDir::Forward => next_words.map(|w| (index + 1, w)),
+                        LinearDir::Backward => {
+                            words.truncate(count);
+                            Some((index, words))
+                        },
+                    };
                 }
 
                 index = match search_direction {
@@ -488,7 +939,7 @@ impl Reader {
             }
 
             let dpi = CURRENT_DEVICE.dpi;
-            let (_, height) = CURRENT_DEVICE.dims;
+            let (_, height) = CURRENT_DEVICE.display_dims();
             let &(small_height, big_height) = BAR_SIZES.get(&(height, dpi)).unwrap();
             let thickness = scale_by_dpi(THICKNESS_MEDIUM, dpi) as i32;
             let (small_thickness, big_thickness) = halves(thickness);
@@ -555,7 +1006,7 @@ impl Reader {
             }
 
             let dpi = CURRENT_DEVICE.dpi;
-            let (_, height) = CURRENT_DEVICE.dims;
+            let (_, height) = CURRENT_DEVICE.display_dims();
             let &(_, big_height) = BAR_SIZES.get(&(height, dpi)).unwrap();
             let thickness = scale_by_dpi(THICKNESS_MEDIUM, dpi) as i32;
 
@@ -592,7 +1043,7 @@ impl Reader {
             }
 
             let dpi = CURRENT_DEVICE.dpi;
-            let (_, height) = CURRENT_DEVICE.dims;
+            let (_, height) = CURRENT_DEVICE.display_dims();
             let thickness = scale_by_dpi(THICKNESS_MEDIUM, dpi) as i32;
             let &(small_height, _) = BAR_SIZES.get(&(height, dpi)).unwrap();
 
@@ -632,7 +1083,7 @@ impl Reader {
             }
 
             let dpi = CURRENT_DEVICE.dpi;
-            let (_, height) = CURRENT_DEVICE.dims;
+            let (_, height) = CURRENT_DEVICE.display_dims();
             let thickness = scale_by_dpi(THICKNESS_MEDIUM, dpi) as i32;
             let (small_thickness, big_thickness) = halves(thickness);
             let &(small_height, big_height) = BAR_SIZES.get(&(height, dpi)).unwrap();
@@ -723,7 +1174,8 @@ impl Reader {
                                                   self.rect.max.y],
                                             doc.as_ref(),
                                             self.current_page,
-                                            self.pages_count);
+                                            self.pages_count,
+                                            self.time_left_text(doc.as_ref()));
             self.children.insert(index, Box::new(bottom_bar) as Box<View>);
 
             hub.send(Event::Render(self.rect, UpdateMode::Gui)).unwrap();
@@ -778,9 +1230,35 @@ impl Reader {
 
             let first_page = self.info.reader.as_ref()
                                  .and_then(|r| r.first_page).unwrap_or(0);
-            let entries = vec![EntryKind::CheckBox("First Page".to_string(),
-                                                   EntryId::ToggleFirstPage,
-                                                   self.current_page == first_page)];
+            let mut entries = vec![EntryKind::CheckBox("First Page".to_string(),
+                                                       EntryId::ToggleFirstPage,
+                                                       self.current_page == first_page)];
+            if !self.history.is_empty() {
+                entries.push(EntryKind::Command("Go Back".to_string(),
+                                                EntryId::PreviousLocation));
+            }
+            if !self.forward_history.is_empty() {
+                entries.push(EntryKind::Command("Go Forward".to_string(),
+                                                EntryId::NextLocation));
+            }
+            let has_annotations = self.info.reader.as_ref()
+                                      .map_or(false, |r| !r.annotations.is_empty());
+            let has_bookmarks = self.info.reader.as_ref()
+                                    .map_or(false, |r| !r.bookmarks.is_empty());
+            entries.push(EntryKind::Command("Thumbnails".to_string(),
+                                            EntryId::ShowThumbnails));
+            if has_bookmarks {
+                entries.push(EntryKind::Command("Bookmarks".to_string(),
+                                                EntryId::ShowBookmarks));
+            }
+            if has_annotations {
+                entries.push(EntryKind::Command("Annotations".to_string(),
+                                                EntryId::ShowAnnotations));
+            }
+            if !self.ephemeral {
+                entries.push(EntryKind::Command("Statistics".to_string(),
+                                                EntryId::ShowStatistics));
+            }
             let page_menu = Menu::new(rect, ViewId::PageMenu, MenuKind::DropDown, entries, fonts);
             hub.send(Event::Render(*page_menu.rect(), UpdateMode::Gui)).unwrap();
             self.children.push(Box::new(page_menu) as Box<View>);
@@ -810,7 +1288,13 @@ impl Reader {
                                                           is_split.is_some() && !is_split.unwrap()),
                                    EntryKind::RadioButton("Even/Odd".to_string(),
                                                           EntryId::ApplyCroppings(current_page, PageScheme::EvenOdd),
-                                                          is_split.is_some() && is_split.unwrap())];
+                                                          is_split.is_some() && is_split.unwrap()),
+                                   EntryKind::Separator,
+                                   EntryKind::SubMenu("Auto Crop".to_string(),
+                                                      vec![EntryKind::Command("Any".to_string(),
+                                                                              EntryId::AutoCrop(PageScheme::Any)),
+                                                           EntryKind::Command("Even/Odd".to_string(),
+                                                                              EntryId::AutoCrop(PageScheme::EvenOdd))])];
 
             let is_applied = self.info.reader.as_ref()
                                  .map(|r| r.cropping_margins.is_some())
@@ -826,6 +1310,121 @@ impl Reader {
         }
     }
 
+    fn toggle_zoom_menu(&mut self, rect: Rectangle, enable: Option<bool>, hub: &Hub, fonts: &mut Fonts) {
+        if let Some(index) = locate_by_id(self, ViewId::ZoomMenu) {
+            if let Some(true) = enable {
+                return;
+            }
+
+            hub.send(Event::Expose(*self.child(index).rect())).unwrap();
+            self.children.remove(index);
+        } else {
+            if let Some(false) = enable {
+                return;
+            }
+
+            let zoom_mode = self.zoom_mode;
+            let mut entries = vec![EntryKind::RadioButton("Fit to Page".to_string(),
+                                                          EntryId::FitToPage,
+                                                          zoom_mode == ZoomMode::FitToPage),
+                                   EntryKind::RadioButton("Fit to Width".to_string(),
+                                                          EntryId::FitToWidth,
+                                                          zoom_mode == ZoomMode::FitToWidth),
+                                   EntryKind::Separator];
+
+            for percent in &ZOOM_LEVELS {
+                let selected = match zoom_mode {
+                    ZoomMode::Custom(zoom) => (100.0 * zoom).round() as u16 == *percent,
+                    _ => false,
+                };
+                entries.push(EntryKind::RadioButton(format!("{}%", percent),
+                                                    EntryId::Zoom(*percent),
+                                                    selected));
+            }
+
+            let zoom_menu = Menu::new(rect, ViewId::ZoomMenu, MenuKind::DropDown, entries, fonts);
+            hub.send(Event::Render(*zoom_menu.rect(), UpdateMode::Gui)).unwrap();
+            self.children.push(Box::new(zoom_menu) as Box<View>);
+        }
+    }
+
+    fn toggle_typography_menu(&mut self, rect: Rectangle, enable: Option<bool>, hub: &Hub, context: &mut Context) {
+        if let Some(index) = locate_by_id(self, ViewId::TypographyMenu) {
+            if let Some(true) = enable {
+                return;
+            }
+
+            hub.send(Event::Expose(*self.child(index).rect())).unwrap();
+            self.children.remove(index);
+        } else {
+            if let Some(false) = enable {
+                return;
+            }
+
+            let reader = self.info.reader.as_ref();
+            let font_family = reader.and_then(|r| r.font_family.clone());
+            let line_height = reader.and_then(|r| r.line_height)
+                                    .map(|l| (100.0 * l).round() as u16);
+            let margin_width = reader.and_then(|r| r.margin_width);
+            let text_align = reader.and_then(|r| r.text_align)
+                                   .unwrap_or(TextAlign::Justify);
+            let hyphenation = reader.and_then(|r| r.hyphenation)
+                                    .unwrap_or(true);
+
+            let mut names = family_names(FONTS_DIR);
+            names.extend(family_names(&context.settings.reader.font_path));
+
+            let mut families = vec![EntryKind::RadioButton("Default".to_string(),
+                                                           EntryId::FontFamily(None),
+                                                           font_family.is_none())];
+            for name in names {
+                let selected = font_family.as_ref() == Some(&name);
+                families.push(EntryKind::RadioButton(name.clone(),
+                                                     EntryId::FontFamily(Some(name)),
+                                                     selected));
+            }
+
+            let mut line_heights = vec![EntryKind::RadioButton("Default".to_string(),
+                                                               EntryId::LineHeight(None),
+                                                               line_height.is_none())];
+            for percent in &LINE_HEIGHTS {
+                line_heights.push(EntryKind::RadioButton(format!("{:.1}", *percent as f32 / 100.0),
+                                                         EntryId::LineHeight(Some(*percent)),
+                                                         line_height == Some(*percent)));
+            }
+
+            let mut margin_widths = vec![EntryKind::RadioButton("Default".to_string(),
+                                                                EntryId::MarginWidth(None),
+                                                                margin_width.is_none())];
+            for width in &MARGIN_WIDTHS {
+                margin_widths.push(EntryKind::RadioButton(format!("{} mm", width),
+                                                          EntryId::MarginWidth(Some(*width)),
+                                                          margin_width == Some(*width)));
+            }
+
+            let text_aligns = vec![EntryKind::RadioButton("Justify".to_string(),
+                                                          EntryId::TextAlign(TextAlign::Justify),
+                                                          text_align == TextAlign::Justify),
+                                   EntryKind::RadioButton("Left".to_string(),
+                                                          EntryId::TextAlign(TextAlign::Left),
+                                                          text_align == TextAlign::Left)];
+
+            let entries = vec![EntryKind::SubMenu("Font Family".to_string(), families),
+                               EntryKind::SubMenu("Line Height".to_string(), line_heights),
+                               EntryKind::SubMenu("Margin Width".to_string(), margin_widths),
+                               EntryKind::SubMenu("Text Align".to_string(), text_aligns),
+                               EntryKind::CheckBox("Hyphenation".to_string(),
+                                                   EntryId::ToggleHyphenation,
+                                                   hyphenation),
+                               EntryKind::Separator,
+                               EntryKind::Command("Reset".to_string(), EntryId::ResetTypography)];
+
+            let typography_menu = Menu::new(rect, ViewId::TypographyMenu, MenuKind::DropDown, entries, &mut context.fonts);
+            hub.send(Event::Render(*typography_menu.rect(), UpdateMode::Gui)).unwrap();
+            self.children.push(Box::new(typography_menu) as Box<View>);
+        }
+    }
+
     fn toggle_search_menu(&mut self, rect: Rectangle, enable: Option<bool>, hub: &Hub, fonts: &mut Fonts) {
         if let Some(index) = locate_by_id(self, ViewId::SearchMenu) {
             if let Some(true) = enable {
@@ -844,7 +1443,17 @@ impl Reader {
                                                           self.search_direction == LinearDir::Forward),
                                    EntryKind::RadioButton("Backward".to_string(),
                                                           EntryId::SearchDirection(LinearDir::Backward),
-                                                          self.search_direction == LinearDir::Backward)];
+                                                          self.search_direction == LinearDir::Backward),
+                                   EntryKind::Separator,
+                                   EntryKind::CheckBox("Case Sensitive".to_string(),
+                                                       EntryId::ToggleCaseSensitive,
+                                                       self.search_options.case_sensitive),
+                                   EntryKind::CheckBox("Whole Word".to_string(),
+                                                       EntryId::ToggleWholeWord,
+                                                       self.search_options.whole_word),
+                                   EntryKind::CheckBox("Regular Expression".to_string(),
+                                                       EntryId::ToggleRegex,
+                                                       self.search_options.regex)];
 
             let kind = if locate::<SearchBar>(self).is_some() {
                 MenuKind::Contextual
@@ -864,6 +1473,7 @@ impl Reader {
             }
 
             self.toggle_bars(Some(false), hub, context);
+            self.toggle_results_list(Some(false), hub, context);
 
             if let Some(ref mut s) = self.search {
                 s.running.store(false, Ordering::Relaxed);
@@ -878,7 +1488,7 @@ impl Reader {
             self.toggle_tool_bar(false, hub);
 
             let dpi = CURRENT_DEVICE.dpi;
-            let (_, height) = CURRENT_DEVICE.dims;
+            let (_, height) = CURRENT_DEVICE.display_dims();
             let &(small_height, _) = BAR_SIZES.get(&(height, dpi)).unwrap();
 
             let index = locate::<TopBar>(self).unwrap() + 2;
@@ -931,7 +1541,8 @@ impl Reader {
             let (pixmap, _) = build_pixmap(&pixmap_rect,
                                            doc.as_ref(),
                                            self.current_page,
-                                           &Margin::default());
+                                           &Margin::default(),
+                                           ZoomMode::FitToPage);
 
             let margin_cropper = MarginCropper::new(self.rect, pixmap, &margin);
             hub.send(Event::Render(*margin_cropper.rect(), UpdateMode::Gui)).unwrap();
@@ -939,52 +1550,116 @@ impl Reader {
         }
     }
 
-    fn set_font_size(&mut self, font_size: f32, hub: &Hub) {
-        if Arc::strong_count(&self.doc) > 1 {
-            return;
+    // The results of the search refer to the pages of the current layout.
+    fn discard_search(&mut self, hub: &Hub) {
+        if let Some(ref s) = self.search {
+            s.running.store(false, Ordering::Relaxed);
+        }
+
+        if self.search.take().is_some() {
+            self.toggle_results_bar(false, hub);
         }
+    }
+
+    // The other threads wait for the document's lock during the layout: the prerendered pages
+    // are invalidated before it is released, and the search is discarded beforehand.
+    fn set_font_size(&mut self, font_size: f32, hub: &Hub) {
+        self.discard_search(hub);
 
         if let Some(ref mut r) = self.info.reader {
             r.font_size = Some(font_size);
         }
 
-        let (width, height) = CURRENT_DEVICE.dims;
-        {
-            let mut doc = self.doc.lock().unwrap();
+        self.layout(font_size);
+        self.update(hub);
+        self.update_bottom_bar(hub);
+    }
 
-            doc.layout(width as f32, height as f32,
-                      pt_to_px(font_size,
-                               CURRENT_DEVICE.dpi));
-            let ratio = doc.pages_count() as f32 / self.pages_count as f32;
-            self.current_page = (self.current_page as f32 * ratio) as usize;
-
-            if let Some(ref mut r) = self.info.reader {
-                for page in &mut r.first_page {
-                    *page = (*page as f32 * ratio) as usize;
-                }
-                let mut next_bookmarks = BTreeSet::new();
-                for page in &r.bookmarks {
-                    next_bookmarks.insert((*page as f32 * ratio) as usize);
-                }
-                r.bookmarks = next_bookmarks;
-            }
+    fn set_typography<F>(&mut self, change: F, hub: &Hub, context: &Context) where F: FnOnce(&mut ReaderInfo) {
+        self.discard_search(hub);
 
-            self.pages_count = doc.pages_count();
+        if let Some(ref mut r) = self.info.reader {
+            change(r);
         }
 
+        let typography = book_typography(self.info.reader.as_ref(), &context.settings.reader.font_path);
+        self.doc.lock().unwrap().set_typography(&typography);
+
+        let font_size = self.info.reader.as_ref()
+                            .and_then(|r| r.font_size)
+                            .unwrap_or(DEFAULT_FONT_SIZE);
+        self.layout(font_size);
         self.update(hub);
         self.update_bottom_bar(hub);
     }
 
+    // Paginates a reflowable document for the current dimensions of the view.
+    fn layout(&mut self, font_size: f32) {
+        let (width, height) = (self.rect.width(), self.rect.height());
+        let mut doc = self.doc.lock().unwrap();
+
+        doc.layout(width as f32, height as f32,
+                  pt_to_px(font_size,
+                           CURRENT_DEVICE.dpi));
+        self.prerenderer.invalidate();
+        let ratio = doc.pages_count() as f32 / self.pages_count as f32;
+        let last_page = doc.pages_count().saturating_sub(1);
+        let rescale = |page: usize| ((page as f32 * ratio) as usize).min(last_page);
+        self.current_page = rescale(self.current_page);
+        for page in self.history.iter_mut().chain(self.forward_history.iter_mut()) {
+            *page = rescale(*page);
+        }
+
+        if let Some(ref mut r) = self.info.reader {
+            r.rescale_pages(doc.pages_count());
+        }
+
+        self.pages_count = doc.pages_count();
+    }
+
     fn add_remove_bookmark(&mut self, hub: &Hub) {
+        let excerpt = self.page_excerpt();
         if let Some(ref mut r) = self.info.reader {
-            if !r.bookmarks.insert(self.current_page) {
-                r.bookmarks.remove(&self.current_page);
-            }
+            r.toggle_bookmark(excerpt);
         }
         self.update(hub);
     }
 
+    // The first words of the current page.
+    fn page_excerpt(&self) -> String {
+        let words = self.doc.lock().unwrap().words(self.current_page).unwrap_or_default();
+        let mut excerpt = String::new();
+        for word in words {
+            if excerpt.chars().count() >= EXCERPT_LENGTH {
+                excerpt.push('…');
+                break;
+            }
+            if !excerpt.is_empty() {
+                excerpt.push(' ');
+            }
+            excerpt.push_str(&word.text);
+        }
+        excerpt
+    }
+
+    fn rename_bookmark(&mut self, page: usize, label: &str, hub: &Hub) {
+        if let Some(ref mut r) = self.info.reader {
+            if let Some(index) = r.bookmark_index(page) {
+                r.bookmarks[index].label = label.to_string();
+            }
+        }
+        self.update_bookmarks_list(hub);
+    }
+
+    fn remove_bookmark(&mut self, page: usize, hub: &Hub) {
+        if let Some(ref mut r) = self.info.reader {
+            if let Some(index) = r.bookmark_index(page) {
+                r.bookmarks.remove(index);
+            }
+        }
+        self.update_bookmarks_list(hub);
+    }
+
     fn crop_margins(&mut self, index: usize, margin: &Margin, hub: &Hub) {
         self.info.reader.as_mut().map(|r| {
             if r.cropping_margins.is_none() {
@@ -997,15 +1672,638 @@ impl Reader {
         self.update(hub);
     }
 
-    fn reseed(&mut self, hub: &Hub, context: &mut Context) {
-        let (tx, _rx) = mpsc::channel();
-        if let Some(index) = locate::<TopBar>(self) {
-            self.child_mut(index).downcast_mut::<TopBar>().unwrap()
-                .update_frontlight_icon(&tx, context);
-            hub.send(Event::ClockTick).unwrap();
-            hub.send(Event::BatteryTick).unwrap();
-        }
+    // Computes the margins of the pages surrounding the current one and crops them accordingly.
+    fn auto_crop(&mut self, scheme: PageScheme, hub: &Hub, context: &mut Context) {
+        let margins: Vec<(usize, Margin)> = {
+            let doc = self.doc.lock().unwrap();
+            let end = (self.current_page + AUTO_CROP_PAGES).min(self.pages_count);
+            let start = end.saturating_sub(AUTO_CROP_PAGES);
+            (start..end).filter_map(|index| content_margin(doc.as_ref(), index).map(|m| (index, m)))
+                        .collect()
+        };
+
+        let merge = |parity: Option<usize>| {
+            let margins: Vec<Margin> = margins.iter()
+                                              .filter(|&&(index, _)| parity.map_or(true, |p| index % 2 == p))
+                                              .map(|&(_, ref m)| m.clone())
+                                              .collect();
+            merge_margins(&margins)
+        };
+
+        let cropping_margins = match scheme {
+            PageScheme::Any => merge(None).map(CroppingMargins::Any),
+            PageScheme::EvenOdd => {
+                match (merge(Some(0)), merge(Some(1))) {
+                    (Some(even), Some(odd)) => Some(CroppingMargins::EvenOdd([even, odd])),
+                    (even, odd) => even.or(odd).map(CroppingMargins::Any),
+                }
+            },
+        };
+
+        if cropping_margins.is_none() {
+            let notif = Notification::new(ViewId::AutoCropNotif,
+                                          "No content found.".to_string(),
+                                          &mut context.notification_index,
+                                          &mut context.fonts,
+                                          hub);
+            self.children.push(Box::new(notif) as Box<View>);
+            return;
+        }
+
+        self.info.reader.as_mut().map(|r| r.cropping_margins = cropping_margins);
+        self.update(hub);
+    }
+
+    #[inline]
+    fn two_pages(&self) -> bool {
+        self.spread && self.zoom_mode == ZoomMode::FitToPage
+    }
+
+    // The area in which a single page is fitted.
+    fn page_rect(&self) -> Rectangle {
+        if self.two_pages() {
+            left_half(&self.rect)
+        } else {
+            self.rect
+        }
+    }
+
+    // The position, on the screen, of the top left corner of the frame.
+    fn origin(&self) -> Point {
+        let dx = self.rect.width() as i32 - self.frame.width() as i32;
+        let dy = self.rect.height() as i32 - self.frame.height() as i32;
+        if self.two_pages() {
+            // The two pages meet in the middle of the screen.
+            let x = self.rect.width() as i32 / 2 - self.frame.width() as i32;
+            self.rect.min + pt!(x, dy / 2)
+        } else if self.zoom_mode == ZoomMode::FitToPage {
+            self.rect.min + pt!(dx / 2, dy / 2)
+        } else {
+            let x = if dx < 0 { -self.offset.x.min(-dx) } else { dx / 2 };
+            self.rect.min + pt!(x, -self.offset.y)
+        }
+    }
+
+    fn screen_rect(&self, rect: &Rectangle) -> Rectangle {
+        let origin = self.origin();
+        let x_min = rect.min.x as f32 * self.scale;
+        let y_min = rect.min.y as f32 * self.scale;
+        let x_max = rect.max.x as f32 * self.scale;
+        let y_max = rect.max.y as f32 * self.scale;
+        rect![x_min as i32 - self.frame.min.x + origin.x,
+              y_min as i32 - self.frame.min.y + origin.y,
+              x_max as i32 - self.frame.min.x + origin.x,
+              y_max as i32 - self.frame.min.y + origin.y]
+    }
+
+    fn word_at(&self, pt: &Point, nearest: bool) -> Option<usize> {
+        let mut index = None;
+        let mut dmin = u32::max_value();
+        for (i, word) in self.words.iter().enumerate() {
+            let rect = self.screen_rect(&word.rect);
+            if rect.includes(pt) {
+                return Some(i);
+            }
+            if nearest {
+                let d = rect.center().dist2(pt);
+                if d < dmin {
+                    index = Some(i);
+                    dmin = d;
+                }
+            }
+        }
+        index
+    }
+
+    fn annotation_at(&self, pt: &Point) -> Option<usize> {
+        let current_page = self.current_page;
+        let words = &self.words;
+        self.info.reader.as_ref().and_then(|r| {
+            r.annotations.iter().position(|a| {
+                a.page == current_page && is_located(words, a) &&
+                words[a.start..a.end+1].iter().any(|w| self.screen_rect(&w.rect).includes(pt))
+            })
+        })
+    }
+
+    fn words_rect(&self, start: usize, end: usize) -> Rectangle {
+        let mut rect = self.screen_rect(&self.words[start].rect);
+        for word in &self.words[start+1..end+1] {
+            rect.absorb(&self.screen_rect(&word.rect));
+        }
+        rect
+    }
+
+    fn start_selection(&mut self, pt: &Point, hub: &Hub) -> bool {
+        if self.ephemeral {
+            return false;
+        }
+
+        if self.words.is_empty() {
+            let current_page = self.current_page;
+            self.words = self.doc.lock().ok()
+                             .and_then(|doc| doc.words(current_page))
+                             .unwrap_or_default();
+        }
+
+        if let Some(index) = self.word_at(pt, false) {
+            self.selection = Some(Selection {
+                anchor: index,
+                start: index,
+                end: index,
+                active: true,
+                extended: false,
+            });
+            hub.send(Event::Render(self.rect, UpdateMode::Fast)).unwrap();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn extend_selection(&mut self, pt: &Point, hub: &Hub) {
+        if let Some(index) = self.word_at(pt, true) {
+            if let Some(ref mut sel) = self.selection {
+                let (start, end) = if index < sel.anchor {
+                    (index, sel.anchor)
+                } else {
+                    (sel.anchor, index)
+                };
+                if start == sel.start && end == sel.end {
+                    return;
+                }
+                sel.start = start;
+                sel.end = end;
+                sel.extended = true;
+            }
+            hub.send(Event::Render(self.rect, UpdateMode::Fast)).unwrap();
+        }
+    }
+
+    fn clear_selection(&mut self, hub: &Hub) {
+        if self.selection.take().is_some() {
+            hub.send(Event::Render(self.rect, UpdateMode::Gui)).unwrap();
+        }
+    }
+
+    fn highlight_selection(&mut self, hub: &Hub) -> Option<usize> {
+        let current_page = self.current_page;
+        let index = self.selection.take().and_then(|sel| {
+            let text = words_text(&self.words[sel.start..sel.end+1]);
+            self.info.reader.as_mut().map(|r| {
+                r.add_annotation(Annotation {
+                    page: current_page,
+                    start: sel.start,
+                    end: sel.end,
+                    text,
+                    note: String::new(),
+                    modified: Local::now(),
+                })
+            })
+        });
         hub.send(Event::Render(self.rect, UpdateMode::Gui)).unwrap();
+        index
+    }
+
+    fn remove_annotation(&mut self, index: usize, hub: &Hub) {
+        if let Some(ref mut r) = self.info.reader {
+            if index < r.annotations.len() {
+                r.annotations.remove(index);
+            }
+        }
+        hub.send(Event::Render(self.rect, UpdateMode::Gui)).unwrap();
+    }
+
+    fn toggle_annotation_menu(&mut self, rect: Rectangle, annot_index: Option<usize>, enable: Option<bool>, hub: &Hub, fonts: &mut Fonts) {
+        if let Some(index) = locate_by_id(self, ViewId::AnnotationMenu) {
+            if let Some(true) = enable {
+                return;
+            }
+
+            hub.send(Event::Expose(*self.child(index).rect())).unwrap();
+            self.children.remove(index);
+        } else {
+            if let Some(false) = enable {
+                return;
+            }
+
+            let entries = if let Some(index) = annot_index {
+                vec![EntryKind::Command("Edit Note".to_string(),
+                                        EntryId::EditAnnotationNote(index)),
+                     EntryKind::Command("Remove".to_string(),
+                                        EntryId::RemoveAnnotation(index))]
+            } else {
+                vec![EntryKind::Command("Highlight".to_string(),
+                                        EntryId::HighlightSelection),
+                     EntryKind::Command("Add Note".to_string(),
+                                        EntryId::AnnotateSelection)]
+            };
+
+            let annotation_menu = Menu::new(rect, ViewId::AnnotationMenu, MenuKind::Contextual, entries, fonts);
+            hub.send(Event::Render(*annotation_menu.rect(), UpdateMode::Gui)).unwrap();
+            self.children.push(Box::new(annotation_menu) as Box<View>);
+        }
+    }
+
+    fn toggle_dictionary(&mut self, word: &str, rect: Rectangle, enable: Option<bool>, hub: &Hub, context: &mut Context) {
+        if let Some(index) = locate_by_id(self, ViewId::Dictionary) {
+            if let Some(true) = enable {
+                return;
+            }
+
+            hub.send(Event::Expose(*self.child(index).rect())).unwrap();
+            self.children.remove(index);
+        } else {
+            if let Some(false) = enable {
+                return;
+            }
+
+            let dictionary_window = dictionary_window(word, &rect, hub, context);
+            self.children.push(Box::new(dictionary_window) as Box<View>);
+        }
+    }
+
+    fn toggle_footnote(&mut self, text: &str, rect: Rectangle, enable: Option<bool>, hub: &Hub, context: &mut Context) {
+        if let Some(index) = locate_by_id(self, ViewId::Footnote) {
+            if let Some(true) = enable {
+                return;
+            }
+
+            hub.send(Event::Expose(*self.child(index).rect())).unwrap();
+            self.children.remove(index);
+        } else {
+            if let Some(false) = enable {
+                return;
+            }
+
+            let footnote_window = footnote_window(text, &rect, hub, context);
+            self.children.push(Box::new(footnote_window) as Box<View>);
+        }
+    }
+
+    fn toggle_thumbnails(&mut self, enable: Option<bool>, hub: &Hub, context: &mut Context) {
+        if let Some(index) = locate_by_id(self, ViewId::Thumbnails) {
+            if let Some(true) = enable {
+                return;
+            }
+
+            hub.send(Event::Expose(*self.child(index).rect())).unwrap();
+            self.children.remove(index);
+        } else {
+            if let Some(false) = enable {
+                return;
+            }
+
+            let path = context.settings.library_path.join(&self.info.file.path);
+            let bookmarks = self.info.reader.as_ref()
+                                .map(|r| r.bookmarks.iter().map(|b| b.page).collect())
+                                .unwrap_or_default();
+            let layout = if self.doc.lock().unwrap().is_reflowable() {
+                let font_size = self.info.reader.as_ref()
+                                    .and_then(|r| r.font_size)
+                                    .unwrap_or(DEFAULT_FONT_SIZE);
+                Some((font_size, book_typography(self.info.reader.as_ref(), &context.settings.reader.font_path)))
+            } else {
+                None
+            };
+            let thumbnail_grid = ThumbnailGrid::new(&self.doc, &path, layout, self.current_page,
+                                                    bookmarks, hub, context);
+            self.children.push(Box::new(thumbnail_grid) as Box<View>);
+        }
+    }
+
+    fn toggle_bookmarks_list(&mut self, enable: Option<bool>, hub: &Hub, context: &mut Context) {
+        if let Some(index) = locate_by_id(self, ViewId::Bookmarks) {
+            if let Some(true) = enable {
+                return;
+            }
+
+            hub.send(Event::Expose(*self.child(index).rect())).unwrap();
+            self.children.remove(index);
+        } else {
+            if let Some(false) = enable {
+                return;
+            }
+
+            let bookmarks = match self.info.reader {
+                Some(ref r) if !r.bookmarks.is_empty() => r.bookmarks.clone(),
+                _ => return,
+            };
+            let toc = self.doc.lock().unwrap().toc();
+            let bookmarks_list = ListWindow::new(ViewId::Bookmarks, "Bookmarks", bookmarks, toc, hub, context);
+            self.children.push(Box::new(bookmarks_list) as Box<View>);
+        }
+    }
+
+    fn update_bookmarks_list(&mut self, hub: &Hub) {
+        let bookmarks = self.info.reader.as_ref()
+                            .map(|r| r.bookmarks.clone())
+                            .unwrap_or_default();
+        if let Some(index) = locate_by_id(self, ViewId::Bookmarks) {
+            if bookmarks.is_empty() {
+                hub.send(Event::Expose(*self.child(index).rect())).unwrap();
+                self.children.remove(index);
+            } else if let Some(bookmarks_list) = self.child_mut(index).downcast_mut::<ListWindow<Bookmark>>() {
+                bookmarks_list.update(bookmarks, hub);
+            }
+        }
+    }
+
+    fn toggle_bookmark_menu(&mut self, rect: Rectangle, page: usize, enable: Option<bool>, hub: &Hub, fonts: &mut Fonts) {
+        if let Some(index) = locate_by_id(self, ViewId::BookmarkMenu) {
+            if let Some(true) = enable {
+                return;
+            }
+
+            hub.send(Event::Expose(*self.child(index).rect())).unwrap();
+            self.children.remove(index);
+        } else {
+            if let Some(false) = enable {
+                return;
+            }
+
+            let entries = vec![EntryKind::Command("Rename".to_string(),
+                                                  EntryId::RenameBookmark(page)),
+                               EntryKind::Command("Remove".to_string(),
+                                                  EntryId::RemoveBookmark(page))];
+            let bookmark_menu = Menu::new(rect, ViewId::BookmarkMenu, MenuKind::Contextual, entries, fonts);
+            hub.send(Event::Render(*bookmark_menu.rect(), UpdateMode::Gui)).unwrap();
+            self.children.push(Box::new(bookmark_menu) as Box<View>);
+        }
+    }
+
+    fn toggle_results_list(&mut self, enable: Option<bool>, hub: &Hub, context: &mut Context) {
+        if let Some(index) = locate_by_id(self, ViewId::SearchResults) {
+            if let Some(true) = enable {
+                return;
+            }
+
+            hub.send(Event::Expose(*self.child(index).rect())).unwrap();
+            self.children.remove(index);
+        } else {
+            if let Some(false) = enable {
+                return;
+            }
+
+            let (query, results) = match self.search {
+                Some(ref s) => (s.query.clone(), s.results.clone()),
+                None => return,
+            };
+            let toc = self.doc.lock().unwrap().toc();
+            let results_list = ListWindow::new(ViewId::SearchResults, &query, results, toc, hub, context);
+            self.children.push(Box::new(results_list) as Box<View>);
+        }
+    }
+
+    // Footnotes are shown in a window, the other internal links are followed,
+    // and the external ones, that can't be opened, are displayed.
+    fn follow_link(&mut self, link: &Link, rect: Rectangle, hub: &Hub, context: &mut Context) {
+        let re = Regex::new(r"^([#@])(\d+)(?:,\d+,\d+)?$").unwrap();
+        if let Some(caps) = re.captures(&link.uri) {
+            if let Ok(index) = caps[2].parse::<usize>() {
+                if &caps[1] == "@" {
+                    hub.send(Event::Back).unwrap();
+                    hub.send(Event::GoTo(index)).unwrap();
+                } else {
+                    let note = self.doc.lock().ok()
+                                   .and_then(|doc| doc.footnote(self.current_page, link));
+                    if let Some(text) = note {
+                        self.toggle_footnote(&text, rect, Some(true), hub, context);
+                    } else {
+                        self.go_to_page(index.saturating_sub(1), true, hub);
+                    }
+                }
+            }
+        } else if is_external(&link.uri) {
+            let notif = Notification::new(ViewId::LinkNotif,
+                                          link.uri.clone(),
+                                          &mut context.notification_index,
+                                          &mut context.fonts,
+                                          hub);
+            self.children.push(Box::new(notif) as Box<View>);
+        } else {
+            println!("Unrecognized URI: {}.", link.uri);
+        }
+    }
+
+    fn toggle_dictionary_menu(&mut self, rect: Rectangle, enable: Option<bool>, hub: &Hub, context: &mut Context) {
+        if let Some(index) = locate_by_id(self, ViewId::DictionaryMenu) {
+            if let Some(true) = enable {
+                return;
+            }
+
+            hub.send(Event::Expose(*self.child(index).rect())).unwrap();
+            self.children.remove(index);
+        } else {
+            if let Some(false) = enable {
+                return;
+            }
+
+            let selected = context.settings.reader.dictionary.clone();
+            let mut entries = vec![EntryKind::RadioButton("All Dictionaries".to_string(),
+                                                          EntryId::Dictionary(None),
+                                                          selected.is_none()),
+                                   EntryKind::Separator];
+
+            for dictionary in &context.dictionaries {
+                let name = dictionary.name().to_string();
+                let active = selected.as_ref() == Some(&name);
+                entries.push(EntryKind::RadioButton(name.clone(),
+                                                    EntryId::Dictionary(Some(name)),
+                                                    active));
+            }
+
+            let dictionary_menu = Menu::new(rect, ViewId::DictionaryMenu, MenuKind::DropDown, entries, &mut context.fonts);
+            hub.send(Event::Render(*dictionary_menu.rect(), UpdateMode::Gui)).unwrap();
+            self.children.push(Box::new(dictionary_menu) as Box<View>);
+        }
+    }
+
+    fn toggle_edit_note(&mut self, annot_index: Option<usize>, enable: Option<bool>, hub: &Hub, fonts: &mut Fonts) {
+        if let Some(index) = locate_by_id(self, ViewId::EditNote) {
+            if let Some(true) = enable {
+                return;
+            }
+
+            hub.send(Event::Expose(*self.child(index).rect())).unwrap();
+            self.children.remove(index);
+
+            if self.focus.map(|focus_id| focus_id == ViewId::EditNoteInput).unwrap_or(false) {
+                self.toggle_keyboard(false, None, hub);
+                hub.send(Event::Focus(None)).unwrap();
+            }
+        } else {
+            if let Some(false) = enable {
+                return;
+            }
+
+            let note = annot_index.and_then(|index| {
+                self.info.reader.as_ref()
+                    .and_then(|r| r.annotations.get(index))
+                    .map(|a| a.note.clone())
+            });
+
+            if note.is_none() {
+                return;
+            }
+
+            let mut edit_note = NamedInput::new("Note".to_string(), ViewId::EditNote,
+                                                ViewId::EditNoteInput, 32, fonts);
+            edit_note.set_text(&note.unwrap(), hub);
+            hub.send(Event::Render(*edit_note.rect(), UpdateMode::Gui)).unwrap();
+            hub.send(Event::Focus(Some(ViewId::EditNoteInput))).unwrap();
+
+            self.edited_annotation = annot_index;
+            self.focus = Some(ViewId::EditNoteInput);
+            self.children.push(Box::new(edit_note) as Box<View>);
+        }
+    }
+
+    fn toggle_rename_bookmark(&mut self, page: Option<usize>, enable: Option<bool>, hub: &Hub, fonts: &mut Fonts) {
+        if let Some(index) = locate_by_id(self, ViewId::RenameBookmark) {
+            if let Some(true) = enable {
+                return;
+            }
+
+            hub.send(Event::Expose(*self.child(index).rect())).unwrap();
+            self.children.remove(index);
+
+            if self.focus.map(|focus_id| focus_id == ViewId::RenameBookmarkInput).unwrap_or(false) {
+                self.toggle_keyboard(false, None, hub);
+                hub.send(Event::Focus(None)).unwrap();
+            }
+        } else {
+            if let Some(false) = enable {
+                return;
+            }
+
+            let label = page.and_then(|page| {
+                self.info.reader.as_ref().and_then(|r| {
+                    r.bookmark_index(page).map(|index| r.bookmarks[index].label.clone())
+                })
+            });
+
+            if label.is_none() {
+                return;
+            }
+
+            let mut rename_bookmark = NamedInput::new("Label".to_string(), ViewId::RenameBookmark,
+                                                      ViewId::RenameBookmarkInput, 32, fonts);
+            rename_bookmark.set_text(&label.unwrap(), hub);
+            hub.send(Event::Render(*rename_bookmark.rect(), UpdateMode::Gui)).unwrap();
+            hub.send(Event::Focus(Some(ViewId::RenameBookmarkInput))).unwrap();
+
+            self.renamed_bookmark = page;
+            self.focus = Some(ViewId::RenameBookmarkInput);
+            self.children.push(Box::new(rename_bookmark) as Box<View>);
+        }
+    }
+
+    fn reseed(&mut self, hub: &Hub, context: &mut Context) {
+        let (tx, _rx) = mpsc::channel();
+        if let Some(index) = locate::<TopBar>(self) {
+            self.child_mut(index).downcast_mut::<TopBar>().unwrap()
+                .update_frontlight_icon(&tx, context);
+            hub.send(Event::ClockTick).unwrap();
+            hub.send(Event::BatteryTick).unwrap();
+        }
+        if !self.ephemeral && self.session.is_none() {
+            self.session = Some(SessionTracker::new(self.current_page));
+        }
+        hub.send(Event::Render(self.rect, UpdateMode::Gui)).unwrap();
+    }
+
+    // Returns whether an action was performed.
+    fn perform_action(&mut self, action: GestureAction, hub: &Hub, context: &mut Context) -> bool {
+        match action {
+            GestureAction::None => return false,
+            GestureAction::NextPage => {
+                if self.search.is_none() {
+                    self.set_current_page(CycleDir::Next, hub, context);
+                } else {
+                    self.set_current_results_page(CycleDir::Next, hub);
+                }
+            },
+            GestureAction::PreviousPage => {
+                if self.search.is_none() {
+                    self.set_current_page(CycleDir::Previous, hub, context);
+                } else {
+                    self.set_current_results_page(CycleDir::Previous, hub);
+                }
+            },
+            GestureAction::NextChapter => {
+                if self.search.is_none() {
+                    self.go_to_chapter(CycleDir::Next, hub);
+                } else {
+                    let last_page = self.search.as_ref().unwrap().highlights.len().saturating_sub(1);
+                    self.go_to_results_page(last_page, hub);
+                }
+            },
+            GestureAction::PreviousChapter => {
+                if self.search.is_none() {
+                    self.go_to_chapter(CycleDir::Previous, hub);
+                } else {
+                    self.go_to_results_page(0, hub);
+                }
+            },
+            GestureAction::ToggleBookmark => self.add_remove_bookmark(hub),
+            GestureAction::NextBookmark => self.go_to_bookmark(CycleDir::Next, hub),
+            GestureAction::PreviousBookmark => self.go_to_bookmark(CycleDir::Previous, hub),
+            GestureAction::PreviousLocation => self.go_to_last_page(hub),
+            GestureAction::NextLocation => self.go_to_next_location(hub),
+            GestureAction::TableOfContents => {
+                if self.search.is_some() {
+                    self.set_current_page(CycleDir::Previous, hub, context);
+                } else if self.ephemeral {
+                    hub.send(Event::Back).unwrap();
+                } else {
+                    hub.send(Event::Show(ViewId::TableOfContents)).unwrap();
+                }
+            },
+            GestureAction::GoToPage => hub.send(Event::Toggle(ViewId::GoToPage)).unwrap(),
+            GestureAction::Thumbnails => hub.send(Event::Toggle(ViewId::Thumbnails)).unwrap(),
+            GestureAction::Bookmarks => hub.send(Event::Toggle(ViewId::Bookmarks)).unwrap(),
+            GestureAction::Search => hub.send(Event::Show(ViewId::SearchBar)).unwrap(),
+            GestureAction::ToggleBars => self.toggle_bars(None, hub, context),
+            GestureAction::Frontlight => {
+                if context.settings.frontlight_presets.len() > 1 {
+                    if context.settings.frontlight {
+                        let lightsensor_level = if CURRENT_DEVICE.has_lightsensor() {
+                            context.lightsensor.level().ok()
+                        } else {
+                            None
+                        };
+                        if let Some(ref frontlight_levels) = guess_frontlight(lightsensor_level, &context.settings.frontlight_presets) {
+                            let LightLevels { intensity, warmth } = *frontlight_levels;
+                            context.frontlight.set_intensity(intensity);
+                            context.frontlight.set_warmth(warmth);
+                        }
+                    }
+                } else {
+                    hub.send(Event::ToggleFrontlight).unwrap();
+                }
+            },
+            GestureAction::Invert => hub.send(Event::Select(EntryId::ToggleInverted)).unwrap(),
+            GestureAction::Refresh => hub.send(Event::Render(self.rect, UpdateMode::Full)).unwrap(),
+            GestureAction::Screenshot => hub.send(Event::Select(EntryId::TakeScreenshot)).unwrap(),
+            GestureAction::Back => {
+                if self.ephemeral {
+                    hub.send(Event::Back).unwrap();
+                } else {
+                    self.exit(hub, context);
+                }
+            },
+            GestureAction::ZoomIn => {
+                let center = self.rect.center();
+                self.zoom(ZOOM_STEP, center, hub, context);
+            },
+            GestureAction::ZoomOut => {
+                let center = self.rect.center();
+                self.zoom(1.0 / ZOOM_STEP, center, hub, context);
+            },
+            GestureAction::ResetZoom => self.reset_zoom(hub, context),
+        }
+        true
     }
 
     fn exit(&mut self, hub: &Hub, context: &mut Context){
@@ -1013,6 +2311,32 @@ impl Reader {
         hub.send(Event::Back).unwrap();
     }
 
+    fn save_session(&mut self, context: &Context) {
+        if let Some(session) = self.session.take().and_then(SessionTracker::finish) {
+            record_session(&context.settings.library_path, &self.info.file.path, session)
+                .map_err(|e| eprintln!("Can't save reading session: {}", e))
+                .ok();
+        }
+    }
+
+    // Gathers the figures shown by the statistics view.
+    fn statistics_report(&self, context: &Context) -> Report {
+        let current_page = self.current_page;
+        let chapter = {
+            let doc = self.doc.lock().unwrap();
+            doc.toc().and_then(|toc| {
+                chapter_at(&toc, current_page).map(|chap| {
+                    let end = chapter_relative(&toc, current_page, CycleDir::Next)
+                                  .unwrap_or(self.pages_count);
+                    (chap.title.clone(), end.saturating_sub(current_page))
+                })
+            })
+        };
+        let statistics = load_statistics(&context.settings.library_path);
+        statistics.report(&self.info.file.path, &self.info.title, chapter,
+                          self.pages_count.saturating_sub(current_page))
+    }
+
     fn quit(&mut self, context: &mut Context) {
         if let Some(ref mut s) = self.search {
             s.running.store(false, Ordering::Relaxed);
@@ -1026,8 +2350,12 @@ impl Reader {
             r.current_page = self.current_page;
             r.pages_count = self.pages_count;
             r.finished = self.finished;
+            r.history = self.history.iter().cloned().collect();
+            r.forward_history = self.forward_history.clone();
         }
 
+        self.save_session(context);
+
         for i in &mut context.metadata {
             if i.file.path == self.info.file.path {
                 *i = self.info.clone();
@@ -1049,100 +2377,85 @@ impl View for Reader {
                 };
                 true
             },
-            Event::Gesture(GestureEvent::Swipe { dir, ref start, .. }) if self.rect.includes(start) => {
+            Event::Gesture(GestureEvent::Swipe { dir, ref start, ref end }) if self.rect.includes(start) => {
+                let is_panable = self.zoom_mode != ZoomMode::FitToPage &&
+                                 self.frame.width() > self.rect.width();
+                let is_zoomed = match self.zoom_mode {
+                    ZoomMode::Custom(_) => true,
+                    _ => false,
+                };
                 match dir {
-                    Dir::West => self.set_current_page(CycleDir::Next, hub, context),
-                    Dir::East => self.set_current_page(CycleDir::Previous, hub, context),
-                    _ => (),
+                    // Zoomed pages follow the finger in both directions.
+                    _ if is_zoomed && (is_panable || dir.axis() == Axis::Vertical) => {
+                        self.horizontal_scroll(start.x - end.x, hub);
+                        self.vertical_scroll(start.y - end.y, hub, context);
+                    },
+                    Dir::West | Dir::East if is_panable => self.horizontal_scroll(start.x - end.x, hub),
+                    Dir::North | Dir::South if self.zoom_mode != ZoomMode::FitToPage => {
+                        self.vertical_scroll(start.y - end.y, hub, context);
+                    },
+                    _ => {
+                        let action = context.settings.reader.gestures.swipe_action(dir);
+                        self.perform_action(action, hub, context);
+                    },
                 };
                 true
             },
+            Event::Gesture(GestureEvent::MultiSwipe { dir, ref starts, .. }) if self.rect.includes(&starts[0]) => {
+                let action = context.settings.reader.gestures.multi_swipe_action(dir);
+                self.perform_action(action, hub, context)
+            },
+            Event::Gesture(GestureEvent::Pinch { ref starts, ref ends, .. }) if self.rect.includes(&starts[0]) => {
+                let action = context.settings.reader.gestures.pinch_action();
+                self.pinch_zoom(action, starts, ends, hub, context)
+            },
+            Event::Gesture(GestureEvent::Spread { ref starts, ref ends, .. }) if self.rect.includes(&starts[0]) => {
+                let action = context.settings.reader.gestures.spread_action();
+                self.pinch_zoom(action, starts, ends, hub, context)
+            },
+            Event::Gesture(GestureEvent::MultiTap(ref points)) if self.rect.includes(&points[0]) => {
+                let action = context.settings.reader.gestures.multi_tap_action();
+                self.perform_action(action, hub, context)
+            },
+            Event::Gesture(GestureEvent::Rotate { ref center, .. }) if self.rect.includes(center) => {
+                let action = context.settings.reader.gestures.rotate_action();
+                self.perform_action(action, hub, context)
+            },
+            Event::Gesture(GestureEvent::HoldButton(code)) => {
+                let action = context.settings.reader.gestures.hold_button_action(code);
+                self.perform_action(action, hub, context)
+            },
             Event::Gesture(GestureEvent::Tap(ref center)) if self.rect.includes(center) => {
                 if self.focus.is_some() {
                     return true;
                 }
 
-                let dx = (self.rect.width() - self.frame.width()) as i32 / 2;
-                let dy = (self.rect.height() - self.frame.height()) as i32 / 2;
+                if let Some(index) = self.annotation_at(center) {
+                    let rect = {
+                        let annot = &self.info.reader.as_ref().unwrap().annotations[index];
+                        self.words_rect(annot.start, annot.end)
+                    };
+                    self.toggle_annotation_menu(rect, Some(index), None, hub, &mut context.fonts);
+                    return true;
+                }
 
                 let links = &self.doc.lock().ok()
                                  .and_then(|d| d.links(self.current_page))
                                  .unwrap_or_default();
 
                 for link in links {
-                    let r = link.rect;
-                    let x_min = r.min.x as f32 * self.scale;
-                    let y_min = r.min.y as f32 * self.scale;
-                    let x_max = r.max.x as f32 * self.scale;
-                    let y_max = r.max.y as f32 * self.scale;
-                    let rect = rect![x_min as i32 - self.frame.min.x + dx,
-                                     y_min as i32 - self.frame.min.y + dy,
-                                     x_max as i32 - self.frame.min.x + dx,
-                                     y_max as i32 - self.frame.min.y + dy];
+                    let rect = self.screen_rect(&link.rect);
 
                     if rect.includes(center) {
-                        let re = Regex::new(r"^([#@])(\d+)(?:,\d+,\d+)?$").unwrap();
-                        if let Some(caps) = re.captures(&link.uri) {
-                            if let Ok(index) = caps[2].parse::<usize>() {
-                                if &caps[1] == "@" {
-                                    hub.send(Event::Back).unwrap();
-                                    hub.send(Event::GoTo(index)).unwrap();
-                                } else {
-                                    self.go_to_page(index.saturating_sub(1), true, hub);
-                                }
-                            }
-                        } else {
-                            println!("Unrecognized URI: {}.", link.uri);
-                        }
+                        self.follow_link(link, rect, hub, context);
                         return true;
                     }
                 }
 
-                let w = self.rect.width() as i32;
-                let x1 = self.rect.min.x + w / 5;
-                let x2 = self.rect.max.x - w / 5;
-
-                if center.x < x1 {
-                    let dx = x1 - center.x;
-                    // Top left corner.
-                    if center.y < self.rect.min.y + dx {
-                        self.go_to_last_page(hub);
-                    // Bottom left corner.
-                    } else if center.y > self.rect.max.y - dx {
-                        if self.search.is_none() {
-                            if self.ephemeral {
-                                hub.send(Event::Back).unwrap();
-                            } else {
-                                hub.send(Event::Show(ViewId::TableOfContents)).unwrap();
-                            }
-                        } else {
-                            self.set_current_page(CycleDir::Previous, hub, context);
-                        }
-                    // Left ear.
-                    } else {
-                        if self.search.is_none() {
-                            self.set_current_page(CycleDir::Previous, hub, context);
-                        } else {
-                            self.set_current_results_page(CycleDir::Previous, hub);
-                        }
-                    }
-                } else if center.x > x2 {
-                    let dx = center.x - x2;
-                    // Top right corner.
-                    if center.y < self.rect.min.y + dx {
-                        self.add_remove_bookmark(hub);
-                    // Right ear 
-                    } else {
-                        if self.search.is_none() {
-                            self.set_current_page(CycleDir::Next, hub, context);
-                        } else {
-                            self.set_current_results_page(CycleDir::Next, hub);
-                        }
-                    }
-                // Middle band.
-                } else {
-                    self.toggle_bars(None, hub, context);
-                }
+                let ear_width = self.rect.width() as i32 / 5;
+                let region = Region::locate(center, &self.rect, ear_width);
+                let action = context.settings.reader.gestures.tap_action(region);
+                self.perform_action(action, hub, context);
 
                 true
             },
@@ -1151,64 +2464,49 @@ impl View for Reader {
                     return true;
                 }
 
-                let w = self.rect.width() as i32;
-                let x1 = self.rect.min.x + w / 3;
-                let x2 = self.rect.max.x - w / 3;
-
-                if center.x < x1 {
-                    let dx = x1 - center.x;
-                    // Top left corner.
-                    if center.y < self.rect.min.y + dx {
-                        self.go_to_bookmark(CycleDir::Previous, hub);
-                    // Bottom left corner.
-                    } else if center.y > self.rect.max.y - dx {
-                        if context.settings.frontlight_presets.len() > 1 {
-                            if context.settings.frontlight {
-                                let lightsensor_level = if CURRENT_DEVICE.has_lightsensor() {
-                                    context.lightsensor.level().ok()
-                                } else {
-                                    None
-                                };
-                                if let Some(ref frontlight_levels) = guess_frontlight(lightsensor_level, &context.settings.frontlight_presets) {
-                                    let LightLevels { intensity, warmth } = *frontlight_levels;
-                                    context.frontlight.set_intensity(intensity);
-                                    context.frontlight.set_warmth(warmth);
-                                }
-                            }
-                        } else {
-                            hub.send(Event::ToggleFrontlight).unwrap();
-                        }
-                    // Left ear.
-                    } else {
-                        if self.search.is_none() {
-                            self.go_to_chapter(CycleDir::Previous, hub);
-                        } else {
-                            self.go_to_results_page(0, hub);
-                        }
-                    }
-                } else if center.x > x2 {
-                    let dx = center.x - x2;
-                    // Top right corner.
-                    if center.y < self.rect.min.y + dx {
-                        self.go_to_bookmark(CycleDir::Next, hub);
-                    // Bottom right corner.
-                    } else if center.y > self.rect.max.y - dx {
-                        hub.send(Event::Select(EntryId::ToggleInverted)).unwrap();
-                    // Right ear.
-                    } else {
-                        if self.search.is_none() {
-                            self.go_to_chapter(CycleDir::Next, hub);
+                let ear_width = self.rect.width() as i32 / 3;
+                let region = Region::locate(center, &self.rect, ear_width);
+
+                // Holding a word in the middle band selects it.
+                if region != Region::MiddleBand || !self.start_selection(center, hub) {
+                    let action = context.settings.reader.gestures.hold_finger_action(region);
+                    self.perform_action(action, hub, context);
+                }
+
+                true
+            },
+            Event::Device(DeviceEvent::Finger { status, ref position, .. }) if self.selection.as_ref().map_or(false, |s| s.active) => {
+                match status {
+                    FingerStatus::Motion => self.extend_selection(position, hub),
+                    FingerStatus::Up => {
+                        self.extend_selection(position, hub);
+                        let (start, end, extended) = {
+                            let sel = self.selection.as_mut().unwrap();
+                            sel.active = false;
+                            (sel.start, sel.end, sel.extended)
+                        };
+                        let rect = self.words_rect(start, end);
+                        // Holding a single word looks it up, dragging selects a passage.
+                        if extended {
+                            self.toggle_annotation_menu(rect, None, Some(true), hub, &mut context.fonts);
                         } else {
-                            let last_page = self.search.as_ref().unwrap().highlights.len() - 1;
-                            self.go_to_results_page(last_page, hub);
+                            self.clear_selection(hub);
+                            let word = self.words[start].text.clone();
+                            self.toggle_dictionary(&word, rect, Some(true), hub, context);
                         }
-                    }
-                } else {
-                    hub.send(Event::Render(self.rect, UpdateMode::Full)).unwrap();
+                    },
+                    _ => (),
                 }
-
                 true
             },
+            Event::Device(DeviceEvent::Finger { status: FingerStatus::Down, ref position, .. }) if self.selection.is_some() => {
+                let inside_menu = locate_by_id(self, ViewId::AnnotationMenu)
+                                      .map_or(false, |index| self.child(index).rect().includes(position));
+                if !inside_menu {
+                    self.clear_selection(hub);
+                }
+                false
+            },
             Event::Submit(ViewId::GoToPageInput, ref text) => {
                 let re = Regex::new(r#"^(")?(\d+)$"#).unwrap();
                 if let Some(caps) = re.captures(text) {
@@ -1224,6 +2522,22 @@ impl View for Reader {
                 }
                 true
             },
+            Event::Submit(ViewId::EditNoteInput, ref text) => {
+                if let Some(index) = self.edited_annotation {
+                    if let Some(annot) = self.info.reader.as_mut().and_then(|r| r.annotations.get_mut(index)) {
+                        annot.note = text.to_string();
+                        annot.modified = Local::now();
+                    }
+                    hub.send(Event::Render(self.rect, UpdateMode::Gui)).unwrap();
+                }
+                true
+            },
+            Event::Submit(ViewId::RenameBookmarkInput, ref text) => {
+                if let Some(page) = self.renamed_bookmark.take() {
+                    self.rename_bookmark(page, text, hub);
+                }
+                true
+            },
             Event::Submit(ViewId::GoToResultsPageInput, ref text) => {
                 if let Ok(index) = text.parse::<usize>() {
                     self.go_to_results_page(index.saturating_sub(1), hub);
@@ -1231,7 +2545,7 @@ impl View for Reader {
                 true
             },
             Event::Submit(ViewId::SearchInput, ref text) => {
-                match make_query(text) {
+                match make_query(text, &self.search_options) {
                     Some(query) => {
                         self.search(text, query, hub);
                         self.toggle_keyboard(false, None, hub);
@@ -1281,6 +2595,22 @@ impl View for Reader {
                 self.toggle_go_to_page(None, ViewId::GoToResultsPage, hub, &mut context.fonts);
                 true
             },
+            Event::Toggle(ViewId::SearchResults) => {
+                self.toggle_results_list(None, hub, context);
+                true
+            },
+            Event::Toggle(ViewId::Bookmarks) => {
+                self.toggle_bookmarks_list(None, hub, context);
+                true
+            },
+            Event::ToggleBookmarkMenu(rect, page) => {
+                self.toggle_bookmark_menu(rect, page, None, hub, &mut context.fonts);
+                true
+            },
+            Event::Toggle(ViewId::Thumbnails) => {
+                self.toggle_thumbnails(None, hub, context);
+                true
+            },
             Event::Slider(SliderId::FontSize, font_size, FingerStatus::Up) => {
                 self.set_font_size(font_size, hub);
                 true
@@ -1293,6 +2623,14 @@ impl View for Reader {
                 self.toggle_margin_cropper_menu(rect, None, hub, &mut context.fonts);
                 true
             },
+            Event::ToggleNear(ViewId::ZoomMenu, rect) => {
+                self.toggle_zoom_menu(rect, None, hub, &mut context.fonts);
+                true
+            },
+            Event::ToggleNear(ViewId::TypographyMenu, rect) => {
+                self.toggle_typography_menu(rect, None, hub, context);
+                true
+            },
             Event::ToggleNear(ViewId::SearchMenu, rect) => {
                 self.toggle_search_menu(rect, None, hub, &mut context.fonts);
                 true
@@ -1301,6 +2639,10 @@ impl View for Reader {
                 self.toggle_page_menu(rect, None, hub, &mut context.fonts);
                 true
             },
+            Event::ToggleNear(ViewId::DictionaryMenu, rect) => {
+                self.toggle_dictionary_menu(rect, None, hub, context);
+                true
+            },
             Event::Close(ViewId::MainMenu) => {
                 toggle_main_menu(self, Rectangle::default(), Some(false), hub, context);
                 true
@@ -1313,6 +2655,16 @@ impl View for Reader {
                 self.toggle_go_to_page(Some(false), ViewId::GoToPage, hub, &mut context.fonts);
                 true
             },
+            Event::Close(ViewId::EditNote) => {
+                self.toggle_edit_note(None, Some(false), hub, &mut context.fonts);
+                true
+            },
+            // The list is closed while the bookmark is renamed: it would cover the keyboard.
+            Event::Close(ViewId::RenameBookmark) => {
+                self.toggle_rename_bookmark(None, Some(false), hub, &mut context.fonts);
+                self.toggle_bookmarks_list(Some(true), hub, context);
+                true
+            },
             Event::Close(ViewId::GoToResultsPage) => {
                 self.toggle_go_to_page(Some(false), ViewId::GoToResultsPage, hub, &mut context.fonts);
                 true
@@ -1339,18 +2691,27 @@ impl View for Reader {
                 self.toggle_margin_cropper(false, hub, context);
                 true
             },
-            Event::SearchResult(index, rect) => {
+            Event::SearchResult(index, ref rects, ref snippet) => {
                 if self.search.is_none() {
                     return true;
                 }
                 let mut results_count = 0;
+                let mut position = 0;
                 if let Some(ref mut s) = self.search {
-                    let pages_count = s.highlights.len();
-                    s.highlights.entry(index).or_insert_with(|| Vec::new()).push(rect);
+                    for (i, page_rects) in rects.iter().enumerate() {
+                        s.highlights.entry(index + i).or_insert_with(Vec::new).push(page_rects.clone());
+                    }
+                    position = s.results.iter().rposition(|r| r.0 <= index).map_or(0, |i| i + 1);
+                    s.results.insert(position, (index, snippet.clone()));
                     s.results_count += 1;
                     results_count = s.results_count;
-                    if results_count > 1 && index <= self.current_page && s.highlights.len() > pages_count {
-                        s.current_page += 1;
+                    if results_count > 1 {
+                        s.current_page = s.highlights.range(..self.current_page+1).count().saturating_sub(1);
+                    }
+                }
+                if let Some(index_list) = locate_by_id(self, ViewId::SearchResults) {
+                    if let Some(results_list) = self.child_mut(index_list).downcast_mut::<ListWindow<(usize, Snippet)>>() {
+                        results_list.insert(position, (index, snippet.clone()), hub);
                     }
                 }
                 self.update_results_bar(hub);
@@ -1386,15 +2747,145 @@ impl View for Reader {
                 });
                 true
             },
+            Event::Select(EntryId::AutoCrop(scheme)) => {
+                self.auto_crop(scheme, hub, context);
+                true
+            },
             Event::Select(EntryId::RemoveCroppings) => {
                 self.info.reader.as_mut().map(|r| r.cropping_margins = None);
                 self.update(hub);
                 true
             },
+            Event::Select(EntryId::FitToPage) => {
+                self.set_zoom_mode(ZoomMode::FitToPage, hub);
+                true
+            },
+            Event::Select(EntryId::FitToWidth) => {
+                self.set_zoom_mode(ZoomMode::FitToWidth, hub);
+                true
+            },
+            Event::Select(EntryId::Zoom(percent)) => {
+                self.set_zoom_mode(ZoomMode::Custom(percent as f32 / 100.0), hub);
+                true
+            },
+            Event::Select(EntryId::FontFamily(ref name)) => {
+                self.set_typography(|r| r.font_family = name.clone(), hub, context);
+                true
+            },
+            Event::Select(EntryId::LineHeight(percent)) => {
+                self.set_typography(|r| r.line_height = percent.map(|p| p as f32 / 100.0), hub, context);
+                true
+            },
+            Event::Select(EntryId::MarginWidth(width)) => {
+                self.set_typography(|r| r.margin_width = width, hub, context);
+                true
+            },
+            Event::Select(EntryId::TextAlign(text_align)) => {
+                self.set_typography(|r| r.text_align = Some(text_align), hub, context);
+                true
+            },
+            Event::Select(EntryId::ToggleHyphenation) => {
+                self.set_typography(|r| r.hyphenation = Some(!r.hyphenation.unwrap_or(true)), hub, context);
+                true
+            },
+            Event::Select(EntryId::ResetTypography) => {
+                self.set_typography(|r| {
+                    r.font_family = None;
+                    r.line_height = None;
+                    r.margin_width = None;
+                    r.text_align = None;
+                    r.hyphenation = None;
+                }, hub, context);
+                true
+            },
+            Event::Select(EntryId::Dictionary(ref name)) => {
+                if let Some(index) = locate_by_id(self, ViewId::Dictionary) {
+                    if let Some(dictionary_window) = self.child_mut(index).downcast_mut::<HtmlWindow>() {
+                        set_dictionary(dictionary_window, name.clone(), hub, context);
+                    }
+                }
+                true
+            },
+            Event::Select(EntryId::HighlightSelection) => {
+                self.highlight_selection(hub);
+                true
+            },
+            Event::Select(EntryId::AnnotateSelection) => {
+                let index = self.highlight_selection(hub);
+                self.toggle_edit_note(index, Some(true), hub, &mut context.fonts);
+                true
+            },
+            Event::Select(EntryId::EditAnnotationNote(index)) => {
+                self.toggle_edit_note(Some(index), Some(true), hub, &mut context.fonts);
+                true
+            },
+            Event::Select(EntryId::RemoveAnnotation(index)) => {
+                self.remove_annotation(index, hub);
+                true
+            },
+            Event::Select(EntryId::ShowAnnotations) => {
+                self.toggle_page_menu(Rectangle::default(), Some(false), hub, &mut context.fonts);
+                self.toggle_bars(Some(false), hub, context);
+                if let Some(ref r) = self.info.reader {
+                    let toc = self.doc.lock().unwrap().toc().unwrap_or_default();
+                    hub.send(Event::OpenAnnotations(r.annotations.clone(), toc)).unwrap();
+                }
+                true
+            },
+            Event::Select(EntryId::ShowBookmarks) => {
+                self.toggle_page_menu(Rectangle::default(), Some(false), hub, &mut context.fonts);
+                self.toggle_bars(Some(false), hub, context);
+                self.toggle_bookmarks_list(Some(true), hub, context);
+                true
+            },
+            Event::Select(EntryId::RenameBookmark(page)) => {
+                self.toggle_bookmarks_list(Some(false), hub, context);
+                self.toggle_rename_bookmark(Some(page), Some(true), hub, &mut context.fonts);
+                true
+            },
+            Event::Select(EntryId::RemoveBookmark(page)) => {
+                self.remove_bookmark(page, hub);
+                true
+            },
+            Event::Select(EntryId::ShowThumbnails) => {
+                self.toggle_page_menu(Rectangle::default(), Some(false), hub, &mut context.fonts);
+                self.toggle_bars(Some(false), hub, context);
+                self.toggle_thumbnails(Some(true), hub, context);
+                true
+            },
+            Event::Select(EntryId::ShowStatistics) => {
+                self.toggle_page_menu(Rectangle::default(), Some(false), hub, &mut context.fonts);
+                self.toggle_bars(Some(false), hub, context);
+                // The ongoing session is included in the statistics.
+                self.save_session(context);
+                let report = self.statistics_report(context);
+                hub.send(Event::OpenStatistics(Box::new(report))).unwrap();
+                true
+            },
             Event::Select(EntryId::SearchDirection(dir)) => {
                 self.search_direction = dir;
                 true
             },
+            Event::Select(EntryId::ToggleCaseSensitive) => {
+                self.search_options.case_sensitive = !self.search_options.case_sensitive;
+                true
+            },
+            Event::Select(EntryId::ToggleWholeWord) => {
+                self.search_options.whole_word = !self.search_options.whole_word;
+                true
+            },
+            Event::Select(EntryId::ToggleRegex) => {
+                self.search_options.regex = !self.search_options.regex;
+                true
+            },
+            Event::Select(EntryId::PreviousLocation) => {
+                self.go_to_last_page(hub);
+                true
+            },
+            Event::Select(EntryId::NextLocation) => {
+                self.go_to_next_location(hub);
+                true
+            },
             Event::Select(EntryId::ToggleFirstPage) => {
                 let current_page = self.current_page;
                 if let Some(ref mut r) = self.info.reader {
@@ -1421,6 +2912,7 @@ impl View for Reader {
                 match v {
                     Some(ViewId::SearchInput) => {
                         self.toggle_results_bar(false, hub);
+                        self.toggle_results_list(Some(false), hub, context);
                         if let Some(ref mut s) = self.search {
                             s.running.store(false, Ordering::Relaxed);
                         }
@@ -1439,26 +2931,71 @@ impl View for Reader {
     }
 
     fn render(&self, fb: &mut Framebuffer, _fonts: &mut Fonts) {
-        let dx = (self.rect.width() - self.frame.width()) as i32 / 2;
-        let dy = (self.rect.height() - self.frame.height()) as i32 / 2;
+        let origin = self.origin();
 
         println!("draw pixmap");
         fb.draw_rectangle(&self.rect, WHITE);
-        fb.draw_framed_pixmap(&self.pixmap, &self.frame, &pt!(dx, dy));
-
-        if let Some(rects) = self.search.as_ref().and_then(|s| s.highlights.get(&self.current_page)) {
-            let dx = (self.rect.width() - self.frame.width()) as i32 / 2;
-            let dy = (self.rect.height() - self.frame.height()) as i32 / 2;
-
-            for r in rects {
-                let x_min = r.min.x as f32 * self.scale;
-                let y_min = r.min.y as f32 * self.scale;
-                let x_max = r.max.x as f32 * self.scale;
-                let y_max = r.max.y as f32 * self.scale;
-                let rect = rect![x_min as i32 - self.frame.min.x + dx,
-                                 y_min as i32 - self.frame.min.y + dy,
-                                 x_max as i32 - self.frame.min.x + dx,
-                                 y_max as i32 - self.frame.min.y + dy];
+        draw_frame(fb, &self.pixmap, &self.frame, origin, &self.rect);
+
+        if self.two_pages() {
+            for &(ref pixmap, ref frame) in &self.next_pages {
+                let x = self.rect.min.x + self.rect.width() as i32 / 2;
+                let dy = self.rect.height() as i32 - frame.height() as i32;
+                draw_frame(fb, pixmap, frame, pt!(x, self.rect.min.y + dy / 2), &self.rect);
+            }
+        } else {
+            let mut y = origin.y + self.frame.height() as i32;
+            for &(ref pixmap, ref frame) in &self.next_pages {
+                let dx = self.rect.width() as i32 - frame.width() as i32;
+                let x = if dx < 0 { self.rect.min.x - self.offset.x.min(-dx) } else { self.rect.min.x + dx / 2 };
+                draw_frame(fb, pixmap, frame, pt!(x, y), &self.rect);
+                y += frame.height() as i32;
+            }
+        }
+
+        if let Some(ref reader_info) = self.info.reader {
+            let dpi = CURRENT_DEVICE.dpi;
+            let thickness = scale_by_dpi(THICKNESS_MEDIUM, dpi) as i32;
+
+            for annot in reader_info.annotations.iter().filter(|a| a.page == self.current_page) {
+                if !is_located(&self.words, annot) {
+                    continue;
+                }
+
+                let rects = line_rects(&self.words[annot.start..annot.end+1]);
+
+                for r in &rects {
+                    let rect = self.screen_rect(r);
+                    if let Some(ref it) = rect.intersection(&fb.rect()) {
+                        fb.draw_blended_rectangle(it, GRAY08, 0.4);
+                    }
+                }
+
+                if !annot.note.is_empty() {
+                    if let Some(r) = rects.last() {
+                        let rect = self.screen_rect(r);
+                        let underline = rect![rect.min.x, rect.max.y,
+                                              rect.max.x, rect.max.y + thickness];
+                        if let Some(ref it) = underline.intersection(&fb.rect()) {
+                            fb.draw_rectangle(it, BLACK);
+                        }
+                    }
+                }
+            }
+        }
+
+        if let Some(ref sel) = self.selection {
+            for r in line_rects(&self.words[sel.start..sel.end+1]) {
+                let rect = self.screen_rect(&r);
+                if let Some(ref it) = rect.intersection(&fb.rect()) {
+                    fb.invert_region(it);
+                }
+            }
+        }
+
+        if let Some(highlights) = self.search.as_ref().and_then(|s| s.highlights.get(&self.current_page)) {
+            for r in highlights.iter().flat_map(|rects| rects.iter()) {
+                let rect = self.screen_rect(r);
 
                 if let Some(ref it) = rect.intersection(&fb.rect()) {
                     fb.invert_region(it);
@@ -1466,7 +3003,7 @@ impl View for Reader {
             }
         }
 
-        if self.info.reader.as_ref().map_or(false, |r| r.bookmarks.contains(&self.current_page)) {
+        if self.info.reader.as_ref().map_or(false, |r| r.bookmark_index(self.current_page).is_some()) {
             let dpi = CURRENT_DEVICE.dpi;
             let thickness = scale_by_dpi(3.0, dpi) as u16;
             let radius = (mm_to_in(0.4) * dpi as f32) as i32 + thickness as i32;
@@ -1483,6 +3020,39 @@ impl View for Reader {
         true
     }
 
+    fn resize(&mut self, rect: Rectangle, _hub: &Hub, context: &mut Context) {
+        if let Some(ref s) = self.search {
+            s.running.store(false, Ordering::Relaxed);
+        }
+
+        self.search = None;
+        self.children.clear();
+        self.focus = None;
+        self.rect = rect;
+        self.offset = pt!(0, 0);
+
+        let reflowable = self.doc.lock().unwrap().is_reflowable();
+
+        if reflowable {
+            let font_size = self.info.reader.as_ref()
+                                .and_then(|r| r.font_size)
+                                .unwrap_or(DEFAULT_FONT_SIZE);
+            self.layout(font_size);
+        }
+
+        if !self.ephemeral {
+            let doc = self.doc.lock().unwrap();
+            self.spread = is_spread(&rect, doc.as_ref(), &context.settings);
+            self.zoom_mode = self.info.reader.as_ref()
+                                 .and_then(|r| r.zoom_mode)
+                                 .unwrap_or_else(|| default_zoom_mode(&rect, &context.settings));
+        }
+
+        // The application renders the whole screen once every view is resized.
+        let (tx, _rx) = mpsc::channel();
+        self.update(&tx);
+    }
+
     fn rect(&self) -> &Rectangle {
         &self.rect
     }
@@ -1500,12 +3070,114 @@ impl View for Reader {
     }
 }
 
-fn build_pixmap(rect: &Rectangle, doc: &Document, index: usize, margin: &Margin) -> (Pixmap, f32) {
+// The zoom mode of the books that don't specify one.
+fn default_zoom_mode(rect: &Rectangle, settings: &Settings) -> ZoomMode {
+    if rect.width() > rect.height() && settings.reader.landscape == LandscapeMode::FitToWidth {
+        ZoomMode::FitToWidth
+    } else {
+        ZoomMode::FitToPage
+    }
+}
+
+fn is_spread(rect: &Rectangle, doc: &Document, settings: &Settings) -> bool {
+    rect.width() > rect.height() &&
+    settings.reader.landscape == LandscapeMode::TwoPages &&
+    !doc.is_reflowable()
+}
+
+fn left_half(rect: &Rectangle) -> Rectangle {
+    rect![rect.min.x, rect.min.y,
+          rect.min.x + rect.width() as i32 / 2, rect.max.y]
+}
+
+fn scaling_factor(rect: &Rectangle, doc: &Document, index: usize, margin: &Margin, zoom_mode: ZoomMode) -> f32 {
     let (width, height) = doc.dims(index).unwrap();
     let p_width = (1.0 - (margin.left + margin.right)) * width;
     let p_height = (1.0 - (margin.top + margin.bottom)) * height;
     let w_ratio = rect.width() as f32 / p_width;
     let h_ratio = rect.height() as f32 / p_height;
-    let scale = w_ratio.min(h_ratio);
+    match zoom_mode {
+        ZoomMode::FitToPage => w_ratio.min(h_ratio),
+        ZoomMode::FitToWidth => w_ratio,
+        ZoomMode::Custom(zoom) => zoom * w_ratio,
+    }
+}
+
+fn build_pixmap(rect: &Rectangle, doc: &Document, index: usize, margin: &Margin, zoom_mode: ZoomMode) -> (Pixmap, f32) {
+    let scale = scaling_factor(rect, doc, index, margin, zoom_mode);
     (doc.pixmap(index, scale).unwrap(), scale)
 }
+
+// The region of the pixmap that remains once the margins are cropped.
+fn frame_rect(pixmap: &Pixmap, margin: &Margin) -> Rectangle {
+    rect![(margin.left * pixmap.width as f32).ceil() as i32,
+          (margin.top * pixmap.height as f32).ceil() as i32,
+          ((1.0 - margin.right) * pixmap.width as f32).floor() as i32,
+          ((1.0 - margin.bottom) * pixmap.height as f32).floor() as i32]
+}
+
+// Estimates the height of the frame of the given page without rendering it.
+fn frame_height(rect: &Rectangle, doc: &Document, index: usize, margin: &Margin, zoom_mode: ZoomMode) -> i32 {
+    let (_, height) = doc.dims(index).unwrap();
+    let scale = scaling_factor(rect, doc, index, margin, zoom_mode);
+    let height = height * scale;
+    ((1.0 - margin.bottom) * height).floor() as i32 - (margin.top * height).ceil() as i32
+}
+
+// Draws the part of *frame* that lies within *clip*, the top left corner of *frame* being at *origin*.
+fn draw_frame(fb: &mut Framebuffer, pixmap: &Pixmap, frame: &Rectangle, origin: Point, clip: &Rectangle) {
+    let rect = rect![origin, origin + pt!(frame.width() as i32, frame.height() as i32)];
+    if let Some(ref visible) = rect.intersection(clip) {
+        let source = *visible - origin + frame.min;
+        fb.draw_framed_pixmap(pixmap, &source, &visible.min);
+    }
+}
+
+// Takes the words of the page *index* from the cache, or extracts them.
+fn page_words(doc: &Document, cache: &mut Option<(usize, Vec<BoundedText>)>, index: usize) -> Option<Vec<BoundedText>> {
+    match cache.take() {
+        Some((i, words)) if i == index => Some(words),
+        _ => doc.words(index),
+    }
+}
+
+// Merges the rectangles of consecutive words that belong to the same line.
+fn line_rects(words: &[BoundedText]) -> Vec<Rectangle> {
+    let mut rects: Vec<Rectangle> = Vec::new();
+    for word in words {
+        if let Some(last) = rects.last_mut() {
+            if word.rect.min.y < last.max.y && word.rect.max.y > last.min.y &&
+               word.rect.min.x >= last.min.x {
+                last.absorb(&word.rect);
+                continue;
+            }
+        }
+        rects.push(word.rect);
+    }
+    rects
+}
+
+fn words_text(words: &[BoundedText]) -> String {
+    words.iter().map(|w| w.text.as_str()).collect::<Vec<&str>>().join(" ")
+}
+
+fn is_located(words: &[BoundedText], annot: &Annotation) -> bool {
+    annot.start <= annot.end && annot.end < words.len() &&
+    words_text(&words[annot.start..annot.end+1]) == annot.text
+}
+
+// The word indices of an annotation become stale when the document is laid out again.
+fn locate_annotations(annotations: &mut [Annotation], index: usize, words: &[BoundedText]) {
+    for annot in annotations.iter_mut().filter(|a| a.page == index) {
+        if is_located(words, annot) {
+            continue;
+        }
+        let len = annot.end.saturating_sub(annot.start);
+        let start = (0..words.len().saturating_sub(len))
+                        .find(|&i| words_text(&words[i..i+len+1]) == annot.text);
+        if let Some(start) = start {
+            annot.start = start;
+            annot.end = start + len;
+        }
+    }
+}
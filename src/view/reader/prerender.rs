@@ -0,0 +1,231 @@
+use std::thread;
+use std::collections::VecDeque;
+use std::sync::{Arc, Weak, Mutex, mpsc};
+use framebuffer::Pixmap;
+use document::Document;
+use metadata::{Margin, ZoomMode};
+use geom::Rectangle;
+use super::scaling_factor;
+
+// The maximum size, in bytes, of the rendered pages kept in memory.
+const CACHE_CAPACITY: usize = 32 * 1024 * 1024;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct PageKey {
+    index: usize,
+    scale: u32,
+    margin: [u32; 4],
+}
+
+impl PageKey {
+    pub fn new(index: usize, scale: f32, margin: &Margin) -> PageKey {
+        PageKey {
+            index,
+            scale: scale.to_bits(),
+            margin: [margin.top.to_bits(), margin.right.to_bits(),
+                     margin.bottom.to_bits(), margin.left.to_bits()],
+        }
+    }
+}
+
+// The least recently used pages are evicted first, once the total size of the pixmaps
+// exceeds *capacity* bytes. The last inserted page is always kept.
+pub struct PixmapCache {
+    capacity: usize,
+    size: usize,
+    entries: VecDeque<(PageKey, Arc<Pixmap>)>,
+}
+
+impl PixmapCache {
+    pub fn new(capacity: usize) -> PixmapCache {
+        PixmapCache {
+            capacity,
+            size: 0,
+            entries: VecDeque::new(),
+        }
+    }
+
+    pub fn get(&mut self, key: &PageKey) -> Option<Arc<Pixmap>> {
+        let position = self.entries.iter().position(|&(ref k, _)| k == key)?;
+        let entry = self.entries.remove(position)?;
+        let pixmap = Arc::clone(&entry.1);
+        self.entries.push_back(entry);
+        Some(pixmap)
+    }
+
+    pub fn contains(&self, key: &PageKey) -> bool {
+        self.entries.iter().any(|&(ref k, _)| k == key)
+    }
+
+    pub fn insert(&mut self, key: PageKey, pixmap: Arc<Pixmap>) {
+        if let Some(position) = self.entries.iter().position(|&(ref k, _)| *k == key) {
+            if let Some((_, old)) = self.entries.remove(position) {
+                self.size -= old.buf.len();
+            }
+        }
+        self.size += pixmap.buf.len();
+        self.entries.push_back((key, pixmap));
+        while self.size > self.capacity && self.entries.len() > 1 {
+            if let Some((_, old)) = self.entries.pop_front() {
+                self.size -= old.buf.len();
+            }
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.size = 0;
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Job {
+    pub index: usize,
+    pub rect: Rectangle,
+    pub margin: Margin,
+    pub zoom_mode: ZoomMode,
+}
+
+// Renders, in a background thread, the pages that are likely to be displayed next.
+pub struct Prerenderer {
+    cache: Arc<Mutex<PixmapCache>>,
+    sender: mpsc::Sender<Vec<Job>>,
+    last_index: usize,
+}
+
+impl Prerenderer {
+    // The thread only keeps a weak reference to the document: it ends with the reader.
+    pub fn new(doc: &Arc<Mutex<Box<Document>>>) -> Prerenderer {
+        let cache = Arc::new(Mutex::new(PixmapCache::new(CACHE_CAPACITY)));
+        let (sender, receiver) = mpsc::channel::<Vec<Job>>();
+        let cache2 = Arc::clone(&cache);
+        let doc2: Weak<Mutex<Box<Document>>> = Arc::downgrade(doc);
+
+        thread::spawn(move || {
+            let mut pending = None;
+
+            loop {
+                let jobs = match pending.take() {
+                    Some(jobs) => jobs,
+                    None => match receiver.recv() {
+                        Ok(jobs) => jobs,
+                        Err(_) => break,
+                    },
+                };
+
+                for job in jobs {
+                    // Only the latest request matters.
+                    while let Ok(jobs) = receiver.try_recv() {
+                        pending = Some(jobs);
+                    }
+
+                    if pending.is_some() {
+                        break;
+                    }
+
+                    let doc = match doc2.upgrade() {
+                        Some(doc) => doc,
+                        None => return,
+                    };
+                    let doc = doc.lock().unwrap();
+
+                    if job.index >= doc.pages_count() {
+                        continue;
+                    }
+
+                    let scale = scaling_factor(&job.rect, doc.as_ref(), job.index, &job.margin, job.zoom_mode);
+                    let key = PageKey::new(job.index, scale, &job.margin);
+
+                    if cache2.lock().unwrap().contains(&key) {
+                        continue;
+                    }
+
+                    if let Some(pixmap) = doc.pixmap(job.index, scale) {
+                        cache2.lock().unwrap().insert(key, Arc::new(pixmap));
+                    }
+                }
+            }
+        });
+
+        Prerenderer {
+            cache,
+            sender,
+            last_index: 0,
+        }
+    }
+
+    // Returns the given page, from the cache when it has already been rendered.
+    pub fn pixmap(&self, rect: &Rectangle, doc: &Document, index: usize, margin: &Margin, zoom_mode: ZoomMode) -> (Arc<Pixmap>, f32) {
+        let scale = scaling_factor(rect, doc, index, margin, zoom_mode);
+        let key = PageKey::new(index, scale, margin);
+
+        if let Some(pixmap) = self.cache.lock().unwrap().get(&key) {
+            return (pixmap, scale);
+        }
+
+        let pixmap = Arc::new(doc.pixmap(index, scale).unwrap());
+        self.cache.lock().unwrap().insert(key, Arc::clone(&pixmap));
+        (pixmap, scale)
+    }
+
+    // The pages following the current one are rendered first, unless the reader is moving backward.
+    pub fn request(&mut self, index: usize, mut next: Vec<Job>, mut previous: Vec<Job>) {
+        let jobs = if index < self.last_index {
+            previous.append(&mut next);
+            previous
+        } else {
+            next.append(&mut previous);
+            next
+        };
+        self.last_index = index;
+        self.sender.send(jobs).ok();
+    }
+
+    // Needs to be called when the content of the pages changes.
+    pub fn invalidate(&self) {
+        self.cache.lock().unwrap().clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pixmap() -> Arc<Pixmap> {
+        Arc::new(Pixmap { width: 1, height: 1, buf: vec![0] })
+    }
+
+    fn big_pixmap() -> Arc<Pixmap> {
+        Arc::new(Pixmap { width: 2, height: 2, buf: vec![0; 4] })
+    }
+
+    #[test]
+    fn test_pixmap_cache() {
+        let margin = Margin::default();
+        let keys: Vec<PageKey> = (0..3).map(|i| PageKey::new(i, 1.0, &margin)).collect();
+        let mut cache = PixmapCache::new(2);
+        cache.insert(keys[0], pixmap());
+        cache.insert(keys[1], pixmap());
+        assert!(cache.get(&keys[0]).is_some());
+        cache.insert(keys[2], pixmap());
+        assert!(cache.contains(&keys[0]));
+        assert!(!cache.contains(&keys[1]));
+        assert!(!cache.contains(&PageKey::new(0, 2.0, &margin)));
+    }
+
+    #[test]
+    fn test_pixmap_cache_size() {
+        let margin = Margin::default();
+        let keys: Vec<PageKey> = (0..3).map(|i| PageKey::new(i, 1.0, &margin)).collect();
+        let mut cache = PixmapCache::new(3);
+        cache.insert(keys[0], pixmap());
+        cache.insert(keys[1], pixmap());
+        cache.insert(keys[2], big_pixmap());
+        assert!(!cache.contains(&keys[0]));
+        assert!(!cache.contains(&keys[1]));
+        assert!(cache.contains(&keys[2]));
+        cache.insert(keys[0], pixmap());
+        assert!(cache.contains(&keys[0]));
+        assert!(!cache.contains(&keys[2]));
+    }
+}
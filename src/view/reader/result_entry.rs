@@ -0,0 +1,117 @@
+use device::CURRENT_DEVICE;
+use framebuffer::{Framebuffer, UpdateMode};
+use view::{View, Event, Hub, Bus};
+use view::reader::list_window::ListItem;
+use font::{MD_AUTHOR, MD_SIZE};
+use color::{TEXT_NORMAL, TEXT_INVERTED_HARD};
+use gesture::GestureEvent;
+use document::Snippet;
+use font::{Fonts, font_from_style};
+use geom::Rectangle;
+use app::Context;
+
+// A search result: its location, and the words around it.
+pub struct ResultEntry {
+    rect: Rectangle,
+    children: Vec<Box<View>>,
+    page: usize,
+    chapter: Option<String>,
+    snippet: Snippet,
+    active: bool,
+}
+
+impl ResultEntry {
+    pub fn new(rect: Rectangle, page: usize, chapter: Option<String>, snippet: Snippet) -> ResultEntry {
+        ResultEntry {
+            rect,
+            children: vec![],
+            page,
+            chapter,
+            snippet,
+            active: false,
+        }
+    }
+}
+
+// A search result is a page and a snippet.
+impl ListItem for (usize, Snippet) {
+    fn page(&self) -> usize {
+        self.0
+    }
+
+    fn entry(&self, rect: Rectangle, chapter: Option<String>) -> Box<View> {
+        Box::new(ResultEntry::new(rect, self.0, chapter, self.1.clone())) as Box<View>
+    }
+}
+
+impl View for ResultEntry {
+    fn handle_event(&mut self, evt: &Event, hub: &Hub, bus: &mut Bus, _context: &mut Context) -> bool {
+        match *evt {
+            Event::Gesture(GestureEvent::Tap(ref center)) if self.rect.includes(center) => {
+                self.active = true;
+                hub.send(Event::Render(self.rect, UpdateMode::Gui)).unwrap();
+                bus.push_back(Event::GoTo(self.page));
+                true
+            },
+            _ => false,
+        }
+    }
+
+    fn render(&self, fb: &mut Framebuffer, fonts: &mut Fonts) {
+        let dpi = CURRENT_DEVICE.dpi;
+
+        let scheme = if self.active {
+            TEXT_INVERTED_HARD
+        } else {
+            TEXT_NORMAL
+        };
+
+        fb.draw_rectangle(&self.rect, scheme[0]);
+
+        let (x_height, padding, baseline) = {
+            let font = font_from_style(fonts, &MD_AUTHOR, dpi);
+            let x_height = font.x_heights.0 as i32;
+            (x_height, font.em() as i32, (self.rect.height() as i32 - 2 * x_height) / 3)
+        };
+
+        let width = self.rect.width() as i32 - 2 * padding;
+
+        // Location
+        {
+            let location = match self.chapter {
+                Some(ref title) => format!("{} · {}", self.page + 1, title),
+                None => (self.page + 1).to_string(),
+            };
+            let font = font_from_style(fonts, &MD_SIZE, dpi);
+            let plan = font.plan(&location, Some(width as u32), None);
+            let pt = self.rect.min + pt!(padding, baseline + x_height);
+            font.render(fb, scheme[2], &plan, &pt);
+        }
+
+        // Snippet
+        {
+            let font = font_from_style(fonts, &MD_AUTHOR, dpi);
+            // Without ligatures, the glyphs match the characters.
+            let mut plan = font.plan(&self.snippet.text, None, Some("-liga"));
+            font.crop_around(&mut plan, self.snippet.start, width as u32);
+            let pt = pt!(self.rect.min.x + padding, self.rect.max.y - baseline);
+            font.render(fb, scheme[1], &plan, &pt);
+        }
+    }
+
+    fn rect(&self) -> &Rectangle {
+        &self.rect
+    }
+
+    fn rect_mut(&mut self) -> &mut Rectangle {
+        &mut self.rect
+    }
+
+    fn children(&self) -> &Vec<Box<View>> {
+        &self.children
+    }
+
+    fn children_mut(&mut self) -> &mut Vec<Box<View>> {
+        &mut self.children
+    }
+}
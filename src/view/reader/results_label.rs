@@ -3,7 +3,8 @@ use font::{Fonts, font_from_style, NORMAL_STYLE};
 use framebuffer::{Framebuffer, UpdateMode};
 use color::TEXT_NORMAL;
 use geom::{Rectangle};
-use view::{View, Event, Hub, Bus};
+use view::{View, Event, Hub, Bus, ViewId};
+use gesture::GestureEvent;
 use app::Context;
 
 pub struct ResultsLabel {
@@ -45,8 +46,12 @@ impl ResultsLabel {
 
 
 impl View for ResultsLabel {
-    fn handle_event(&mut self, evt: &Event, hub: &Hub, _bus: &mut Bus, _context: &mut Context) -> bool {
+    fn handle_event(&mut self, evt: &Event, hub: &Hub, bus: &mut Bus, _context: &mut Context) -> bool {
         match *evt {
+            Event::Gesture(GestureEvent::Tap(ref center)) if self.rect.includes(center) => {
+                bus.push_back(Event::Toggle(ViewId::SearchResults));
+                true
+            },
             Event::EndOfSearch => {
                 self.completed = true;
                 hub.send(Event::Render(self.rect, UpdateMode::Gui)).unwrap();
@@ -0,0 +1,403 @@
+use std::fs::{self, File};
+use std::thread;
+use std::path::{Path, PathBuf};
+use std::hash::{Hash, Hasher};
+use std::collections::BTreeSet;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use fnv::{FnvHashMap, FnvHasher};
+use png::{self, HasParameters};
+use failure::{Error, ResultExt};
+use device::CURRENT_DEVICE;
+use framebuffer::{Framebuffer, UpdateMode, Pixmap};
+use geom::{Rectangle, CornerSpec, BorderSpec, Dir};
+use font::{Fonts, font_from_style, MD_SIZE};
+use view::{View, Event, Hub, Bus, ViewId, Align};
+use view::{THICKNESS_SMALL, THICKNESS_LARGE, BORDER_RADIUS_MEDIUM};
+use view::popup::Popup;
+use document::{Document, Typography, fingerprint};
+use gesture::GestureEvent;
+use color::{BLACK, WHITE, GRAY08};
+use unit::{scale_by_dpi, mm_to_in};
+use app::Context;
+
+const THUMBNAILS_CACHE_DIR: &str = "cache/thumbnails";
+const LABEL_THUMBNAILS: &str = "Pages";
+// The number of books whose thumbnails are kept in the cache.
+const MAX_CACHED_BOOKS: usize = 32;
+// The number of columns of the grid, for the portrait and landscape orientations.
+const PORTRAIT_COLUMNS: usize = 3;
+const LANDSCAPE_COLUMNS: usize = 5;
+// The height of the frames of the thumbnails, relative to their width.
+const FRAME_RATIO: f32 = 1.4;
+
+// A grid of thumbnails of the pages of the document.
+//
+// The thumbnails of a page of the grid are rendered in a background thread when it is shown,
+// and saved in a cache directory specific to the book, its layout and the size of the thumbnails.
+pub struct ThumbnailGrid {
+    popup: Popup,
+    children: Vec<Box<View>>,
+    doc: Arc<Mutex<Box<Document>>>,
+    cache_dir: Option<PathBuf>,
+    thumbnails: Arc<Mutex<FnvHashMap<usize, Pixmap>>>,
+    running: Arc<AtomicBool>,
+    pages_count: usize,
+    current_page: usize,
+    bookmarks: BTreeSet<usize>,
+    columns: usize,
+    rows: usize,
+    // The height reserved for the page numbers, below the thumbnails.
+    label_height: i32,
+    grid_page: usize,
+}
+
+impl ThumbnailGrid {
+    // The *layout* of reflowable documents is given by their font size and typography.
+    pub fn new(doc: &Arc<Mutex<Box<Document>>>, path: &Path, layout: Option<(f32, Typography)>, current_page: usize, bookmarks: BTreeSet<usize>, hub: &Hub, context: &mut Context) -> ThumbnailGrid {
+        let dpi = CURRENT_DEVICE.dpi;
+        let (width, height) = CURRENT_DEVICE.display_dims();
+        let popup = Popup::full_screen(ViewId::Thumbnails, &mut context.fonts);
+        let children = popup.title_bar(LABEL_THUMBNAILS.to_string(), Align::Center, None);
+
+        let pages_count = doc.lock().unwrap().pages_count();
+        let label_height = {
+            let font = font_from_style(&mut context.fonts, &MD_SIZE, dpi);
+            3 * font.x_heights.0 as i32
+        };
+
+        let mut thumbnail_grid = ThumbnailGrid {
+            popup,
+            children,
+            doc: Arc::clone(doc),
+            cache_dir: None,
+            thumbnails: Arc::new(Mutex::new(FnvHashMap::default())),
+            running: Arc::new(AtomicBool::new(false)),
+            pages_count,
+            current_page,
+            bookmarks,
+            columns: if width < height { PORTRAIT_COLUMNS } else { LANDSCAPE_COLUMNS },
+            rows: 1,
+            label_height,
+            grid_page: 0,
+        };
+
+        let body_rect = thumbnail_grid.body_rect();
+        let cell_width = body_rect.width() as usize / thumbnail_grid.columns;
+        let cell_height = (cell_width as f32 * FRAME_RATIO) as usize + label_height as usize;
+        thumbnail_grid.rows = (body_rect.height() as usize / cell_height).max(1);
+        thumbnail_grid.grid_page = current_page / thumbnail_grid.capacity();
+
+        let frame = thumbnail_grid.frame_rect(0);
+        thumbnail_grid.cache_dir = cache_dir(path, layout.as_ref(), pages_count, &frame);
+
+        if let Some(ref dir) = thumbnail_grid.cache_dir {
+            if !dir.exists() {
+                if let Err(e) = prune_cache(MAX_CACHED_BOOKS - 1) {
+                    eprintln!("Can't prune the thumbnails cache: {}", e);
+                }
+            }
+        }
+
+        thumbnail_grid.update_label(hub);
+        thumbnail_grid.render_thumbnails(hub);
+        hub.send(Event::Render(thumbnail_grid.popup.rect, UpdateMode::Gui)).unwrap();
+
+        thumbnail_grid
+    }
+
+    fn body_rect(&self) -> Rectangle {
+        self.popup.body_rect()
+    }
+
+    fn capacity(&self) -> usize {
+        self.columns * self.rows
+    }
+
+    fn grid_pages_count(&self) -> usize {
+        ((self.pages_count + self.capacity() - 1) / self.capacity()).max(1)
+    }
+
+    // The cell of the *i*-th thumbnail of the current page of the grid.
+    fn cell_rect(&self, i: usize) -> Rectangle {
+        let body_rect = self.body_rect();
+        let cell_width = body_rect.width() as i32 / self.columns as i32;
+        let cell_height = body_rect.height() as i32 / self.rows as i32;
+        let x = body_rect.min.x + (i % self.columns) as i32 * cell_width;
+        let y = body_rect.min.y + (i / self.columns) as i32 * cell_height;
+        rect![x, y, x + cell_width, y + cell_height]
+    }
+
+    // The space reserved for the thumbnail within its cell, above the page number.
+    fn frame_rect(&self, i: usize) -> Rectangle {
+        let dpi = CURRENT_DEVICE.dpi;
+        let cell_rect = self.cell_rect(i);
+        let padding = scale_by_dpi(BORDER_RADIUS_MEDIUM, dpi) as i32;
+        rect![cell_rect.min.x + padding,
+              cell_rect.min.y + padding,
+              cell_rect.max.x - padding,
+              cell_rect.max.y - self.label_height]
+    }
+
+    // Renders, or loads from the cache, the missing thumbnails of the current page of the grid.
+    // Only the thumbnails of the current page are kept in memory.
+    fn render_thumbnails(&mut self, hub: &Hub) {
+        self.running.store(false, Ordering::Relaxed);
+
+        let start = self.grid_page * self.capacity();
+        let end = (start + self.capacity()).min(self.pages_count);
+        let frame = self.frame_rect(0);
+        let jobs: Vec<(usize, Rectangle)> = {
+            let mut thumbnails = self.thumbnails.lock().unwrap();
+            thumbnails.retain(|index, _| *index >= start && *index < end);
+            (start..end).filter(|index| !thumbnails.contains_key(index))
+                        .map(|index| (index, self.cell_rect(index - start)))
+                        .collect()
+        };
+
+        if jobs.is_empty() {
+            return;
+        }
+
+        let running = Arc::new(AtomicBool::new(true));
+        self.running = Arc::clone(&running);
+        let doc = Arc::clone(&self.doc);
+        let thumbnails = Arc::clone(&self.thumbnails);
+        let cache_dir = self.cache_dir.clone();
+        let hub2 = hub.clone();
+
+        thread::spawn(move || {
+            let (max_width, max_height) = (frame.width() as f32, frame.height() as f32);
+
+            for (index, cell_rect) in jobs {
+                if !running.load(Ordering::Relaxed) {
+                    break;
+                }
+
+                let path = cache_dir.as_ref().map(|dir| dir.join(format!("{}.png", index)));
+                let mut pixmap = path.as_ref().and_then(|p| load_pixmap(p).ok());
+
+                if pixmap.is_none() {
+                    let doc = doc.lock().unwrap();
+                    pixmap = doc.dims(index).and_then(|(width, height)| {
+                        let scale = (max_width / width).min(max_height / height);
+                        doc.pixmap(index, scale)
+                    });
+                    if let (Some(pixmap), Some(path)) = (pixmap.as_ref(), path.as_ref()) {
+                        if let Err(e) = save_pixmap(pixmap, path) {
+                            eprintln!("Can't save thumbnail: {}", e);
+                        }
+                    }
+                }
+
+                if let Some(pixmap) = pixmap {
+                    let mut thumbnails = thumbnails.lock().unwrap();
+                    // The grid might have been paged in the meantime.
+                    if !running.load(Ordering::Relaxed) {
+                        break;
+                    }
+                    thumbnails.insert(index, pixmap);
+                    hub2.send(Event::Render(cell_rect, UpdateMode::Gui)).unwrap();
+                }
+            }
+        });
+    }
+
+    fn update_label(&mut self, hub: &Hub) {
+        let grid_pages_count = self.grid_pages_count();
+        self.popup.update_title(&mut self.children, LABEL_THUMBNAILS,
+                                self.grid_page, grid_pages_count, hub);
+    }
+
+    fn set_grid_page(&mut self, dir: Dir, hub: &Hub) {
+        let index = match dir {
+            Dir::West if self.grid_page + 1 < self.grid_pages_count() => self.grid_page + 1,
+            Dir::East if self.grid_page > 0 => self.grid_page - 1,
+            _ => return,
+        };
+        self.grid_page = index;
+        self.update_label(hub);
+        self.render_thumbnails(hub);
+        hub.send(Event::Render(self.body_rect(), UpdateMode::Gui)).unwrap();
+    }
+}
+
+// The thumbnails depend on the content of the file, its layout, its pagination and their size.
+fn cache_dir(path: &Path, layout: Option<&(f32, Typography)>, pages_count: usize, frame: &Rectangle) -> Option<PathBuf> {
+    let file = File::open(path).ok()?;
+    let mut hasher = FnvHasher::default();
+    fingerprint(path, &file).ok()?.hash(&mut hasher);
+    if let Some(&(font_size, ref typography)) = layout {
+        CURRENT_DEVICE.display_dims().hash(&mut hasher);
+        font_size.to_bits().hash(&mut hasher);
+        typography.font_family.hash(&mut hasher);
+        typography.line_height.map(f32::to_bits).hash(&mut hasher);
+        typography.margin_width.hash(&mut hasher);
+        typography.text_align.hash(&mut hasher);
+        typography.hyphenation.hash(&mut hasher);
+    }
+    pages_count.hash(&mut hasher);
+    (frame.width(), frame.height()).hash(&mut hasher);
+    Some(Path::new(THUMBNAILS_CACHE_DIR).join(format!("{:016x}", hasher.finish())))
+}
+
+// Removes the least recently updated directories of the cache, until at most *max_count* remain.
+fn prune_cache(max_count: usize) -> Result<(), Error> {
+    let dir = Path::new(THUMBNAILS_CACHE_DIR);
+    if !dir.exists() {
+        return Ok(());
+    }
+    let mut entries = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let modified = entry.metadata()?.modified()?;
+        entries.push((modified, entry.path()));
+    }
+    if entries.len() <= max_count {
+        return Ok(());
+    }
+    entries.sort();
+    let count = entries.len() - max_count;
+    for (_, path) in entries.into_iter().take(count) {
+        fs::remove_dir_all(&path).context("Can't remove directory.")?;
+    }
+    Ok(())
+}
+
+fn load_pixmap(path: &Path) -> Result<Pixmap, Error> {
+    let file = File::open(path)?;
+    let decoder = png::Decoder::new(file);
+    let (info, mut reader) = decoder.read_info().context("Can't read header.")?;
+    if info.color_type != png::ColorType::Grayscale || info.bit_depth != png::BitDepth::Eight {
+        return Err(format_err!("Unexpected color type."));
+    }
+    let mut buf = vec![0; info.buffer_size()];
+    reader.next_frame(&mut buf).context("Can't read data.")?;
+    Ok(Pixmap { width: info.width as i32, height: info.height as i32, buf })
+}
+
+fn save_pixmap(pixmap: &Pixmap, path: &Path) -> Result<(), Error> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let file = File::create(path).context("Can't create output file.")?;
+    let mut encoder = png::Encoder::new(file, pixmap.width as u32, pixmap.height as u32);
+    encoder.set(png::ColorType::Grayscale).set(png::BitDepth::Eight);
+    let mut writer = encoder.write_header().context("Can't write header.")?;
+    writer.write_image_data(&pixmap.buf).context("Can't write data to file.")?;
+    Ok(())
+}
+
+impl Drop for ThumbnailGrid {
+    fn drop(&mut self) {
+        self.running.store(false, Ordering::Relaxed);
+    }
+}
+
+impl View for ThumbnailGrid {
+    fn handle_event(&mut self, evt: &Event, hub: &Hub, bus: &mut Bus, _context: &mut Context) -> bool {
+        match *evt {
+            Event::Gesture(GestureEvent::Tap(ref center)) if !self.popup.rect.includes(center) => {
+                hub.send(Event::Close(ViewId::Thumbnails)).unwrap();
+                true
+            },
+            Event::Gesture(GestureEvent::Tap(ref center)) if self.body_rect().includes(center) => {
+                let start = self.grid_page * self.capacity();
+                let end = (start + self.capacity()).min(self.pages_count);
+                if let Some(index) = (start..end).find(|&index| self.cell_rect(index - start).includes(center)) {
+                    hub.send(Event::Close(ViewId::Thumbnails)).unwrap();
+                    bus.push_back(Event::GoTo(index));
+                }
+                true
+            },
+            Event::Gesture(GestureEvent::Swipe { dir, ref start, .. }) if self.popup.rect.includes(start) => {
+                self.set_grid_page(dir, hub);
+                true
+            },
+            Event::Gesture(..) => true,
+            _ => false,
+        }
+    }
+
+    fn render(&self, fb: &mut Framebuffer, fonts: &mut Fonts) {
+        let dpi = CURRENT_DEVICE.dpi;
+        let border_thickness = scale_by_dpi(THICKNESS_LARGE, dpi) as u16;
+        let frame_thickness = scale_by_dpi(THICKNESS_SMALL, dpi) as u16;
+
+        self.popup.render(fb);
+
+        let start = self.grid_page * self.capacity();
+        let end = (start + self.capacity()).min(self.pages_count);
+        let thumbnails = self.thumbnails.lock().unwrap();
+        let font = font_from_style(fonts, &MD_SIZE, dpi);
+
+        for index in start..end {
+            let cell_rect = self.cell_rect(index - start);
+            let frame = self.frame_rect(index - start);
+
+            // The thumbnail is centered in its frame, a gray frame stands for the missing ones.
+            let thumbnail_rect = if let Some(pixmap) = thumbnails.get(&index) {
+                let dx = (frame.width() as i32 - pixmap.width) / 2;
+                let dy = (frame.height() as i32 - pixmap.height) / 2;
+                let pt = frame.min + pt!(dx, dy);
+                fb.draw_pixmap(pixmap, &pt);
+                rect![pt, pt + pt!(pixmap.width, pixmap.height)]
+            } else {
+                fb.draw_rectangle_outline(&frame, &BorderSpec { thickness: frame_thickness,
+                                                                 color: GRAY08 });
+                frame
+            };
+
+            // The current page is framed.
+            if index == self.current_page {
+                let thickness = border_thickness as i32;
+                fb.draw_rectangle_outline(&rect![thumbnail_rect.min - thickness,
+                                                 thumbnail_rect.max + thickness],
+                                          &BorderSpec { thickness: border_thickness,
+                                                        color: BLACK });
+            }
+
+            // Bookmarked pages carry the same mark as in the reader.
+            if self.bookmarks.contains(&index) {
+                let thickness = frame_thickness;
+                let radius = (mm_to_in(0.4) * dpi as f32) as i32 + thickness as i32;
+                let center = pt!(thumbnail_rect.max.x - 2 * radius,
+                                 thumbnail_rect.min.y + 2 * radius);
+                fb.draw_rounded_rectangle_with_border(&Rectangle::from_disk(&center, radius),
+                                                      &CornerSpec::Uniform(radius),
+                                                      &BorderSpec { thickness, color: WHITE },
+                                                      &BLACK);
+            }
+
+            let plan = font.plan(&(index + 1).to_string(), None, None);
+            let dx = (cell_rect.width() as i32 - plan.width as i32) / 2;
+            let dy = (cell_rect.max.y - frame.max.y - font.x_heights.0 as i32) / 2;
+            let pt = pt!(cell_rect.min.x + dx, cell_rect.max.y - dy);
+            font.render(fb, BLACK, &plan, &pt);
+        }
+    }
+
+    fn is_background(&self) -> bool {
+        true
+    }
+
+    fn rect(&self) -> &Rectangle {
+        &self.popup.rect
+    }
+
+    fn rect_mut(&mut self) -> &mut Rectangle {
+        &mut self.popup.rect
+    }
+
+    fn children(&self) -> &Vec<Box<View>> {
+        &self.children
+    }
+
+    fn children_mut(&mut self) -> &mut Vec<Box<View>> {
+        &mut self.children
+    }
+
+    fn id(&self) -> Option<ViewId> {
+        Some(ViewId::Thumbnails)
+    }
+}
@@ -22,9 +22,10 @@ impl ToolBar {
         let side = rect.height() as i32;
 
         if is_reflowable {
+            let font_size_rect = rect![rect.min, rect.min + pt!(side)];
             let font_size_icon = Icon::new("font_size",
-                                           rect![rect.min, rect.min + pt!(side)],
-                                           Event::Show(ViewId::FontSizeMenu));
+                                           font_size_rect,
+                                           Event::ToggleNear(ViewId::TypographyMenu, font_size_rect));
             children.push(Box::new(font_size_icon) as Box<View>);
 
             let slider = Slider::new(rect![rect.min.x + side, rect.min.y,
@@ -40,7 +41,14 @@ impl ToolBar {
                                       Event::Show(ViewId::MarginCropper));
             children.push(Box::new(crop_icon) as Box<View>);
 
-            let filler = Filler::new(rect![rect.min.x + side, rect.min.y,
+            let zoom_rect = rect![rect.min.x + side, rect.min.y,
+                                  rect.min.x + 2 * side, rect.max.y];
+            let zoom_icon = Icon::new("zoom",
+                                      zoom_rect,
+                                      Event::ToggleNear(ViewId::ZoomMenu, zoom_rect));
+            children.push(Box::new(zoom_icon) as Box<View>);
+
+            let filler = Filler::new(rect![rect.min.x + 2 * side, rect.min.y,
                                            rect.max.x - 2 * side, rect.max.y],
                                      WHITE);
             children.push(Box::new(filler) as Box<View>);